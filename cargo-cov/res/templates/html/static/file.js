@@ -111,3 +111,65 @@ sourceElement.onmouseout = clearArrow;
 if (history.replaceState) {
     sourceElement.onclick = followArrow;
 }
+
+
+/** @param {MouseEvent} e */
+function switchFunctionView(e) {
+    var target = e.target;
+    var view = target.getAttribute('data-view');
+    if (!view) {
+        return;
+    }
+    var buttons = functionViewElement.getElementsByTagName('button');
+    for (var i = 0; i < buttons.length; ++ i) {
+        var button = buttons[i];
+        var selected = button === target;
+        button.className = selected ? 'active' : '';
+        document.getElementById(button.getAttribute('data-view')).hidden = !selected;
+    }
+}
+
+var functionViewElement = document.getElementById('function-view');
+functionViewElement.onclick = switchFunctionView;
+
+
+// Mermaid is not bundled with the report. If `mermaid.min.js` is copied next to this script, the flowcharts are drawn
+// by it, otherwise they are shown as text, so the report never depends on a remote server.
+var MERMAID_URL = document.currentScript.src.replace(/[^\/]*$/, 'mermaid.min.js');
+var mermaidPromise = null;
+
+/** Loads Mermaid on first use. If it cannot be loaded, the flowcharts are shown as text. */
+function loadMermaid() {
+    if (!mermaidPromise) {
+        mermaidPromise = new Promise(function (resolve, reject) {
+            var script = document.createElement('script');
+            script.src = MERMAID_URL;
+            script.onload = function () {
+                window.mermaid.initialize({startOnLoad: false});
+                resolve(window.mermaid);
+            };
+            script.onerror = reject;
+            document.head.appendChild(script);
+        });
+    }
+    return mermaidPromise;
+}
+
+/** @param {MouseEvent} e */
+function toggleCfg(e) {
+    var target = e.target;
+    if (!/\bcfg-toggle\b/.test(target.className)) {
+        return;
+    }
+    var cfg = target.nextElementSibling;
+    cfg.hidden = !cfg.hidden;
+    if (!cfg.hidden && !cfg.hasAttribute('data-processed')) {
+        loadMermaid().then(function (mermaid) {
+            mermaid.run({nodes: [cfg]});
+        }, function () {
+            cfg.setAttribute('data-processed', 'text');
+        });
+    }
+}
+
+document.getElementById('function-instances').onclick = toggleCfg;
@@ -1,14 +1,19 @@
 //! Extra functions for command line argument parsing.
 
-use error::Result;
+use cargo::Package;
+use error::{ErrorKind, Result, ResultExt};
 use sourcepath::SourceType;
 use utils::{join_3, parent_3};
 
 use clap::ArgMatches;
+use cov::ReportOptions;
+use glob::Pattern;
 
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::env;
 use std::ffi::OsStr;
+use std::mem;
 use std::path::{Path, PathBuf};
 
 lazy_static! {
@@ -19,6 +24,29 @@ lazy_static! {
         "manifest-path",
         "target",
         "profiler",
+        "runner",
+        "gcov-prefix",
+        "gcda-sysroot",
+        "backend",
+        "configuration",
+    ].iter().cloned().collect();
+
+    /// The list of special flags, i.e. special arguments which take no values. See [`normalize()`] for detail.
+    ///
+    /// [`normalize()`]: ./fn.normalize.html
+    static ref SPECIAL_FLAGS: HashSet<&'static str> = [
+        "per-test",
+        "feature-matrix",
+    ].iter().cloned().collect();
+
+    /// The list of libtest flags that take a value (i.e. of the form `--foo bar`).
+    static ref LIBTEST_FLAGS_WITH_VALUES: HashSet<&'static str> = [
+        "--color",
+        "--format",
+        "--logfile",
+        "--skip",
+        "--test-threads",
+        "-Z",
     ].iter().cloned().collect();
 
     /// The list of `rustc` flags that take a value (i.e. of the form `--foo bar`).
@@ -79,6 +107,11 @@ pub type SpecialMap<'a> = HashMap<&'static str, &'a OsStr>;
 /// * `--manifest-path`
 /// * `--target`
 /// * `--profiler`
+/// * `--runner`
+/// * `--gcov-prefix`
+/// * `--gcda-sysroot`
+/// * `--backend`
+/// * `--configuration`
 pub fn update_from_clap<'a>(matches: &'a ArgMatches, specialized: &mut SpecialMap<'a>) {
     for name in SPECIALS.iter() {
         if let Some(value) = matches.value_of_os(name) {
@@ -114,14 +147,199 @@ pub fn is_rustc_compiling_local_crate<'a, I: IntoIterator<Item = &'a OsStr>>(arg
     false
 }
 
+/// Kinds of crates which are compiled and then executed on the host during the build, i.e. build scripts and
+/// procedural macros. Their GCNO files are stored in the subfolder `[cov_build_path]/gcno/«kind»/`.
+pub const HOST_CRATE_KINDS: &[&str] = &["build-script", "proc-macro"];
+
+/// Finds out whether `rustc` is building a build script or a procedural macro crate from its arguments. Returns the
+/// corresponding entry in [`HOST_CRATE_KINDS`].
+///
+/// [`HOST_CRATE_KINDS`]: ./constant.HOST_CRATE_KINDS.html
+pub fn rustc_host_crate_kind<'a, I: IntoIterator<Item = &'a OsStr>>(args: I) -> Option<&'static str> {
+    let mut prev_arg = None;
+    for arg in args {
+        match (prev_arg, arg.to_str()) {
+            (Some("--crate-type"), Some("proc-macro")) => return Some(HOST_CRATE_KINDS[1]),
+            (Some("--crate-name"), Some(name)) if name.starts_with("build_script_") => return Some(HOST_CRATE_KINDS[0]),
+            _ => {},
+        }
+        prev_arg = arg.to_str();
+    }
+    None
+}
+
+#[test]
+fn test_rustc_host_crate_kind() {
+    let args = ["--crate-name", "build_script_build", "build.rs", "--crate-type", "bin"];
+    assert_eq!(rustc_host_crate_kind(args.iter().map(OsStr::new)), Some("build-script"));
+    let args = ["--crate-name", "derive_foo", "src/lib.rs", "--crate-type", "proc-macro"];
+    assert_eq!(rustc_host_crate_kind(args.iter().map(OsStr::new)), Some("proc-macro"));
+    let args = ["--crate-name", "foo", "src/lib.rs", "--crate-type", "lib"];
+    assert_eq!(rustc_host_crate_kind(args.iter().map(OsStr::new)), None);
+}
+
+/// Finds out the path of the executable `rustc` is going to produce from its arguments, i.e.
+/// `«out-dir»/«crate-name»«extra-filename»`. Returns `None` if `rustc` is not building a binary or test crate.
+pub fn rustc_output_executable<'a, I: IntoIterator<Item = &'a OsStr>>(args: I) -> Option<PathBuf> {
+    let mut prev_arg = None;
+    let mut is_executable = false;
+    let mut crate_name = None;
+    let mut out_dir = None;
+    let mut extra_filename = "";
+    for arg in args {
+        match (prev_arg, arg.to_str()) {
+            (_, Some("--test")) | (Some("--crate-type"), Some("bin")) => is_executable = true,
+            (Some("--crate-name"), Some(name)) => crate_name = Some(name),
+            (Some("--out-dir"), _) => out_dir = Some(Path::new(arg)),
+            (Some("-C"), Some(codegen)) if codegen.starts_with("extra-filename=") => extra_filename = &codegen[15..],
+            _ => {},
+        }
+        prev_arg = arg.to_str();
+    }
+
+    if !is_executable {
+        return None;
+    }
+    let file_name = format!("{}{}{}", crate_name?, extra_filename, env::consts::EXE_SUFFIX);
+    Some(out_dir?.join(file_name))
+}
+
+#[test]
+fn test_rustc_output_executable() {
+    let args = ["--crate-name", "foo", "src/main.rs", "--crate-type", "bin", "-C", "extra-filename=-1a2b", "--out-dir", "/t/debug/deps"];
+    let expected = format!("/t/debug/deps/foo-1a2b{}", env::consts::EXE_SUFFIX);
+    assert_eq!(rustc_output_executable(args.iter().map(OsStr::new)), Some(PathBuf::from(expected)));
+    let args = ["--crate-name", "foo", "src/lib.rs", "--test", "--out-dir", "/t/debug/deps"];
+    let expected = format!("/t/debug/deps/foo{}", env::consts::EXE_SUFFIX);
+    assert_eq!(rustc_output_executable(args.iter().map(OsStr::new)), Some(PathBuf::from(expected)));
+    let args = ["--crate-name", "foo", "src/lib.rs", "--crate-type", "lib", "--out-dir", "/t/debug/deps"];
+    assert_eq!(rustc_output_executable(args.iter().map(OsStr::new)), None);
+}
+
+/// Finds out whether the debuginfo level is already given in the `rustc` arguments, by `-g` or `-C debuginfo=«level»`.
+/// Benchmarks are built in the `bench` profile, which has no debuginfo by default.
+pub fn rustc_has_debuginfo<'a, I: IntoIterator<Item = &'a OsStr>>(args: I) -> bool {
+    args.into_iter().any(|arg| arg.to_str().map_or(false, |s| s == "-g" || s.contains("debuginfo=")))
+}
+
+#[test]
+fn test_rustc_has_debuginfo() {
+    let args = ["--crate-name", "bench", "benches/bench.rs", "--test", "-C", "opt-level=3"];
+    assert!(!rustc_has_debuginfo(args.iter().map(OsStr::new)));
+    let args = ["--crate-name", "demo", "examples/demo.rs", "--crate-type", "bin", "-C", "debuginfo=2"];
+    assert!(rustc_has_debuginfo(args.iter().map(OsStr::new)));
+    let args = ["--crate-name", "demo", "examples/demo.rs", "-Cdebuginfo=0"];
+    assert!(rustc_has_debuginfo(args.iter().map(OsStr::new)));
+    let args = ["--crate-name", "demo", "examples/demo.rs", "-g"];
+    assert!(rustc_has_debuginfo(args.iter().map(OsStr::new)));
+}
+
+/// Parses a line range of the form `START-END` or `LINE`.
+pub fn parse_line_range(range: &str) -> Option<(u32, u32)> {
+    let mut parts = range.splitn(2, '-');
+    let start = parts.next()?.trim().parse().ok()?;
+    let end = match parts.next() {
+        Some(end) => end.trim().parse().ok()?,
+        None => start,
+    };
+    if start <= end {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_parse_line_range() {
+    assert_eq!(parse_line_range("10-20"), Some((10, 20)));
+    assert_eq!(parse_line_range("7"), Some((7, 7)));
+    assert_eq!(parse_line_range("20-10"), None);
+    assert_eq!(parse_line_range("a-b"), None);
+}
+
+/// Splits a command line like `qemu-arm -L '/opt/my sysroot'` into words, following the quoting rules of the shell.
+///
+/// Words are separated by whitespace, unless quoted by `'…'` or `"…"`. Outside of single quotes, a backslash escapes
+/// the following whitespace, quote or backslash. Other backslashes are kept, so Windows paths can be written as is.
+/// Returns `None` if a quote is not closed.
+pub fn split_shell_words(command: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut has_word = false;
+    let mut quote = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => word.push(c),
+            (_, '\\') => match chars.peek().cloned() {
+                Some(next) if next == '\\' || next == '"' || (quote.is_none() && (next == '\'' || next.is_whitespace())) => {
+                    word.push(next);
+                    chars.next();
+                },
+                _ => word.push(c),
+            },
+            (Some(_), _) => word.push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                has_word = true;
+            },
+            (None, _) if c.is_whitespace() => if has_word || !word.is_empty() {
+                words.push(mem::replace(&mut word, String::new()));
+                has_word = false;
+            },
+            (None, _) => word.push(c),
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    if has_word || !word.is_empty() {
+        words.push(word);
+    }
+    Some(words)
+}
+
+#[test]
+fn test_split_shell_words() {
+    let words = |words: &[&str]| Some(words.iter().map(|w| w.to_string()).collect::<Vec<_>>());
+    assert_eq!(split_shell_words("qemu-arm -L /usr/arm-linux-gnueabihf"), words(&["qemu-arm", "-L", "/usr/arm-linux-gnueabihf"]));
+    assert_eq!(split_shell_words(r#"  'runner x' "a \"b\" c" d\ e '' "#), words(&["runner x", r#"a "b" c"#, "d e", ""]));
+    assert_eq!(split_shell_words(r"C:\tools\run.exe --flag"), words(&[r"C:\tools\run.exe", "--flag"]));
+    assert_eq!(split_shell_words("it's"), None);
+    assert_eq!(split_shell_words(""), words(&[]));
+}
+
+/// Removes the test name filters from the arguments passed to a libtest program, keeping only the options.
+pub fn strip_libtest_filters<'a, I: IntoIterator<Item = &'a OsStr>>(args: I) -> Vec<&'a OsStr> {
+    let mut options = Vec::new();
+    let mut keep_next = false;
+    for arg in args {
+        if keep_next {
+            keep_next = false;
+        } else if let Some(s) = arg.to_str() {
+            if LIBTEST_FLAGS_WITH_VALUES.contains(s) {
+                keep_next = true;
+            } else if !s.starts_with('-') {
+                continue;
+            }
+        }
+        options.push(arg);
+    }
+    options
+}
+
 /// Extracts *special arguments* from the iterator of arguments.
 ///
 /// The values will be inserted to the `specialized` map. Remaining arguments are returned as a vector.
 ///
-/// The meaning of special arguments is described in [`update_from_clap()`]. When used in the `build`, `run` and `test`
-/// external subcommands, `clap` will be distinguish the special arguments from the rest that are forwarded to the
-/// corresponding `cargo` subcommand. This function performs the second command-line argument to partition these special
-/// arguments from all other arguments.
+/// The meaning of special arguments is described in [`update_from_clap()`]. When used in the `build`, `test`, `bench`,
+/// `run` and `exec` external subcommands, `clap` will be distinguish the special arguments from the rest that are
+/// forwarded to the corresponding `cargo` subcommand. This function performs the second command-line argument to
+/// partition these special arguments from all other arguments.
+///
+/// Special flags such as `--per-test` and `--feature-matrix` are also extracted. They are inserted into the `specialized`
+/// map with an empty value.
 ///
 /// [`update_from_clap()`]: ./fn.update_from_clap.html
 pub fn normalize<'a, I: IntoIterator<Item = &'a OsStr>>(args: I, specialized: &mut SpecialMap<'a>) -> Vec<&'a OsStr> {
@@ -144,6 +362,9 @@ pub fn normalize<'a, I: IntoIterator<Item = &'a OsStr>>(args: I, specialized: &m
                     } else if let Some(name) = SPECIALS.get(s) {
                         current_name = Some(name);
                         continue;
+                    } else if let Some(name) = SPECIAL_FLAGS.get(s) {
+                        specialized.insert(name, OsStr::new(""));
+                        continue;
                     } else if let Some(eq_index) = s.find('=') {
                         if let Some(name) = SPECIALS.get(&s[..eq_index]) {
                             let value = OsStr::new(&s[(eq_index + 1)..]);
@@ -161,22 +382,58 @@ pub fn normalize<'a, I: IntoIterator<Item = &'a OsStr>>(args: I, specialized: &m
     normalized
 }
 
+#[test]
+fn test_normalize() {
+    let mut specialized = SpecialMap::new();
+    let args = ["--release", "--target=x86_64-unknown-linux-gnu", "--bench", "parse", "--per-test", "--", "--target", "--exact"];
+    let normalized = normalize(args.iter().map(OsStr::new), &mut specialized);
+    assert_eq!(normalized, ["--release", "--bench", "parse", "--", "--target", "--exact"].iter().map(OsStr::new).collect::<Vec<_>>());
+    assert_eq!(specialized.get("target"), Some(&OsStr::new("x86_64-unknown-linux-gnu")));
+    assert_eq!(specialized.get("per-test"), Some(&OsStr::new("")));
+
+    let mut specialized = SpecialMap::new();
+    let args = ["--example", "demo", "--runner", "qemu-arm", "--", "--runner", "--some-arg"];
+    let normalized = normalize(args.iter().map(OsStr::new), &mut specialized);
+    assert_eq!(normalized, ["--example", "demo", "--", "--runner", "--some-arg"].iter().map(OsStr::new).collect::<Vec<_>>());
+    assert_eq!(specialized.get("runner"), Some(&OsStr::new("qemu-arm")));
+    assert_eq!(specialized.len(), 1);
+}
 
-/// Parsed command-line configuration for the `report` subcommand.
+
+/// Parsed command-line configuration for the `report` and `badge` subcommands.
 pub struct ReportConfig<'a> {
     pub workspace_path: Cow<'a, Path>,
     pub gcno_path: Cow<'a, Path>,
     pub gcda_path: Cow<'a, Path>,
     pub output_path: Cow<'a, Path>,
+    pub history_path: PathBuf,
     pub template_name: &'a OsStr,
     pub allowed_source_types: SourceType,
+    /// User-defined categories (`--include` names) which are allowed.
+    pub allowed_categories: Vec<&'a str>,
+    /// User-defined source categories from `--category NAME=GLOB`, checked in order before [`identify_source_path()`].
+    /// The names never collide with the built-in source types of `--include`.
+    ///
+    /// [`identify_source_path()`]: ../sourcepath/fn.identify_source_path.html
+    pub categories: Vec<(&'a str, Pattern)>,
+    /// Path prefix remapping rules from `--remap-path-prefix FROM=TO`. The last matching rule wins, like `rustc`.
+    pub path_remaps: Vec<(&'a str, &'a str)>,
+    /// Packages of the workspace. Empty if the workspace is given by `--workspace`.
+    pub packages: Vec<Package>,
+    /// Only report the source files of this package, if specified.
+    pub package_filter: Option<&'a str>,
+    /// Options for analyzing the GCNO/GCDA graph, e.g. `--exclude-panic-paths`.
+    pub report_options: ReportOptions,
 }
 
 impl<'a> ReportConfig<'a> {
-    /// Parses the command-line arguments for the `report` subcommand.
+    /// Parses the command-line arguments for the `report` subcommand. The output is written to `target/cov/«output_name»`
+    /// by default.
+    ///
+    /// Returns [`PackageNotFound`] if `--package` does not name a package of the workspace.
     ///
-    /// Returns None if
-    pub fn parse(matches: &'a ArgMatches<'a>, cov_build_path: Result<PathBuf>) -> Result<ReportConfig<'a>> {
+    /// [`PackageNotFound`]: ../error/enum.ErrorKind.html#variant.PackageNotFound
+    pub fn parse(matches: &'a ArgMatches<'a>, workspace: Result<(PathBuf, Vec<Package>)>, output_name: &str) -> Result<ReportConfig<'a>> {
         fn match_or_else<'a, F: FnOnce() -> PathBuf>(matches: &'a ArgMatches<'a>, name: &str, default: F) -> Cow<'a, Path> {
             match matches.value_of_os(name) {
                 Some(path) => Cow::Borrowed(Path::new(path)),
@@ -185,30 +442,202 @@ impl<'a> ReportConfig<'a> {
         }
 
 
-        let (workspace_path, cov_build_path) = match (matches.value_of_os("workspace"), cov_build_path) {
+        let (workspace_path, cov_build_path, packages) = match (matches.value_of_os("workspace"), workspace) {
             (Some(workspace_path), _) => {
                 let workspace_path = Path::new(workspace_path);
                 let cov_build_path = join_3(workspace_path, "target", "cov", "build");
-                (Cow::Borrowed(workspace_path), cov_build_path)
+                (Cow::Borrowed(workspace_path), cov_build_path, Vec::new())
             },
-            (_, Ok(cov_build_path)) => (Cow::Owned(parent_3(&cov_build_path).to_owned()), cov_build_path),
+            (_, Ok((cov_build_path, packages))) => (Cow::Owned(parent_3(&cov_build_path).to_owned()), cov_build_path, packages),
             (None, Err(e)) => return Err(e),
         };
 
         let gcno_path = match_or_else(matches, "gcno", || cov_build_path.join("gcno"));
         let gcda_path = match_or_else(matches, "gcda", || cov_build_path.join("gcda"));
-        let output_path = match_or_else(matches, "output", || join_3(&workspace_path, "target", "cov", "report"));
+        let output_path = match_or_else(matches, "output", || join_3(&workspace_path, "target", "cov", output_name));
+        let history_path = cov_build_path.with_file_name("history");
 
         let template_name = matches.value_of_os("template").unwrap_or_else(|| OsStr::new("html"));
-        let allowed_source_types = matches.values_of("include").map_or(SourceType::DEFAULT, |it| SourceType::from_multi_str(it).expect("SourceType"));
+        let mut path_remaps = Vec::new();
+        for rule in matches.values_of("remap-path-prefix").into_iter().flat_map(|it| it) {
+            let mut parts = rule.rsplitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(to), Some(from)) => path_remaps.push((from, to)),
+                _ => bail!(ErrorKind::InvalidPathRule("--remap-path-prefix", rule.to_owned())),
+            }
+        }
+
+        let mut categories = Vec::new();
+        for rule in matches.values_of("category").into_iter().flat_map(|it| it) {
+            let mut parts = rule.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(glob)) => {
+                    ensure!(name.parse::<SourceType>().is_err(), ErrorKind::ReservedCategoryName(name.to_owned()));
+                    let pattern = Pattern::new(glob).chain_err(|| ErrorKind::InvalidPathRule("--category", rule.to_owned()))?;
+                    categories.push((name, pattern));
+                },
+                _ => bail!(ErrorKind::InvalidPathRule("--category", rule.to_owned())),
+            }
+        }
+
+        let (allowed_source_types, allowed_categories) = match matches.values_of("include") {
+            None => (SourceType::DEFAULT, Vec::new()),
+            Some(names) => {
+                let (category_names, source_type_names) = names.partition::<Vec<_>, _>(|name| categories.iter().any(|&(n, _)| n == *name));
+                let source_types = SourceType::from_multi_str(source_type_names.iter().cloned()).map_err(|_| {
+                    let name = source_type_names.iter().find(|name| name.parse::<SourceType>().is_err()).expect("unknown name");
+                    ErrorKind::UnknownSourceType((*name).to_owned())
+                })?;
+                (source_types, category_names)
+            },
+        };
+        let package_filter = matches.value_of("package");
+        if let Some(name) = package_filter {
+            ensure!(packages.iter().any(|pkg| pkg.name == name), ErrorKind::PackageNotFound(name.to_owned()));
+        }
+        let report_options = ReportOptions {
+            exclude_panic_paths: matches.is_present("exclude-panic-paths"),
+        };
 
         Ok(ReportConfig {
             workspace_path,
             gcno_path,
             gcda_path,
             output_path,
+            history_path,
             template_name,
             allowed_source_types,
+            allowed_categories,
+            categories,
+            path_remaps,
+            packages,
+            package_filter,
+            report_options,
         })
     }
+
+    /// Applies the `--remap-path-prefix` rules to a source path.
+    pub fn remap_path<'p>(&self, path: &'p str) -> Cow<'p, str> {
+        match self.path_remaps.iter().rev().find(|&&(from, _)| path.starts_with(from)) {
+            Some(&(from, to)) => Cow::Owned(format!("{}{}", to, &path[from.len()..])),
+            None => Cow::Borrowed(path),
+        }
+    }
+
+    /// Checks whether the source path (after remapping) should be reported, according to its `--category` or
+    /// [`SourceType`], and `--include`.
+    ///
+    /// [`SourceType`]: ../sourcepath/struct.SourceType.html
+    pub fn is_included(&self, path: &str, source_type: SourceType) -> bool {
+        match self.categories.iter().find(|&&(_, ref pattern)| pattern.matches(path)) {
+            Some(&(name, _)) => self.allowed_categories.contains(&name),
+            None => self.allowed_source_types.contains(source_type),
+        }
+    }
+
+    /// Finds the package which the source file belongs to, i.e. the package with the longest root containing the path.
+    /// Relative paths are resolved against the workspace.
+    pub fn package_of(&self, path: &str) -> Option<&Package> {
+        let path = self.workspace_path.join(path);
+        self.packages.iter().filter(|pkg| path.starts_with(&pkg.root)).max_by_key(|pkg| pkg.root.components().count())
+    }
+
+    /// Describes which files are reported and how they are counted, i.e. the options affecting the totals of a report
+    /// such as `--include`, `--category`, `--package` and `--exclude-panic-paths`. The history only compares reports of
+    /// the same selection. The default options are described by an empty string. The source types of `--include` are
+    /// listed by name in alphabetical order, so the same options are always described by the same string.
+    pub fn selection(&self) -> String {
+        let mut parts = Vec::new();
+        if self.allowed_source_types != SourceType::DEFAULT || !self.allowed_categories.is_empty() {
+            let mut names = self.allowed_source_types.names();
+            names.extend(self.allowed_categories.iter().cloned());
+            parts.push(format!("--include={}", names.join(",")));
+        }
+        for &(name, ref pattern) in &self.categories {
+            parts.push(format!("--category={}={}", name, pattern.as_str()));
+        }
+        for &(from, to) in &self.path_remaps {
+            parts.push(format!("--remap-path-prefix={}={}", from, to));
+        }
+        if let Some(name) = self.package_filter {
+            parts.push(format!("--package={}", name));
+        }
+        if self.report_options.exclude_panic_paths {
+            parts.push("--exclude-panic-paths".to_owned());
+        }
+        parts.join(" ")
+    }
+}
+
+#[cfg(test)]
+fn test_report_config(packages: Vec<Package>) -> ReportConfig<'static> {
+    ReportConfig {
+        workspace_path: Cow::Borrowed(Path::new("/ws")),
+        gcno_path: Cow::Borrowed(Path::new("/ws/target/cov/build/gcno")),
+        gcda_path: Cow::Borrowed(Path::new("/ws/target/cov/build/gcda")),
+        output_path: Cow::Borrowed(Path::new("/ws/target/cov/report")),
+        history_path: PathBuf::from("/ws/target/cov/history"),
+        template_name: OsStr::new("html"),
+        allowed_source_types: SourceType::DEFAULT,
+        allowed_categories: Vec::new(),
+        categories: Vec::new(),
+        path_remaps: Vec::new(),
+        packages,
+        package_filter: None,
+        report_options: ReportOptions::default(),
+    }
+}
+
+#[test]
+fn test_package_of() {
+    let package = |name: &str, root: &str| Package {
+        name: name.to_owned(),
+        root: PathBuf::from(root),
+    };
+    let config = test_report_config(vec![package("root", "/ws"), package("nested", "/ws/crates/nested")]);
+    let package_name = |path| config.package_of(path).map(|pkg| &*pkg.name);
+    assert_eq!(package_name("/ws/src/lib.rs"), Some("root"));
+    assert_eq!(package_name("/ws/crates/nested/src/lib.rs"), Some("nested"));
+    assert_eq!(package_name("crates/nested/build.rs"), Some("nested"));
+    assert_eq!(package_name("/ws/crates/nested-2/src/lib.rs"), Some("root"));
+    assert_eq!(package_name("/home/user/.cargo/registry/src/foo/lib.rs"), None);
+}
+
+#[test]
+fn test_remap_path_and_categories() {
+    let mut config = test_report_config(Vec::new());
+    config.path_remaps = vec![("/checkout/", "/ws/"), ("/checkout/third_party/", "/opt/third_party/")];
+    assert_eq!(config.remap_path("/checkout/src/lib.rs"), "/ws/src/lib.rs");
+    assert_eq!(config.remap_path("/checkout/third_party/zlib/lib.rs"), "/opt/third_party/zlib/lib.rs");
+    assert_eq!(config.remap_path("/ws/src/main.rs"), "/ws/src/main.rs");
+
+    config.categories = vec![("proto", Pattern::new("/ws/src/proto/*.rs").unwrap()), ("tools", Pattern::new("/ws/tools/**").unwrap())];
+    config.allowed_categories = vec!["tools"];
+    assert!(!config.is_included("/ws/src/proto/api.rs", SourceType::LOCAL));
+    assert!(config.is_included("/ws/src/lib.rs", SourceType::LOCAL));
+    assert!(config.is_included("/ws/tools/gen/main.rs", SourceType::LOCAL));
+    assert!(!config.is_included("/home/user/.cargo/registry/src/github.com-1ecc6299db9ec823/foo/lib.rs", SourceType::CRATES));
+}
+
+#[test]
+fn test_selection() {
+    let mut config = test_report_config(Vec::new());
+    assert_eq!(config.selection(), "");
+
+    config.allowed_source_types = SourceType::LOCAL;
+    config.package_filter = Some("nested");
+    config.report_options.exclude_panic_paths = true;
+    assert_eq!(config.selection(), "--include=local --package=nested --exclude-panic-paths");
+
+    config.allowed_source_types = SourceType::DEFAULT;
+    config.categories = vec![("proto", Pattern::new("*/proto/*.rs").unwrap())];
+    config.allowed_categories = vec!["proto"];
+    config.package_filter = None;
+    config.report_options.exclude_panic_paths = false;
+    assert_eq!(config.selection(), "--include=local,macros,unknown,proto --category=proto=*/proto/*.rs");
+
+    config.allowed_source_types = SourceType::GIT | SourceType::CRATES;
+    config.allowed_categories = Vec::new();
+    config.categories = Vec::new();
+    assert_eq!(config.selection(), "--include=crates,git");
 }
@@ -0,0 +1,176 @@
+//! SVG coverage badges.
+//!
+//! `cargo cov badge` writes one badge per coverage metric for the whole workspace, and for each package in the
+//! workspace:
+//!
+//! ```text
+//! /path/to/workspace/target/cov/badge/
+//!     lines.svg
+//!     branches.svg
+//!     functions.svg
+//!     «package»/
+//!         lines.svg
+//!         branches.svg
+//!         functions.svg
+//!     ...
+//! ```
+//!
+//! The badges are colored using the same thresholds as the percentage cells in the HTML summary page.
+
+use error::Result;
+
+use cov::report::FileSummary;
+
+use std::fs::{File, create_dir_all};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A coverage metric shown on a badge.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Metric {
+    /// Percentage of covered lines.
+    Lines,
+    /// Percentage of taken branches.
+    Branches,
+    /// Percentage of called functions.
+    Functions,
+}
+
+impl Metric {
+    /// All metrics, in the order shown in the report.
+    pub const ALL: &'static [Metric] = &[Metric::Lines, Metric::Branches, Metric::Functions];
+
+    /// The name of the metric, used as the label and file name of the badge.
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Lines => "lines",
+            Metric::Branches => "branches",
+            Metric::Functions => "functions",
+        }
+    }
+
+    /// Extracts the covered and total count of this metric from the summary.
+    fn extract(self, summary: &FileSummary) -> (usize, usize) {
+        match self {
+            Metric::Lines => (summary.lines_covered, summary.lines_count),
+            Metric::Branches => (summary.branches_taken, summary.branches_count),
+            Metric::Functions => (summary.functions_called, summary.functions_count),
+        }
+    }
+
+    /// The "fair" and "good" percentage thresholds, same as those of `macros::coverage_percent_cell` in the HTML
+    /// template.
+    fn thresholds(self) -> (f64, f64) {
+        match self {
+            Metric::Lines | Metric::Functions => (75.0, 90.0),
+            Metric::Branches => (50.0, 75.0),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = ();
+    fn from_str(s: &str) -> ::std::result::Result<Metric, ()> {
+        Ok(match s {
+            "lines" => Metric::Lines,
+            "branches" => Metric::Branches,
+            "functions" => Metric::Functions,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Computes the text and color of the badge value.
+fn badge_value(metric: Metric, summary: &FileSummary) -> (String, &'static str) {
+    let (value, total) = metric.extract(summary);
+    let (fair, good) = metric.thresholds();
+    if total == 0 {
+        ("—".to_owned(), "#9f9f9f")
+    } else if value == total {
+        ("100%".to_owned(), "#4c1")
+    } else if value == 0 {
+        ("0%".to_owned(), "#e05d44")
+    } else {
+        let p = 100.0 * value as f64 / total as f64;
+        let color = if p > good {
+            "#97ca00"
+        } else if p > fair {
+            "#dfb317"
+        } else {
+            "#fe7d37"
+        };
+        (format!("{:.1}%", p.max(0.1).min(99.9)), color)
+    }
+}
+
+#[test]
+fn test_badge_value() {
+    let summary = FileSummary {
+        lines_count: 1000,
+        lines_covered: 800,
+        branches_count: 100,
+        branches_taken: 80,
+        functions_count: 10,
+        functions_called: 10,
+        ..FileSummary::default()
+    };
+    assert_eq!(badge_value(Metric::Lines, &summary), ("80.0%".to_owned(), "#dfb317"));
+    assert_eq!(badge_value(Metric::Branches, &summary), ("80.0%".to_owned(), "#97ca00"));
+    assert_eq!(badge_value(Metric::Functions, &summary), ("100%".to_owned(), "#4c1"));
+    assert_eq!(badge_value(Metric::Lines, &FileSummary::default()), ("—".to_owned(), "#9f9f9f"));
+}
+
+/// Estimates the width in pixels of the text in 11px Verdana.
+fn text_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c {
+            'f' | 'i' | 'j' | 'l' | 'r' | 't' | '.' | ',' | ':' | '-' | ' ' => 4,
+            'm' | 'w' | 'M' | 'W' | '%' | '—' => 11,
+            _ => 7,
+        })
+        .sum()
+}
+
+/// Renders a flat badge with the label on the left and the value on the right.
+fn render(label: &str, value: &str, color: &str) -> String {
+    let label_width = text_width(label) + 10;
+    let value_width = text_width(value) + 10;
+    let width = label_width + value_width;
+    let label_x = label_width as f64 / 2.0;
+    let value_x = label_width as f64 + value_width as f64 / 2.0;
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {value}">
+<title>{label}: {value}</title>
+<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{value_width}" height="20" fill="{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+<text x="{label_x}" y="15" fill="#010101" fill-opacity=".3">{label}</text><text x="{label_x}" y="14">{label}</text>
+<text x="{value_x}" y="15" fill="#010101" fill-opacity=".3">{value}</text><text x="{value_x}" y="14">{value}</text>
+</g>
+</svg>
+"##,
+        width = width,
+        label_width = label_width,
+        value_width = value_width,
+        label_x = label_x,
+        value_x = value_x,
+        label = label,
+        value = value,
+        color = color,
+    )
+}
+
+/// Writes the badges of the metrics into `«folder»/«metric».svg`.
+pub fn write_badges(folder: &Path, metrics: &[Metric], summary: &FileSummary) -> Result<()> {
+    create_dir_all(folder)?;
+    for &metric in metrics {
+        let (value, color) = badge_value(metric, summary);
+        let path = folder.join(metric.name()).with_extension("svg");
+        let mut file = File::create(&path)?;
+        file.write_all(render(metric.name(), &value, color).as_bytes())?;
+        progress!("Created", "{}", path.display());
+    }
+    Ok(())
+}
@@ -1,24 +1,25 @@
 //! Build environment information for `cargo cov`.
 
-use argparse::SpecialMap;
+use argparse::{SpecialMap, split_shell_words};
 use error::{ErrorKind, Result, ResultExt};
 use lookup::*;
-use shim::move_gcov_files;
-use utils::{CommandExt, clean_dir, set_executable};
+use shim::{collect_gcda_from_sysroot, configuration_id, gcov_dir, move_gcov_files};
+use utils::{CommandExt, clean_dir, join_2, set_executable};
 
 use cov::IntoStringLossy;
-use serde_json::from_reader;
+use serde_json::{from_reader, from_str};
 use shell_escape::escape;
 use tempfile::TempDir;
 
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::env::current_exe;
+use std::collections::{BTreeMap, HashMap};
+use std::env::{self, current_exe};
 use std::ffi::{OsStr, OsString};
 use std::fs::{File, canonicalize, create_dir, create_dir_all};
-use std::io::{self, Write};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::iter::once;
-use std::path::{Path, PathBuf};
+use std::mem::replace;
+use std::path::{Component, Path, PathBuf};
 use std::process::{Command, Stdio};
 
 include!(concat!(env!("OUT_DIR"), "/host.rs"));
@@ -46,7 +47,21 @@ pub struct Cargo<'a> {
     /// Arguments to be forwarded to `cargo`.
     forward_args: Vec<&'a OsStr>,
     /// List of packages in this workspace.
-    workspace_packages: Vec<String>,
+    workspace_packages: Vec<Package>,
+    /// Whether to run every test in isolation to collect per-test coverage.
+    per_test: bool,
+    /// Command which runs the covered program, e.g. an emulator for cross-targets.
+    runner: Option<&'a OsStr>,
+    /// Folder which the covered program writes GCDA files into, on the device it runs.
+    gcov_prefix: Option<&'a OsStr>,
+    /// Local folder where the GCDA files written under `gcov_prefix` can be collected.
+    gcda_sysroot: Option<PathBuf>,
+    /// Whether to use the source-based coverage (`-Cinstrument-coverage`) instead of GCNO/GCDA.
+    is_source_based: bool,
+    /// The configuration tag of this build (e.g. `--features=a`) and its folder name, if tagged.
+    configuration: Option<(Cow<'a, str>, String)>,
+    /// The feature sets to build and test separately, if `--feature-matrix` is given.
+    feature_matrix: Option<Vec<Vec<String>>>,
 }
 
 impl<'a> Cargo<'a> {
@@ -71,20 +86,52 @@ impl<'a> Cargo<'a> {
         cov_build_path.push("build");
         create_dir_all(&cov_build_path).chain_err(|| "Cannot prepare coverage build directory")?;
 
-        let mut workspace_packages = metadata.workspace_members;
-        for pkg_id in &mut workspace_packages {
-            let space_index = pkg_id.find(' ').unwrap_or_else(|| pkg_id.len());
-            pkg_id.truncate(space_index);
+        let root_package = metadata.packages.iter().find(|pkg| pkg.manifest_path == manifest_path).cloned();
+        let workspace_packages = metadata
+            .packages
+            .into_iter()
+            .map(|pkg| {
+                let mut root = pkg.manifest_path;
+                root.pop();
+                Package {
+                    name: pkg.name,
+                    root,
+                }
+            })
+            .collect();
+
+        let per_test = special_args.contains_key("per-test");
+        let runner = special_args.get("runner").cloned();
+        if let Some(runner) = runner {
+            let runner_str = runner.to_str().ok_or_else(|| ErrorKind::InvalidRunner(runner.to_string_lossy().into_owned()))?;
+            ensure!(split_shell_words(runner_str).is_some(), ErrorKind::InvalidRunner(runner_str.to_owned()));
         }
-
+        let gcov_prefix = special_args.get("gcov-prefix").cloned();
+        let gcda_sysroot = special_args.get("gcda-sysroot").cloned().or(gcov_prefix).map(PathBuf::from);
         let target = special_args.get("target").and_then(|s| s.to_str()).unwrap_or(HOST);
+        let is_source_based = match special_args.get("backend").map(|s| s.to_string_lossy()) {
+            None => false,
+            Some(ref backend) if backend == "gcov" => false,
+            Some(ref backend) if backend == "source" => true,
+            Some(backend) => bail!(ErrorKind::UnknownBackend(backend.into_owned())),
+        };
+        let configuration = special_args.get("configuration").and_then(|s| s.to_str()).map(|tag| (Cow::Borrowed(tag), configuration_id(tag)));
+        ensure!(configuration.is_none() || !is_source_based, ErrorKind::GcnoOnly("--configuration"));
+        let feature_matrix = if special_args.contains_key("feature-matrix") {
+            ensure!(!is_source_based, ErrorKind::GcnoOnly("--feature-matrix"));
+            let package = root_package.ok_or(ErrorKind::NoRootPackage("--feature-matrix"))?;
+            Some(feature_matrix(&package)?)
+        } else {
+            None
+        };
         let (profiler_lib_path, profiler_lib_name) = match special_args.get("profiler") {
             Some(&path) => {
                 let (p, n) = split_profiler_lib(Path::new(path)).chain_err(|| "Cannot parse user-provided profiler library")?;
                 (Cow::Owned(canonicalize(p)?.into_string_lossy()), Cow::Borrowed(n))
             },
             None => {
-                if supports_built_in_profiler(&rustc_path, target) {
+                // The source-based coverage always uses the profiler runtime shipped with rustc.
+                if is_source_based || supports_built_in_profiler(&rustc_path, target) {
                     (Cow::Borrowed("@native"), Cow::Borrowed("@native"))
                 } else {
                     let (p, n) = find_native_profiler_lib(target).chain_err(|| "Native profiler library not found")?;
@@ -105,12 +152,25 @@ impl<'a> Cargo<'a> {
             profiler_lib_name,
             forward_args,
             workspace_packages,
+            per_test,
+            runner,
+            gcov_prefix,
+            gcda_sysroot,
+            is_source_based,
+            configuration,
+            feature_matrix,
         })
     }
 
-    /// Obtains the `target/cov/build` path and transfers ownership.
-    pub fn into_cov_build_path(self) -> PathBuf {
-        self.cov_build_path
+    /// Obtains the `target/cov/build` path and the packages in this workspace, and transfers ownership.
+    pub fn into_cov_build_path_and_packages(self) -> (PathBuf, Vec<Package>) {
+        (self.cov_build_path, self.workspace_packages)
+    }
+
+    /// Obtains a copy of the `target/cov/build` path and the packages in this workspace, keeping the environment
+    /// usable for running more subcommands.
+    pub fn cov_build_path_and_packages(&self) -> (PathBuf, Vec<Package>) {
+        (self.cov_build_path.clone(), self.workspace_packages.clone())
     }
 
     /// Prepares the coverage folder for building.
@@ -129,8 +189,12 @@ impl<'a> Cargo<'a> {
             Err(_) => "cargo-cov".to_owned(),
         };
 
-        create_dir_all(self.cov_build_path.join("gcno"))?;
-        create_dir_all(self.cov_build_path.join("gcda"))?;
+        create_dir_all(self.gcov_dir("gcno"))?;
+        create_dir_all(self.gcov_dir("gcda"))?;
+        if let Some((ref tag, _)) = self.configuration {
+            let mut name_file = File::create(self.gcov_dir("gcno").join("name"))?;
+            name_file.write_all(tag.as_bytes())?;
+        }
 
         let rustc_shim = self.write_shim(&self_path, "rustc-shim.bat")?;
         let rustdoc_shim = self.write_shim(&self_path, "rustdoc-shim.bat")?;
@@ -167,6 +231,18 @@ impl<'a> Cargo<'a> {
         Ok(())
     }
 
+    /// Obtains the folder storing the GCNO or GCDA files (`extension`) of this build. See [`shim::gcov_dir()`].
+    ///
+    /// [`shim::gcov_dir()`]: ../shim/fn.gcov_dir.html
+    fn gcov_dir(&self, extension: &str) -> PathBuf {
+        gcov_dir(&self.cov_build_path, OsStr::new(extension), self.configuration_id())
+    }
+
+    /// Obtains the folder name of the configuration, if tagged.
+    fn configuration_id(&self) -> Option<&OsStr> {
+        self.configuration.as_ref().map(|&(_, ref id)| OsStr::new(id))
+    }
+
     /// Writes the content of a shim script.
     fn write_shim(&self, self_path: &str, shim_name: &str) -> io::Result<PathBuf> {
         #[cfg(unix)]
@@ -195,41 +271,184 @@ bitflags! {
     pub struct CleanTargets: u8 {
         /// Delete the `target/cov/build/gcda/` folder.
         const BUILD_GCDA = 1;
-        /// Delete the `target/cov/build/gcno/` folder and built artifacts of all crates in the current workspace.
+        /// Delete the `target/cov/build/gcno/` folder, built artifacts of all crates in the current workspace, and the
+        /// target folders of the tagged configurations.
         const BUILD_GCNO = 2;
         /// Delete the whole `target/cov/build/` folder.
         const BUILD_EXTERNAL = 4;
         /// Delete the `target/cov/report` folder.
         const REPORT = 8;
+        /// Delete the `target/cov/history` folder.
+        const HISTORY = 16;
     }
 }
 
 impl<'a> Cargo<'a> {
-    /// Runs the real cargo subcommand (build, test, run).
-    pub fn forward(self, subcommand: &str) -> Result<()> {
-        self.prepare_cov_build_path()?;
-        let mut cmd = Command::new(self.cargo_path);
+    /// Creates a command which runs the real cargo subcommand with the coverage shims.
+    fn cargo_command(&self, subcommand: &str) -> Command {
+        let mut cmd = Command::new(&self.cargo_path);
         cmd.current_dir(&self.cov_build_path)
-            .env("COV_RUSTC", self.rustc_path)
-            .env("COV_RUSTDOC", self.rustdoc_path)
+            .env("COV_RUSTC", &self.rustc_path)
+            .env("COV_RUSTDOC", &self.rustdoc_path)
             .env("COV_BUILD_PATH", &self.cov_build_path)
             .env("COV_PROFILER_LIB_PATH", &*self.profiler_lib_path)
             .env("COV_PROFILER_LIB_NAME", &*self.profiler_lib_name)
             .arg(subcommand)
             .arg("--manifest-path")
-            .arg(self.manifest_path);
+            .arg(&self.manifest_path);
         if self.target != HOST {
             cmd.args(&["--target", self.target]);
         }
-        cmd.args(self.forward_args);
+        if let Some(runner) = self.runner {
+            cmd.env("COV_RUNNER", runner);
+        }
+        if let Some((gcov_prefix, strip)) = self.gcov_prefix_env() {
+            // Only the covered programs should write under the prefix, so the shim sets `GCOV_PREFIX` on them alone.
+            cmd.env("COV_GCOV_PREFIX", gcov_prefix).env("COV_GCOV_PREFIX_STRIP", strip);
+        }
+        if let Some(ref sysroot) = self.gcda_sysroot {
+            // With `--per-test`, the shim collects the GCDA files of every test, before the next one adds to them.
+            cmd.env("COV_GCDA_SYSROOT", sysroot);
+        }
+        if self.is_source_based {
+            // The raw profiles are written to `target/cov/build`, and will be moved to the `gcda` folder by the shims.
+            cmd.env("COV_BACKEND", "source").env("LLVM_PROFILE_FILE", self.cov_build_path.join("%p-%m.profraw"));
+        }
+        if let Some((_, ref id)) = self.configuration {
+            // Every configuration is built separately, so the objects of one configuration never replace another's.
+            cmd.env("COV_CONFIGURATION", id).env("CARGO_TARGET_DIR", join_2(&self.cov_build_path, "configurations", id));
+        }
+        cmd
+    }
+
+    /// Obtains the values of `GCOV_PREFIX` and `GCOV_PREFIX_STRIP` for the covered programs, if `--gcov-prefix` is
+    /// given.
+    ///
+    /// The `target/cov/build` path is stripped, so the GCDA files appear at the same relative path in the sysroot.
+    fn gcov_prefix_env(&self) -> Option<(&'a OsStr, String)> {
+        self.gcov_prefix.map(|gcov_prefix| {
+            let strip = self.cov_build_path.components().filter(|c| match *c {
+                Component::Normal(_) => true,
+                _ => false,
+            }).count();
+            (gcov_prefix, strip.to_string())
+        })
+    }
+
+    /// Collects the GCDA files written under `--gcov-prefix` by the runner, if a sysroot is configured.
+    fn collect_gcda_from_sysroot(&self) -> Result<()> {
+        if let Some(ref sysroot) = self.gcda_sysroot {
+            let count = collect_gcda_from_sysroot(&self.cov_build_path, sysroot, self.configuration_id())?;
+            progress!("Collected", "{} profile data from {}", count, sysroot.display());
+        }
+        Ok(())
+    }
+
+    /// Whether `--feature-matrix` is given, i.e. [`forward()`] will run once for every feature set.
+    ///
+    /// [`forward()`]: #method.forward
+    pub fn has_feature_matrix(&self) -> bool {
+        self.feature_matrix.is_some()
+    }
+
+    /// Runs the real cargo subcommand (build, test, bench, run).
+    ///
+    /// With `--feature-matrix`, the subcommand is run once for every feature set, with the default features disabled.
+    /// Every feature set is tagged as a separate configuration (e.g. `--no-default-features --features=a,b`), so it is
+    /// built in its own target folder and its GCNO/GCDA files never clash with those of the other feature sets.
+    pub fn forward(&mut self, subcommand: &str) -> Result<()> {
+        let feature_sets = match self.feature_matrix.take() {
+            Some(feature_sets) => feature_sets,
+            None => return self.forward_once(subcommand, &[]),
+        };
+        if self.configuration.is_some() {
+            warning!("--configuration is ignored by --feature-matrix");
+        }
+        let result = self.forward_feature_sets(subcommand, &feature_sets);
+        self.feature_matrix = Some(feature_sets);
+        result
+    }
+
+    /// Runs the real cargo subcommand once for every feature set of `--feature-matrix`. The configuration is restored
+    /// after every run, so the `Cargo` can be forwarded again (e.g. by `cargo cov watch`).
+    fn forward_feature_sets(&mut self, subcommand: &str, feature_sets: &[Vec<String>]) -> Result<()> {
+        for (i, features) in feature_sets.iter().enumerate() {
+            let tag = feature_set_tag(features);
+            progress!("Configuring", "{} ({}/{})", tag, i + 1, feature_sets.len());
+            let id = configuration_id(&tag);
+            let configuration = replace(&mut self.configuration, Some((Cow::Owned(tag), id)));
+            let mut extra_args = vec!["--no-default-features".to_owned()];
+            if !features.is_empty() {
+                extra_args.push(format!("--features={}", features.join(",")));
+            }
+            let result = self.forward_once(subcommand, &extra_args);
+            self.configuration = configuration;
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Runs the real cargo subcommand once, with `extra_args` appended to the forwarded arguments.
+    fn forward_once(&self, subcommand: &str, extra_args: &[String]) -> Result<()> {
+        self.prepare_cov_build_path()?;
+        let mut cmd = self.cargo_command(subcommand);
+        if self.per_test && subcommand == "test" {
+            cmd.env("COV_PER_TEST", "1");
+        }
+        cmd.args(extra_args).args(&self.forward_args);
 
         progress!("Delegate", "{:?}", cmd);
 
         cmd.ensure_success("cargo")?;
-        if subcommand == "test" || subcommand == "run" {
-            // Before 1.19, the test-runner is absent, so we need to move them outside of the shim.
-            move_gcov_files(&self.cov_build_path, OsStr::new("gcda"))?;
+        // Build scripts are executed by cargo directly. Also, before 1.19, the test-runner is absent. So we need to
+        // move the GCDA files outside of the shim.
+        move_gcov_files(&self.cov_build_path, OsStr::new("gcda"), self.configuration_id())?;
+        if subcommand == "test" || subcommand == "bench" || subcommand == "run" {
+            self.collect_gcda_from_sysroot()?;
+        }
+
+        Ok(())
+    }
+
+    /// Builds the binaries with coverage enabled, and then runs an arbitrary command which may execute them.
+    ///
+    /// The forwarded arguments before `--` are passed to `cargo build`, and those after `--` form the command. The
+    /// folders containing the built binaries are prepended to `$PATH`. The path of every binary is also exposed as the
+    /// environment variable `COV_BIN_«NAME»`, where `«NAME»` is the binary name in uppercase with `-` replaced by `_`.
+    ///
+    /// All GCDA files generated will be moved to `target/cov/build/gcda/` after the command exits, even if it failed.
+    pub fn exec(self) -> Result<()> {
+        let split_index = self.forward_args.iter().position(|a| *a == OsStr::new("--")).ok_or(ErrorKind::NoExecCommand)?;
+        let (build_args, command_args) = self.forward_args.split_at(split_index);
+        let (program, command_args) = command_args[1..].split_first().ok_or(ErrorKind::NoExecCommand)?;
+
+        self.prepare_cov_build_path()?;
+        let mut cmd = self.cargo_command("build");
+        cmd.args(build_args).arg("--message-format=json").stdout(Stdio::piped());
+        progress!("Delegate", "{:?}", cmd);
+        let mut child = cmd.spawn()?;
+        let binaries = collect_binaries(child.stdout.take().expect("stdout"))?;
+        let status = child.wait()?;
+        ensure!(status.success(), ErrorKind::ForwardFailed("cargo", status));
+
+        let mut cmd = Command::new(program);
+        cmd.args(command_args);
+        for (key, value) in binaries_env(&binaries, env::var_os("PATH"))? {
+            cmd.env(key, value);
         }
+        if let Some((gcov_prefix, strip)) = self.gcov_prefix_env() {
+            cmd.env("GCOV_PREFIX", gcov_prefix).env("GCOV_PREFIX_STRIP", strip);
+        }
+        if self.is_source_based {
+            // Otherwise the binaries would write `default_*.profraw` into the current folder, which is never collected.
+            cmd.env("LLVM_PROFILE_FILE", self.cov_build_path.join("%p-%m.profraw"));
+        }
+
+        progress!("Executing", "{:?}", cmd);
+        let status = cmd.status()?;
+        move_gcov_files(&self.cov_build_path, OsStr::new("gcda"), self.configuration_id())?;
+        self.collect_gcda_from_sysroot()?;
+        ensure!(status.success(), ErrorKind::ForwardFailed("command", status));
 
         Ok(())
     }
@@ -257,22 +476,141 @@ impl<'a> Cargo<'a> {
                 if self.target != HOST {
                     cmd.args(&["--target", self.target]);
                 }
-                for pkg_name in &self.workspace_packages {
-                    cmd.args(&["-p", pkg_name]);
+                for pkg in &self.workspace_packages {
+                    cmd.args(&["-p", &pkg.name]);
                 }
                 progress!("Delegate", "{:?}", cmd);
                 cmd.ensure_success("cargo")?;
-                do_clean(&self.cov_build_path.join("gcno"))?
+                do_clean(&self.cov_build_path.join("gcno"))?;
+                do_clean(&self.cov_build_path.join("configurations"))?
             }
         }
 
         if clean_targets.contains(CleanTargets::REPORT) {
             do_clean(&self.cov_build_path.with_file_name("report"))?;
         }
+        if clean_targets.contains(CleanTargets::HISTORY) {
+            do_clean(&self.cov_build_path.with_file_name("history"))?;
+        }
         Ok(())
     }
 }
 
+/// Reads the JSON messages printed by `cargo build --message-format=json`, and returns the path of every binary built,
+/// keyed by the binary name.
+///
+/// Compiler diagnostics embedded in the messages are printed to `stderr`.
+fn collect_binaries<R: Read>(reader: R) -> Result<BTreeMap<String, PathBuf>> {
+    let mut binaries = BTreeMap::new();
+    for line in BufReader::new(reader).lines() {
+        let message = match from_str::<BuildMessage>(&line?) {
+            Ok(message) => message,
+            Err(e) => {
+                debug!("skipping unrecognized cargo message: {}", e);
+                continue;
+            },
+        };
+        match &*message.reason {
+            "compiler-message" => if let Some(rendered) = message.message.and_then(|m| m.rendered) {
+                eprint!("{}", rendered);
+            },
+            "compiler-artifact" => if let Some(target) = message.target {
+                if target.kind.iter().any(|k| k == "bin") {
+                    if let Some(path) = message.filenames.into_iter().next() {
+                        binaries.insert(target.name, path);
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+    Ok(binaries)
+}
+
+#[test]
+fn test_collect_binaries() {
+    let messages = br#"{"reason":"compiler-artifact","target":{"kind":["lib"],"name":"foo"},"filenames":["/t/debug/libfoo.rlib"]}
+{"reason":"compiler-message","message":{"rendered":"warning: unused variable: `x`\n"}}
+{"reason":"compiler-artifact","target":{"kind":["bin"],"name":"foo-cli"},"filenames":["/t/debug/foo-cli"]}
+{"reason":"build-script-executed","package_id":"foo 0.1.0"}
+Compiling foo v0.1.0
+{"reason":"compiler-artifact","target":{"kind":["bin"],"name":"helper"},"filenames":["/t/debug/helper","/t/debug/helper.dSYM"]}
+"#;
+    let binaries = collect_binaries(&messages[..]).unwrap();
+    let expected = [("foo-cli", "/t/debug/foo-cli"), ("helper", "/t/debug/helper")]
+        .iter()
+        .map(|&(name, path)| (name.to_owned(), PathBuf::from(path)))
+        .collect::<BTreeMap<_, _>>();
+    assert_eq!(binaries, expected);
+}
+
+/// Computes the environment variables exposing the `binaries` to the command of [`Cargo::exec()`]: `COV_BIN_«NAME»`
+/// of every binary, and `PATH` with the folders of the binaries prepended to the original `path`.
+///
+/// [`Cargo::exec()`]: ./struct.Cargo.html#method.exec
+fn binaries_env(binaries: &BTreeMap<String, PathBuf>, path: Option<OsString>) -> Result<Vec<(OsString, OsString)>> {
+    let mut vars = Vec::with_capacity(binaries.len() + 1);
+    let mut paths = Vec::new();
+    for (name, path) in binaries {
+        vars.push((format!("COV_BIN_{}", name.to_uppercase().replace('-', "_")).into(), path.clone().into_os_string()));
+        if let Some(parent) = path.parent() {
+            if !paths.iter().any(|p| p == parent) {
+                paths.push(parent.to_owned());
+            }
+        }
+    }
+    if let Some(path) = path {
+        paths.extend(env::split_paths(&path));
+    }
+    vars.push(("PATH".into(), env::join_paths(paths).chain_err(|| "Cannot add the binaries to $PATH")?));
+    Ok(vars)
+}
+
+#[test]
+fn test_binaries_env() {
+    let binaries = [("foo-cli", "/t/debug/foo-cli"), ("helper", "/t/debug/helper"), ("gen", "/t/gen/gen")]
+        .iter()
+        .map(|&(name, path)| (name.to_owned(), PathBuf::from(path)))
+        .collect::<BTreeMap<_, _>>();
+    let original_path = env::join_paths(&["/usr/bin", "/bin"]).unwrap();
+    let vars = binaries_env(&binaries, Some(original_path)).unwrap();
+    let expected_path = env::join_paths(&["/t/debug", "/t/gen", "/usr/bin", "/bin"]).unwrap();
+    assert_eq!(
+        vars,
+        vec![
+            ("COV_BIN_FOO_CLI".into(), "/t/debug/foo-cli".into()),
+            ("COV_BIN_GEN".into(), "/t/gen/gen".into()),
+            ("COV_BIN_HELPER".into(), "/t/debug/helper".into()),
+            ("PATH".into(), expected_path),
+        ]
+    );
+
+    let vars = binaries_env(&BTreeMap::new(), None).unwrap();
+    assert_eq!(vars, vec![("PATH".into(), OsString::new())]);
+}
+
+#[derive(Debug, Deserialize)]
+struct BuildMessage {
+    reason: String,
+    #[serde(default)]
+    target: Option<BuildTarget>,
+    #[serde(default)]
+    filenames: Vec<PathBuf>,
+    #[serde(default)]
+    message: Option<CompilerMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BuildTarget {
+    kind: Vec<String>,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompilerMessage {
+    rendered: Option<String>,
+}
+
 /// Locates the path to `Cargo.toml` if it is not specified in the command line.
 fn locate_project(cargo_path: &OsStr) -> Result<PathBuf> {
     let child = Command::new(cargo_path) // @rustfmt-force-break
@@ -292,10 +630,120 @@ struct ProjectLocation {
 
 #[derive(Debug, Deserialize)]
 struct Metadata {
-    workspace_members: Vec<String>,
+    packages: Vec<MetadataPackage>,
     target_directory: Option<PathBuf>,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+struct MetadataPackage {
+    name: String,
+    manifest_path: PathBuf,
+    #[serde(default)]
+    features: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    metadata: Option<PackageMetadata>,
+}
+
+/// The `[package.metadata]` table of `Cargo.toml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct PackageMetadata {
+    #[serde(default)]
+    cov: Option<CovMetadata>,
+}
+
+/// The `[package.metadata.cov]` table of `Cargo.toml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct CovMetadata {
+    /// The feature sets to test with `--feature-matrix`, instead of every combination of the features.
+    #[serde(rename = "feature-matrix")]
+    feature_matrix: Option<Vec<Vec<String>>>,
+}
+
+/// The maximum number of features whose combinations are tested by `--feature-matrix`, when the feature sets are not
+/// listed in `[package.metadata.cov]`.
+const MAX_FEATURE_MATRIX_FEATURES: usize = 6;
+
+/// Lists the feature sets of a package to test with `--feature-matrix`.
+///
+/// If `Cargo.toml` contains a `feature-matrix` list in `[package.metadata.cov]`, it is used as is, e.g.
+///
+/// ```toml
+/// [package.metadata.cov]
+/// feature-matrix = [[], ["std"], ["std", "serde"]]
+/// ```
+///
+/// Otherwise, every combination of the features (except `default`) is listed, from the empty set to all features.
+/// Returns [`FeatureMatrixTooLarge`] if there are more than 6 features.
+///
+/// [`FeatureMatrixTooLarge`]: ../error/enum.ErrorKind.html#variant.FeatureMatrixTooLarge
+fn feature_matrix(package: &MetadataPackage) -> Result<Vec<Vec<String>>> {
+    if let Some(feature_sets) = package.metadata.as_ref().and_then(|m| m.cov.as_ref()).and_then(|c| c.feature_matrix.clone()) {
+        return Ok(feature_sets);
+    }
+
+    let features = package.features.keys().filter(|f| *f != "default").collect::<Vec<_>>();
+    ensure!(features.len() <= MAX_FEATURE_MATRIX_FEATURES, ErrorKind::FeatureMatrixTooLarge(features.len()));
+    let mut feature_sets = (0..(1_usize << features.len()))
+        .map(|mask| features.iter().enumerate().filter(|&(i, _)| mask & (1 << i) != 0).map(|(_, f)| (*f).clone()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    feature_sets.sort_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+    Ok(feature_sets)
+}
+
+/// Describes a feature set of `--feature-matrix` by the arguments passed to cargo, used as the configuration tag.
+fn feature_set_tag(features: &[String]) -> String {
+    if features.is_empty() {
+        "--no-default-features".to_owned()
+    } else {
+        format!("--no-default-features --features={}", features.join(","))
+    }
+}
+
+#[test]
+fn test_feature_matrix() {
+    let mut package = MetadataPackage {
+        name: "x".to_owned(),
+        manifest_path: PathBuf::from("/ws/Cargo.toml"),
+        features: BTreeMap::new(),
+        metadata: None,
+    };
+    for &(name, ref deps) in &[("default", vec!["std"]), ("std", vec![]), ("serde", vec!["std"])] {
+        package.features.insert(name.to_owned(), deps.iter().map(|d| d.to_string()).collect());
+    }
+
+    let feature_sets = feature_matrix(&package).unwrap();
+    let tags = feature_sets.iter().map(|f| feature_set_tag(f)).collect::<Vec<_>>();
+    assert_eq!(
+        tags,
+        vec![
+            "--no-default-features",
+            "--no-default-features --features=serde",
+            "--no-default-features --features=std",
+            "--no-default-features --features=serde,std",
+        ]
+    );
+
+    package.metadata = Some(PackageMetadata {
+        cov: Some(CovMetadata {
+            feature_matrix: Some(vec![vec!["std".to_owned()]]),
+        }),
+    });
+    assert_eq!(feature_matrix(&package).unwrap(), vec![vec!["std".to_owned()]]);
+
+    let joined = feature_set_tag(&["a".to_owned(), "b".to_owned()]);
+    let underscored = feature_set_tag(&["a_b".to_owned()]);
+    assert_ne!(configuration_id(&joined), configuration_id(&underscored));
+}
+
+/// A package in the current workspace.
+#[derive(Debug, Clone)]
+pub struct Package {
+    /// Name of the package.
+    pub name: String,
+    /// Folder containing the `Cargo.toml` of the package.
+    pub root: PathBuf,
+}
+
 /// Obtains the `target/` directory for a crate using `cargo metadata`.
 ///
 /// This method is supported only starting from Rust 1.19.
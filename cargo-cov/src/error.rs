@@ -40,6 +40,65 @@ error_chain! {
             display(".cargo/config has no `build.rustc` key")
         }
 
+        UnknownBackend(backend: String) {
+            description("unknown coverage backend")
+            display("unknown coverage backend `{}`, expecting `gcov` or `source`", backend)
+        }
+
+        PackageNotFound(name: String) {
+            description("package not found in the workspace")
+            display("package `{}` not found in the workspace", name)
+        }
+
+        InvalidPathRule(option: &'static str, rule: String) {
+            description("invalid path rule")
+            display("invalid {} rule `{}`, expecting the form `A=B`", option, rule)
+        }
+
+        SourceFileNotFound(path: String) {
+            description("source file not found in the coverage data")
+            display("source file `{}` not found in the coverage data", path)
+        }
+
+        InvalidLineRange(range: String) {
+            description("invalid line range")
+            display("invalid line range `{}`, expecting the form `START-END` or `LINE`", range)
+        }
+
+        GcnoOnly(feature: &'static str) {
+            description("not supported by the source-based coverage")
+            display("{} requires the GCNO/GCDA coverage, and is not supported by the source-based coverage", feature)
+        }
+
+        UnknownSourceType(name: String) {
+            description("unknown source type")
+            display("unknown source type `{}` in --include, expecting a built-in type or a name defined by --category", name)
+        }
+
+        ReservedCategoryName(name: String) {
+            description("category name is a built-in source type")
+            display("--category name `{}` is a built-in source type of --include, please choose another name", name)
+        }
+
+        NoRootPackage(option: &'static str) {
+            description("the manifest does not define a package")
+            display("{} requires a package, but the manifest only defines a virtual workspace, try --manifest-path", option)
+        }
+
+        FeatureMatrixTooLarge(count: usize) {
+            description("too many features to test every combination")
+            display("cannot test every combination of {} features, please list the feature sets in `feature-matrix` of `[package.metadata.cov]`", count)
+        }
+
+        InvalidRunner(runner: String) {
+            description("invalid runner command")
+            display("invalid --runner command `{}`, a quote is not closed", runner)
+        }
+
+        NoExecCommand {
+            description("no command to execute, please supply it after `--`, e.g. `cargo cov exec -- ./run-tests.sh`")
+        }
+
         ForwardFailed(command: &'static str, status: ExitStatus) {
             description("command failed")
             display("{} exited with {}", command, status)
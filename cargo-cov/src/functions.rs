@@ -0,0 +1,138 @@
+//! Function listing in the terminal.
+//!
+//! `cargo cov functions` prints every function with its cyclomatic complexity, block coverage and CRAP score, sorted to
+//! find the complex but poorly tested functions first:
+//!
+//! ```text
+//!     CRAP  Complexity   Blocks    Calls  Function
+//!    30.00           5    0.00%        0  crate_name::parse_args (./src/main.rs:12)
+//!     7.25           4   62.50%       10  crate_name::run (./src/main.rs:40)
+//! ```
+
+use error::Result;
+use sourcepath::{SourceType, identify_source_path};
+
+use cov::report::Function;
+
+use std::cmp::Ordering;
+use std::io::{self, Write};
+use std::path::{MAIN_SEPARATOR, Path};
+use std::str::FromStr;
+
+/// The order of the listed functions.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SortKey {
+    /// Highest CRAP score first.
+    Crap,
+    /// Highest cyclomatic complexity first.
+    Complexity,
+    /// Lowest block coverage first.
+    Coverage,
+    /// Most called first.
+    Calls,
+    /// Alphabetically by the demangled name.
+    Name,
+}
+
+impl FromStr for SortKey {
+    type Err = ();
+    fn from_str(s: &str) -> ::std::result::Result<SortKey, ()> {
+        Ok(match s {
+            "crap" => SortKey::Crap,
+            "complexity" => SortKey::Complexity,
+            "coverage" => SortKey::Coverage,
+            "calls" => SortKey::Calls,
+            "name" => SortKey::Name,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Computes the ratio of executed basic blocks of the function.
+fn block_coverage(function: &Function) -> f64 {
+    let s = &function.summary;
+    if s.blocks_count == 0 {
+        0.0
+    } else {
+        s.blocks_executed as f64 / s.blocks_count as f64
+    }
+}
+
+/// Compares two functions by the sort key. Ties are broken by the name.
+fn compare(key: SortKey, a: &Function, b: &Function) -> Ordering {
+    let ordering = match key {
+        SortKey::Crap => b.summary.crap().partial_cmp(&a.summary.crap()).unwrap_or(Ordering::Equal),
+        SortKey::Complexity => b.summary.complexity.cmp(&a.summary.complexity),
+        SortKey::Coverage => block_coverage(a).partial_cmp(&block_coverage(b)).unwrap_or(Ordering::Equal),
+        SortKey::Calls => b.summary.entry_count.cmp(&a.summary.entry_count),
+        SortKey::Name => Ordering::Equal,
+    };
+    ordering.then_with(|| a.demangled_name.cmp(&b.demangled_name))
+}
+
+#[test]
+fn test_compare() {
+    use cov::report::FunctionSummary;
+
+    fn names(functions: &[Function]) -> Vec<&str> {
+        functions.iter().map(|f| &*f.demangled_name).collect()
+    }
+
+    let function = |name: &str, complexity, blocks_executed| Function {
+        demangled_name: name.to_owned(),
+        summary: FunctionSummary {
+            blocks_count: 4,
+            blocks_executed,
+            complexity,
+            ..FunctionSummary::default()
+        },
+        ..Function::default()
+    };
+    let mut functions = vec![function("a", 1, 0), function("b", 3, 4), function("c", 3, 2), function("d", 2, 0)];
+
+    functions.sort_by(|a, b| compare(SortKey::Crap, a, b));
+    assert_eq!(names(&functions), ["d", "c", "b", "a"]);
+    functions.sort_by(|a, b| compare(SortKey::Complexity, a, b));
+    assert_eq!(names(&functions), ["b", "c", "d", "a"]);
+    functions.sort_by(|a, b| compare(SortKey::Coverage, a, b));
+    assert_eq!(names(&functions), ["a", "d", "c", "b"]);
+}
+
+/// Shortens the source path for display, by replacing the known folders with a prefix like `«rust»`. The `crate_path`
+/// should end with a path separator.
+pub fn display_path(path: &str, crate_path: &str) -> String {
+    let (source_type, stripped_len) = identify_source_path(path, crate_path);
+    if source_type == SourceType::MACROS {
+        path.to_owned()
+    } else {
+        format!("{}{}{}", source_type.prefix(), MAIN_SEPARATOR, &path[stripped_len..])
+    }
+}
+
+/// Sorts the functions, and prints the first `limit` of them to stdout. The source paths are shown relative to the
+/// workspace.
+pub fn print(functions: &mut [(String, Function)], key: SortKey, limit: usize, workspace_path: &Path) -> Result<()> {
+    functions.sort_by(|a, b| compare(key, &a.1, &b.1));
+
+    let mut crate_path = workspace_path.to_string_lossy().into_owned();
+    crate_path.push(MAIN_SEPARATOR);
+
+    let stdout = io::stdout();
+    let mut lock = stdout.lock();
+    writeln!(lock, "{:>8}  {:>10}  {:>7}  {:>7}  Function", "CRAP", "Complexity", "Blocks", "Calls")?;
+    for &(ref path, ref function) in functions.iter().take(limit) {
+        let location = display_path(path, &crate_path);
+        writeln!(
+            lock,
+            "{:>8.2}  {:>10}  {:>6.2}%  {:>7}  {} ({}:{})",
+            function.summary.crap(),
+            function.summary.complexity,
+            100.0 * block_coverage(function),
+            function.summary.entry_count,
+            function.demangled_name,
+            location,
+            function.line,
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,299 @@
+//! Coverage trend history.
+//!
+//! Every `cargo cov report` appends one line to `target/cov/history/history.jsonl`, recording when the report was
+//! generated, the git commit checked out in the workspace, the options selecting the reported files (see
+//! [`ReportConfig::selection()`]), and the [`FileSummary`] of every reported file:
+//!
+//! ```json
+//! {"timestamp":1514764800,"commit":"0123456789abcdef0123456789abcdef01234567","selection":"","files":{"/path/to/src/lib.rs":{...}}}
+//! ```
+//!
+//! The history can be displayed with `cargo cov history`, and the summary page of the report receives the recent runs
+//! of each file to draw a trend. Only the runs of the same selection are compared, since e.g. a report of a single
+//! `--package` has nothing to do with the total of the workspace.
+//!
+//! [`ReportConfig::selection()`]: ../argparse/struct.ReportConfig.html#method.selection
+//!
+//! [`FileSummary`]: ../../cov/report/struct.FileSummary.html
+
+use error::{Result, ResultExt};
+use utils::sum_file_summaries;
+
+use cov::report::FileSummary;
+use serde_json::{from_str, to_string};
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions, create_dir_all};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the history file inside `target/cov/history/`.
+const HISTORY_FILE_NAME: &str = "history.jsonl";
+
+/// A single run recorded in the history.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    /// Seconds since the Unix epoch when the report was generated.
+    pub timestamp: u64,
+    /// The git commit checked out in the workspace, if any.
+    pub commit: Option<String>,
+    /// The options selecting the reported files, see [`ReportConfig::selection()`]. Empty for the default options.
+    ///
+    /// [`ReportConfig::selection()`]: ../argparse/struct.ReportConfig.html#method.selection
+    #[serde(default)]
+    pub selection: String,
+    /// Summary of every reported file, keyed by the source path.
+    pub files: BTreeMap<String, FileSummary>,
+}
+
+impl Entry {
+    /// Creates a history entry of the current time and commit.
+    pub fn new<'a, I: IntoIterator<Item = (&'a str, FileSummary)>>(workspace_path: &Path, selection: String, files: I) -> Entry {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Entry {
+            timestamp,
+            commit: read_git_commit(workspace_path),
+            selection,
+            files: files.into_iter().map(|(path, summary)| (path.to_owned(), summary)).collect(),
+        }
+    }
+
+    /// Sums up the summaries of all files.
+    pub fn total(&self) -> FileSummary {
+        sum_file_summaries(self.files.values())
+    }
+}
+
+/// Reads the entries of the given selection from the history folder. Returns an empty list if the history does not
+/// exist.
+pub fn load(history_path: &Path, selection: &str) -> Result<Vec<Entry>> {
+    let path = history_path.join(HISTORY_FILE_NAME);
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match from_str::<Entry>(&line) {
+            Ok(ref entry) if entry.selection != selection => {},
+            Ok(entry) => entries.push(entry),
+            Err(e) => warning!("ignored malformed history at {}:{}: {}", path.display(), i + 1, e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Appends an entry to the history.
+pub fn append(history_path: &Path, entry: &Entry) -> Result<()> {
+    create_dir_all(history_path)?;
+    let path = history_path.join(HISTORY_FILE_NAME);
+    let mut file = OpenOptions::new().create(true).append(true).open(&path).chain_err(|| format!("Cannot open `{}`", path.display()))?;
+    writeln!(file, "{}", to_string(entry)?)?;
+    Ok(())
+}
+
+/// Prints the trend of the total coverage of the recent `limit` runs to stdout.
+pub fn print(entries: &[Entry], limit: usize) -> Result<()> {
+    let stdout = io::stdout();
+    let mut lock = stdout.lock();
+
+    writeln!(lock, "{:<16}  {:<10}  {:>15}  {:>15}  {:>15}", "Date (UTC)", "Commit", "Lines", "Branches", "Functions")?;
+    let start = entries.len().saturating_sub(limit);
+    let mut previous = start.checked_sub(1).map(|i| entries[i].total());
+    for entry in &entries[start..] {
+        let total = entry.total();
+        let commit = entry.commit.as_ref().map_or("-", |c| &c[..c.len().min(10)]);
+        let lines = format_percent(total.lines_covered, total.lines_count);
+        let delta = format_delta((total.lines_covered, total.lines_count), previous.map(|p| (p.lines_covered, p.lines_count)));
+        writeln!(
+            lock,
+            "{:<16}  {:<10}  {:>15}  {:>15}  {:>15}",
+            format_timestamp(entry.timestamp),
+            commit,
+            lines + &delta,
+            format_percent(total.branches_taken, total.branches_count),
+            format_percent(total.functions_called, total.functions_count),
+        )?;
+        previous = Some(total);
+    }
+    Ok(())
+}
+
+/// Formats the line, branch and function coverage of `total` in one line, each followed by the change in percentage
+/// points since `previous` if it is given, e.g. `lines 85.00% (+2.50), branches 60.00%, functions 90.00%`.
+pub fn format_summary(total: &FileSummary, previous: Option<&FileSummary>) -> String {
+    let metric = |name: &str, value: (usize, usize), previous_value: Option<(usize, usize)>| {
+        format!("{} {}{}", name, format_percent(value.0, value.1), format_delta(value, previous_value))
+    };
+    format!(
+        "{}, {}, {}",
+        metric("lines", (total.lines_covered, total.lines_count), previous.map(|p| (p.lines_covered, p.lines_count))),
+        metric("branches", (total.branches_taken, total.branches_count), previous.map(|p| (p.branches_taken, p.branches_count))),
+        metric("functions", (total.functions_called, total.functions_count), previous.map(|p| (p.functions_called, p.functions_count))),
+    )
+}
+
+/// Formats the change of the percentage `(value, total)` since `previous` as ` (+1.23)`, or an empty string if there is
+/// nothing to compare or the change is negligible.
+fn format_delta(current: (usize, usize), previous: Option<(usize, usize)>) -> String {
+    match previous {
+        Some(p) if p.1 > 0 && current.1 > 0 => {
+            let diff = ratio(current.0, current.1) - ratio(p.0, p.1);
+            if diff.abs() < 0.005 {
+                String::new()
+            } else {
+                format!(" ({:+.2})", diff)
+            }
+        },
+        _ => String::new(),
+    }
+}
+
+#[test]
+fn test_format_summary() {
+    let mut previous = FileSummary::default();
+    previous.lines_count = 4;
+    previous.lines_covered = 2;
+    previous.branches_count = 2;
+    previous.branches_taken = 1;
+    let mut total = previous;
+    total.lines_covered = 3;
+
+    assert_eq!(format_summary(&total, None), "lines 75.00%, branches 50.00%, functions —");
+    assert_eq!(format_summary(&total, Some(&previous)), "lines 75.00% (+25.00), branches 50.00%, functions —");
+    assert_eq!(format_summary(&previous, Some(&total)), "lines 50.00% (-25.00), branches 50.00%, functions —");
+}
+
+/// Computes the percentage `100 * value / total`.
+fn ratio(value: usize, total: usize) -> f64 {
+    100.0 * value as f64 / total as f64
+}
+
+/// Formats the percentage `value / total`, or `—` if the total is zero.
+fn format_percent(value: usize, total: usize) -> String {
+    if total == 0 {
+        "—".to_owned()
+    } else {
+        format!("{:.2}%", ratio(value, total))
+    }
+}
+
+/// Formats the seconds since the Unix epoch as `YYYY-MM-DD HH:MM` in UTC.
+fn format_timestamp(timestamp: u64) -> String {
+    // Converts days to civil date, using the algorithm from <http://howardhinnant.github.io/date_algorithms.html>.
+    let days = timestamp / 86400 + 719_468;
+    let secs = timestamp % 86400;
+    let era = days / 146_097;
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, secs / 3600, secs / 60 % 60)
+}
+
+#[test]
+fn test_format_timestamp() {
+    assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+    assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00");
+    assert_eq!(format_timestamp(1_514_764_799), "2017-12-31 23:59");
+}
+
+/// Reads the commit hash of `HEAD` from the git repository containing the workspace, without running `git`.
+///
+/// Branch references are resolved through the loose reference files and `packed-refs`. Returns `None` if the workspace
+/// is not in a git repository.
+fn read_git_commit(workspace_path: &Path) -> Option<String> {
+    let git_dir = find_git_dir(workspace_path)?;
+    let head = read_trimmed(&git_dir.join("HEAD"))?;
+    if !head.starts_with("ref:") {
+        return Some(head);
+    }
+    let reference = head[4..].trim();
+
+    // A linked worktree stores the shared references in the common folder.
+    let common_dir = match read_trimmed(&git_dir.join("commondir")) {
+        Some(common) => git_dir.join(common),
+        None => git_dir.clone(),
+    };
+    for dir in &[&git_dir, &common_dir] {
+        if let Some(commit) = read_trimmed(&dir.join(reference)) {
+            return Some(commit);
+        }
+    }
+
+    let packed_refs = read_trimmed(&common_dir.join("packed-refs"))?;
+    packed_refs.lines().filter_map(|line| {
+        let mut parts = line.splitn(2, ' ');
+        let commit = parts.next()?;
+        if parts.next()? == reference {
+            Some(commit.to_owned())
+        } else {
+            None
+        }
+    }).next()
+}
+
+/// Finds the `.git` folder of the repository containing the path. The `.git` may also be a file pointing to the folder,
+/// e.g. in a linked worktree or submodule.
+fn find_git_dir(path: &Path) -> Option<PathBuf> {
+    let mut git_path = path.join(".git");
+    loop {
+        if git_path.is_dir() {
+            return Some(git_path);
+        } else if git_path.is_file() {
+            let content = read_trimmed(&git_path)?;
+            if content.starts_with("gitdir:") {
+                git_path.set_file_name(content[7..].trim());
+                return Some(git_path);
+            }
+        }
+        git_path.pop();
+        if !git_path.pop() {
+            return None;
+        }
+        git_path.push(".git");
+    }
+}
+
+/// Reads a small text file and trims the whitespaces. Returns `None` if the file cannot be read.
+fn read_trimmed(path: &Path) -> Option<String> {
+    let mut content = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut content)).ok()?;
+    Some(content.trim().to_owned())
+}
+
+#[test]
+fn test_read_git_commit() {
+    use std::fs::create_dir_all;
+    use tempfile::TempDir;
+
+    fn write(path: PathBuf, content: &str) -> io::Result<()> {
+        File::create(path)?.write_all(content.as_bytes())
+    }
+
+    let dir = TempDir::new().expect("created temporary directory");
+    let workspace = dir.path().join("crate");
+    let git_dir = dir.path().join(".git");
+    create_dir_all(git_dir.join("refs").join("heads")).unwrap();
+    create_dir_all(&workspace).unwrap();
+    assert_eq!(read_git_commit(&workspace), None);
+
+    write(git_dir.join("HEAD"), "0123456789abcdef0123456789abcdef01234567\n").unwrap();
+    assert_eq!(read_git_commit(&workspace).unwrap(), "0123456789abcdef0123456789abcdef01234567");
+
+    write(git_dir.join("HEAD"), "ref: refs/heads/master\n").unwrap();
+    write(git_dir.join("packed-refs"), "# pack-refs with: peeled fully-peeled sorted\nfedcba9876543210fedcba9876543210fedcba98 refs/heads/master\n").unwrap();
+    assert_eq!(read_git_commit(&workspace).unwrap(), "fedcba9876543210fedcba9876543210fedcba98");
+
+    write(git_dir.join("refs").join("heads").join("master"), "00112233445566778899aabbccddeeff00112233\n").unwrap();
+    assert_eq!(read_git_commit(&workspace).unwrap(), "00112233445566778899aabbccddeeff00112233");
+}
@@ -0,0 +1,166 @@
+//! Hot spots in the terminal.
+//!
+//! The counts in the profile data are exact execution counts, so the coverage of a benchmark or example (`cargo cov
+//! run`) doubles as a cheap deterministic profiler. `cargo cov hotspots` lists the most called functions with their
+//! share of the block executions of all functions, and the most executed lines with their share of the counts of all
+//! executed lines:
+//!
+//! ```text
+//! Hot functions
+//!      Calls      Blocks    Share  Function
+//!       1000       12000   60.00%  crate_name::inner (./src/lib.rs:12)
+//!          1        8000   40.00%  crate_name::outer (./src/lib.rs:4)
+//!
+//! Hot lines
+//!      Count    Share  Line
+//!       5000   25.00%  ./src/lib.rs:14 (crate_name::inner)
+//! ```
+//!
+//! With `--folded`, the count of every executed line is printed in the folded stack format instead, i.e. the frames
+//! `file;function;line` followed by the count, which can be rendered by flame graph tools like `flamegraph.pl` or
+//! `inferno-flamegraph`:
+//!
+//! ```text
+//! ./src/lib.rs;crate_name::inner;14 5000
+//! ```
+
+use error::Result;
+use functions::display_path;
+
+use cov::report::{File, FunctionGroup};
+
+use std::io::{self, Write};
+use std::path::{MAIN_SEPARATOR, Path};
+
+/// An executed source line.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct HotLine<'a> {
+    /// The source path, shortened by [`display_path()`].
+    ///
+    /// [`display_path()`]: ../functions/fn.display_path.html
+    path: String,
+    line: u32,
+    count: u64,
+    /// The generic name of the function containing the line.
+    function: &'a str,
+}
+
+/// Finds the function containing a line, i.e. the last function defined before it.
+fn function_of_line<'a>(groups: &[FunctionGroup<'a>], line: u32) -> &'a str {
+    groups.iter().take_while(|group| group.line <= line).last().map_or("?", |group| group.generic_name)
+}
+
+/// Lists every executed line of the files, in the order of the files and line numbers.
+fn executed_lines<'a>(files: &'a [(String, File)], crate_path: &str) -> Vec<HotLine<'a>> {
+    let mut lines = Vec::new();
+    for &(ref path, ref file) in files {
+        let path = display_path(path, crate_path);
+        let groups = file.function_groups();
+        lines.extend(file.lines.iter().filter(|&(_, line)| line.count > 0).map(|(&line_number, line)| HotLine {
+            path: path.clone(),
+            line: line_number,
+            count: line.count,
+            function: function_of_line(&groups, line_number),
+        }));
+    }
+    lines
+}
+
+/// Formats the executed lines in the folded stack format. Semicolons in the function names are replaced, since they
+/// separate the frames.
+fn fold(lines: &[HotLine]) -> Vec<String> {
+    lines
+        .iter()
+        .map(|line| format!("{};{};{} {}", line.path, line.function.replace(';', ","), line.line, line.count))
+        .collect()
+}
+
+#[test]
+fn test_fold() {
+    use cov::report::{Function, Line};
+
+    let function = |name: &str, line| Function {
+        generic_name: name.to_owned(),
+        line,
+        ..Function::default()
+    };
+    let mut file = File::default();
+    file.functions = vec![function("x::f", 3), function("x::g::<[u8; 4]>", 1)];
+    for &(line_number, count) in &[(1, 2), (2, 0), (3, 10), (4, 10), (6, 7)] {
+        file.lines.insert(line_number, Line { count, ..Line::default() });
+    }
+    let files = [("/ws/src/x.rs".to_owned(), file)];
+    let lines = executed_lines(&files, &format!("/ws{}", MAIN_SEPARATOR));
+
+    assert_eq!(lines.iter().map(|line| line.function).collect::<Vec<_>>(), ["x::g::<[u8; 4]>", "x::f", "x::f", "x::f"]);
+    let prefix = format!(".{}src/x.rs", MAIN_SEPARATOR);
+    assert_eq!(
+        fold(&lines),
+        vec![
+            format!("{};x::g::<[u8, 4]>;1 2", prefix),
+            format!("{};x::f;3 10", prefix),
+            format!("{};x::f;4 10", prefix),
+            format!("{};x::f;6 7", prefix),
+        ]
+    );
+}
+
+/// Computes the percentage `100 * value / total`.
+fn share(value: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        100.0 * value as f64 / total as f64
+    }
+}
+
+/// Prints the first `limit` most called functions and most executed lines to stdout. If `folded` is true, prints every
+/// executed line in the folded stack format instead. The source paths are shown relative to the workspace.
+pub fn print(files: &[(String, File)], limit: usize, folded: bool, workspace_path: &Path) -> Result<()> {
+    let mut crate_path = workspace_path.to_string_lossy().into_owned();
+    crate_path.push(MAIN_SEPARATOR);
+
+    let stdout = io::stdout();
+    let mut lock = stdout.lock();
+
+    let mut lines = executed_lines(files, &crate_path);
+    if folded {
+        for line in fold(&lines) {
+            writeln!(lock, "{}", line)?;
+        }
+        return Ok(());
+    }
+
+    let block_total = files.iter().flat_map(|&(_, ref file)| &file.functions).map(|f| f.summary.block_executions).sum::<u64>();
+
+    let mut functions = files.iter().flat_map(|&(ref path, ref file)| file.functions.iter().map(move |f| (path, f))).collect::<Vec<_>>();
+    functions.sort_by(|&(_, a), &(_, b)| {
+        let ordering = (b.summary.entry_count, b.summary.block_executions).cmp(&(a.summary.entry_count, a.summary.block_executions));
+        ordering.then_with(|| a.demangled_name.cmp(&b.demangled_name))
+    });
+    writeln!(lock, "Hot functions")?;
+    writeln!(lock, "{:>10}  {:>10}  {:>7}  Function", "Calls", "Blocks", "Share")?;
+    for &(path, function) in functions.iter().take(limit) {
+        writeln!(
+            lock,
+            "{:>10}  {:>10}  {:>6.2}%  {} ({}:{})",
+            function.summary.entry_count,
+            function.summary.block_executions,
+            share(function.summary.block_executions, block_total),
+            function.demangled_name,
+            display_path(path, &crate_path),
+            function.line,
+        )?;
+    }
+
+    // A block may span several lines, and a line may be counted in several blocks, so the lines are compared with the
+    // counts of the lines instead of the block executions.
+    let line_total = lines.iter().map(|line| line.count).sum::<u64>();
+    lines.sort_by(|a, b| b.count.cmp(&a.count));
+    writeln!(lock, "\nHot lines")?;
+    writeln!(lock, "{:>10}  {:>7}  Line", "Count", "Share")?;
+    for line in lines.iter().take(limit) {
+        writeln!(lock, "{:>10}  {:>6.2}%  {}:{} ({})", line.count, share(line.count, line_total), line.path, line.line, line.function)?;
+    }
+    Ok(())
+}
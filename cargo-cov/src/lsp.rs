@@ -0,0 +1,314 @@
+//! Coverage feed for editors over the Language Server Protocol.
+//!
+//! `cargo cov lsp` is a language server speaking JSON-RPC on stdin and stdout. It does not provide any language
+//! feature. Instead, whenever a source file in the coverage data is opened, every unexecuted line of it is published as
+//! a diagnostic of "hint" severity, which editors usually render as a subtle underline or gutter mark:
+//!
+//! ```json
+//! {"range": {"start": {"line": 2, "character": 0}, "end": {"line": 3, "character": 0}}, "severity": 4,
+//!  "source": "cov", "message": "not covered, reachable from line 2 when the branch is not taken"}
+//! ```
+//!
+//! The source files are selected and their paths are mapped like `cargo cov report` does, e.g. by `--include` and
+//! `--remap-path-prefix`. The profile data folder is polled for changes, and the diagnostics of the open documents are
+//! refreshed after the coverage is reloaded.
+//!
+//! Progress and errors are printed to stderr, which editors usually show in the log of the server.
+
+use argparse::ReportConfig;
+use error::Result;
+use report;
+use ui;
+use utils::{Snapshot, snapshot_files};
+
+use cov::report::File;
+use serde_json::{Value, from_slice, to_vec};
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::canonicalize;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{RecvTimeoutError, channel};
+use std::thread;
+use std::time::Duration;
+
+/// Interval between two checks of the profile data folder.
+const POLL_INTERVAL: u64 = 500;
+
+/// The `DiagnosticSeverity.Hint` of the protocol.
+const SEVERITY_HINT: u32 = 4;
+
+/// The `MethodNotFound` error code of JSON-RPC.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Entry point of `cargo cov lsp` subcommand. Serves the coverage until the client sends `exit`, or closes stdin.
+pub fn lsp(config: &ReportConfig) -> Result<()> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        let mut lock = stdin.lock();
+        loop {
+            match read_message(&mut lock) {
+                Ok(Some(message)) => if sender.send(message).is_err() {
+                    break;
+                },
+                Ok(None) => break,
+                Err(e) => {
+                    warning!("cannot read message: {}", e);
+                    break;
+                },
+            }
+        }
+    });
+
+    let take_snapshot = || {
+        let mut snapshot = Snapshot::new();
+        snapshot_files(&mut snapshot, &config.gcda_path, |_| true);
+        snapshot
+    };
+    let mut loaded_snapshot = take_snapshot();
+    let mut pending_snapshot = None;
+
+    let stdout = io::stdout();
+    let mut server = Server {
+        config,
+        output: stdout.lock(),
+        diagnostics: HashMap::new(),
+        documents: BTreeSet::new(),
+    };
+    // Keep serving if nothing has been built yet, the coverage will be loaded once the profile data appears.
+    server.reload()?;
+    loop {
+        match receiver.recv_timeout(Duration::from_millis(POLL_INTERVAL)) {
+            Ok(message) => if !server.handle(&message)? {
+                return Ok(());
+            },
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            Err(RecvTimeoutError::Timeout) => {
+                // Reload only after the profile data stays unchanged for one more interval, like `wait_for_change()`.
+                let snapshot = take_snapshot();
+                if snapshot == loaded_snapshot {
+                    pending_snapshot = None;
+                } else if pending_snapshot.as_ref() == Some(&snapshot) {
+                    loaded_snapshot = snapshot;
+                    pending_snapshot = None;
+                    progress!("Reloading", "{}", config.gcda_path.display());
+                    server.reload()?;
+                } else {
+                    pending_snapshot = Some(snapshot);
+                }
+            },
+        }
+    }
+}
+
+/// State of the language server.
+struct Server<'a, W> {
+    config: &'a ReportConfig<'a>,
+    output: W,
+    /// The diagnostics of every source file in the coverage data, keyed by the canonical path.
+    diagnostics: HashMap<PathBuf, Vec<Value>>,
+    /// URIs of the open documents.
+    documents: BTreeSet<String>,
+}
+
+impl<'a, W: Write> Server<'a, W> {
+    /// Handles a request or notification from the client. Returns false if the server should exit.
+    fn handle(&mut self, message: &Value) -> io::Result<bool> {
+        let method = message["method"].as_str().unwrap_or("");
+        let uri = message["params"]["textDocument"]["uri"].as_str();
+        match (method, &message["id"]) {
+            ("initialize", id) => {
+                let capabilities = json!({
+                    "textDocumentSync": {"openClose": true, "change": 0},
+                });
+                let result = json!({
+                    "capabilities": capabilities,
+                    "serverInfo": {"name": "cargo-cov", "version": crate_version!()},
+                });
+                self.send(&json!({"jsonrpc": "2.0", "id": id, "result": result}))?;
+            },
+            ("shutdown", id) => {
+                self.send(&json!({"jsonrpc": "2.0", "id": id, "result": null}))?;
+            },
+            ("exit", _) => return Ok(false),
+            ("textDocument/didOpen", _) => if let Some(uri) = uri {
+                self.documents.insert(uri.to_owned());
+                self.publish(uri)?;
+            },
+            ("textDocument/didClose", _) => if let Some(uri) = uri {
+                self.documents.remove(uri);
+                self.send_diagnostics(uri, &[])?;
+            },
+            (_, &Value::Null) => {}, // Notifications may be ignored.
+            (_, id) => {
+                let error = json!({"code": METHOD_NOT_FOUND, "message": format!("unsupported method `{}`", method)});
+                self.send(&json!({"jsonrpc": "2.0", "id": id, "error": error}))?;
+            },
+        }
+        Ok(true)
+    }
+
+    /// Reloads the coverage, and publishes the diagnostics of all open documents again. If the coverage cannot be read,
+    /// the error is printed and the previous diagnostics are kept.
+    fn reload(&mut self) -> io::Result<()> {
+        match load_diagnostics(self.config) {
+            Ok(diagnostics) => self.diagnostics = diagnostics,
+            Err(e) => return ui::print_error(&e),
+        }
+        let documents = self.documents.iter().cloned().collect::<Vec<_>>();
+        for uri in &documents {
+            self.publish(uri)?;
+        }
+        Ok(())
+    }
+
+    /// Publishes the diagnostics of a document. Documents not in the coverage data have no diagnostics.
+    fn publish(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics = match uri_to_path(uri) {
+            Some(path) => self.diagnostics.get(&canonical_path(&path)).cloned().unwrap_or_default(),
+            None => Vec::new(),
+        };
+        self.send_diagnostics(uri, &diagnostics)
+    }
+
+    fn send_diagnostics(&mut self, uri: &str, diagnostics: &[Value]) -> io::Result<()> {
+        let params = json!({"uri": uri, "diagnostics": diagnostics});
+        self.send(&json!({"jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": params}))
+    }
+
+    fn send(&mut self, message: &Value) -> io::Result<()> {
+        write_message(&mut self.output, message)
+    }
+}
+
+/// Reads the coverage, and creates the diagnostics of every source file, keyed by the canonical path.
+fn load_diagnostics(config: &ReportConfig) -> Result<HashMap<PathBuf, Vec<Value>>> {
+    let files = report::list_files_with_hints(config)?;
+    Ok(files.into_iter().map(|(path, file, hints)| (canonical_path(&path), diagnostics(&file, &hints))).collect())
+}
+
+/// Creates a diagnostic for every unexecuted line of the file, with the explanation from `hints` if any.
+fn diagnostics(file: &File, hints: &HashMap<u32, String>) -> Vec<Value> {
+    file.lines
+        .iter()
+        .filter(|&(_, line)| line.count == 0)
+        .map(|(&line_number, _)| {
+            let message = match hints.get(&line_number) {
+                Some(hint) => format!("not covered, {}", hint),
+                None => "not covered".to_owned(),
+            };
+            let line = line_number.saturating_sub(1);
+            json!({
+                "range": {"start": {"line": line, "character": 0}, "end": {"line": line + 1, "character": 0}},
+                "severity": SEVERITY_HINT,
+                "source": "cov",
+                "message": message,
+            })
+        })
+        .collect()
+}
+
+/// Resolves symbolic links in the path, so that the same file opened by another path is still found.
+fn canonical_path(path: &Path) -> PathBuf {
+    canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+/// Converts a `file://` URI to a path. Returns `None` for other schemes, or if the URI is malformed.
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    if !uri.starts_with("file://") {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(uri.len());
+    let mut rest = uri[7..].bytes();
+    while let Some(b) = rest.next() {
+        if b == b'%' {
+            let hex = [rest.next()?, rest.next()?];
+            bytes.push(u8::from_str_radix(::std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+    // Windows paths are written as `file:///C:/path`.
+    let is_windows_drive = path.len() >= 3 && path.as_bytes()[0] == b'/' && path.as_bytes()[2] == b':';
+    if cfg!(windows) && is_windows_drive {
+        Some(PathBuf::from(&path[1..]))
+    } else {
+        Some(PathBuf::from(path))
+    }
+}
+
+/// Reads a message framed by the `Content-Length` header. Returns `None` at the end of the stream.
+fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        let mut parts = header.splitn(2, ':');
+        if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let content_length = content_length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content)?;
+    let message = from_slice(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Some(message))
+}
+
+/// Writes a message framed by the `Content-Length` header.
+fn write_message<W: Write>(writer: &mut W, message: &Value) -> io::Result<()> {
+    let content = to_vec(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n", content.len())?;
+    writer.write_all(&content)?;
+    writer.flush()
+}
+
+#[test]
+fn test_uri_to_path() {
+    assert_eq!(uri_to_path("file:///src/my%20crate/lib.rs"), Some(PathBuf::from("/src/my crate/lib.rs")));
+    assert_eq!(uri_to_path("file:///src/%E2%9C%93.rs"), Some(PathBuf::from("/src/✓.rs")));
+    assert_eq!(uri_to_path("file:///src/%2"), None);
+    assert_eq!(uri_to_path("untitled:Untitled-1"), None);
+}
+
+#[test]
+fn test_message_framing() {
+    let message = json!({"jsonrpc": "2.0", "method": "exit"});
+    let mut buffer = Vec::new();
+    write_message(&mut buffer, &message).unwrap();
+    write_message(&mut buffer, &message).unwrap();
+
+    let mut reader = io::Cursor::new(buffer);
+    assert_eq!(read_message(&mut reader).unwrap(), Some(message.clone()));
+    assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+    assert_eq!(read_message(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_diagnostics() {
+    use cov::report::Line;
+
+    let mut file = File::default();
+    for &(line_number, count) in &[(1, 5), (2, 0), (3, 0)] {
+        file.lines.insert(line_number, Line { count, ..Line::default() });
+    }
+    let mut hints = HashMap::new();
+    hints.insert(2, "reachable from line 1 when the branch is taken".to_owned());
+
+    let diagnostics = diagnostics(&file, &hints);
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+    assert_eq!(diagnostics[0]["message"], "not covered, reachable from line 1 when the branch is taken");
+    assert_eq!(diagnostics[1]["range"]["end"]["line"], 3);
+    assert_eq!(diagnostics[1]["message"], "not covered");
+}
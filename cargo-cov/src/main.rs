@@ -44,20 +44,37 @@ extern crate walkdir;
 #[macro_use]
 mod ui;
 mod argparse;
+mod badge;
 mod cargo;
 mod error;
+mod functions;
+mod history;
+mod hotspots;
 mod lookup;
+mod lsp;
 mod report;
+mod review;
+mod serve;
 mod shim;
+mod show;
 mod sourcepath;
 mod template;
 mod utils;
+mod watch;
 
 use argparse::*;
 use cargo::Cargo;
-use clap::ArgMatches;
-use error::Result;
+use clap::{Arg, ArgMatches};
+use cov::cfg::CfgFilter;
+use badge::Metric;
+use error::{ErrorKind, Result, ResultExt};
+use functions::SortKey;
+use utils::sum_file_summaries;
 
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
 use std::process::exit;
 
 /// Program entry. Calls [`run()`] and prints any error returned to `stderr`.
@@ -92,14 +109,14 @@ fn run() -> Result<()> {
     }
     debug!("matches = {:?}", matches);
 
-    // Read the --profiler/--target/--manifest-path options specified before the subcommand:
+    // Read the special options (--profiler/--target/--manifest-path etc) specified before the subcommand:
     //
     //     cargo cov --manifest-path Cargo.toml clean ...
     //               ^~~~~~~~~~~~~~~~~~~~~~~~~~
-    let mut special_args = SpecialMap::with_capacity(3);
+    let mut special_args = SpecialMap::with_capacity(6);
     update_from_clap(matches, &mut special_args);
 
-    // Read the --profiler/--target/--manifest-path options specified after the subcommand:
+    // Read the special options (--profiler/--target/--manifest-path etc) specified after the subcommand:
     //
     //     cargo cov clean --manifest-path Cargo.toml ...
     //                     ^~~~~~~~~~~~~~~~~~~~~~~~~~
@@ -107,8 +124,9 @@ fn run() -> Result<()> {
     let matches = matches.expect("matches");
     update_from_clap(matches, &mut special_args);
 
-    // Extracting --profiler/--target/--manifest-path if they are written in an external subcommand (build, test, run).
-    let forward_args = match matches.values_of_os("") {
+    // Extracting the special options if they are written in an external subcommand (build, test, bench, run, exec), or
+    // after the `--` of `watch`.
+    let forward_args = match matches.values_of_os("").or_else(|| matches.values_of_os("args")) {
         Some(args) => normalize(args, &mut special_args),
         None => Vec::new(),
     };
@@ -116,9 +134,20 @@ fn run() -> Result<()> {
 
     // Actually run the subcommands. Please do not pass ArgMatches as a whole to the receiver functions.
     match subcommand {
-        "build" | "test" | "run" => cargo?.forward(subcommand)?,
+        "build" | "test" | "bench" | "run" => forward(cargo?, subcommand, matches)?,
+        "exec" => cargo?.exec()?,
         "clean" => clean(&cargo?, matches)?,
         "report" => generate_reports(cargo, matches)?,
+        "serve" => serve_report(cargo, matches)?,
+        "watch" => watch_tests(cargo?, matches)?,
+        "history" => show_history(cargo, matches)?,
+        "badge" => generate_badges(cargo, matches)?,
+        "functions" => list_functions(cargo, matches)?,
+        "show" => show_source(cargo, matches)?,
+        "lsp" => serve_lsp(cargo, matches)?,
+        "review" => review_diff(cargo, matches)?,
+        "hotspots" => show_hotspots(cargo, matches)?,
+        "cfg" => export_cfg(cargo, matches)?,
         _ => ui::print_unknown_subcommand(subcommand)?,
     }
 
@@ -139,7 +168,9 @@ Options:
 Subcommands:
     build     Compile the crate and produce coverage data (*.gcno)
     test      Test the crate and produce profile data (*.gcda)
-    run       Run a program and produces profile data (*.gcda)
+    bench     Run the benchmarks and produce profile data (*.gcda)
+    run       Run a program and produces profile data (*.gcda), e.g. `cargo cov run --example demo`
+    exec      Run an arbitrary command using the built binaries, e.g. `cargo cov exec -- ./e2e.sh`
 {subcommands}
 ";
 
@@ -158,6 +189,11 @@ Subcommands:
             (@arg profiler: --profiler [LIB] +global "Path to `libclang_rt.profile_*.a`")
             (@arg target: --target [TRIPLE] +global "Target triple which the covered program will run in")
             (@arg ("manifest-path"): --("manifest-path") [PATH] +global "Path to the manifest of the package")
+            (@arg runner: --runner [COMMAND] +global "Command which runs the covered program, e.g. `qemu-arm -L /sysroot`")
+            (@arg ("gcov-prefix"): --("gcov-prefix") [PATH] +global "Folder the covered program writes *.gcda into, passed to the runner as $GCOV_PREFIX")
+            (@arg ("gcda-sysroot"): --("gcda-sysroot") [PATH] +global "Local folder to collect *.gcda written under $GCOV_PREFIX, default to the --gcov-prefix")
+            (@arg backend: --backend [BACKEND] +global possible_values(&["gcov", "source"]) "Coverage backend, `gcov` (default) or `source` (-Cinstrument-coverage)")
+            (@arg configuration: --configuration [TAG] +global "Tag the coverage of this build by a configuration, e.g. `--configuration=--features=a`")
             (@subcommand clean =>
                 (about: "Clean coverage artifacts")
                 (@setting UnifiedHelpMessage)
@@ -167,34 +203,145 @@ Subcommands:
                     (@arg all_crates: --("all-crates") "Remove build artifacts of all crates")
                 )
                 (@arg report: --report "Remove the coverage report")
+                (@arg history: --history "Remove the coverage history")
             )
             (@subcommand report =>
                 (about: "Generates a coverage report")
                 (@arg template: --template [TEMPLATE] "Report template, default to 'html'")
                 (@arg open: --open "Open the report in browser after it is generated")
-                (@arg include: --include [TYPES]... +use_delimiter possible_values(&[
-                    "local",
-                    "macros",
-                    "rustsrc",
-                    "crates",
-                    "unknown",
-                    "all",
-                ]) "Generate reports for some specific sources")
-                (@arg workspace: --workspace [PATH] "The directory to find the source code, default to the current Cargo workspace")
+                (args: &report_args(ReportArgs::all()))
                 (@arg output: --output -o [PATH] "The directory to store the generated report, default to `<src>/target/cov/report/`")
-                (@arg gcno: --gcno [PATH] "The directory that contains all *.gcno files, default to `<src>/target/cov/build/gcno/`")
-                (@arg gcda: --gcda [PATH] "The directory that contains all *.gcda files, default to `<src>/target/cov/build/gcda/`")
+            )
+            (@subcommand serve =>
+                (about: "Serves the coverage report on localhost, and regenerates it whenever the profile data changes")
+                (@arg port: --port [PORT] "Port to listen on, default to 8000, or 0 to pick a free port")
+                (@arg open: --open "Open the report in browser after the server is started")
+                (@arg template: --template [TEMPLATE] "Report template, default to 'html'")
+                (args: &report_args(ReportArgs::all()))
+                (@arg output: --output -o [PATH] "The directory to store the generated report, default to `<src>/target/cov/report/`")
+            )
+            (@subcommand watch =>
+                (about: "Runs the tests and regenerates the coverage report whenever a source file changes")
+                (@arg template: --template [TEMPLATE] "Report template, default to 'html'")
+                (args: &report_args(ReportArgs::PACKAGE | ReportArgs::FILTER | ReportArgs::PANIC_PATHS))
+                (@arg output: --output -o [PATH] "The directory to store the generated report, default to `<src>/target/cov/report/`")
+                (@arg args: [ARGS]... +last "Arguments forwarded to `cargo test` after `--`, e.g. `cargo cov watch -- --lib`")
+            )
+            (@subcommand badge =>
+                (about: "Generates SVG coverage badges for the workspace and each package")
+                (@arg metric: --metric [METRIC]... +use_delimiter possible_values(&["lines", "branches", "functions"]) "Metrics to draw badges for, default to all")
+                (args: &report_args(ReportArgs::FILTER | ReportArgs::PANIC_PATHS | ReportArgs::INPUT))
+                (@arg output: --output -o [PATH] "The directory to store the badges, default to `<src>/target/cov/badge/`")
+            )
+            (@subcommand functions =>
+                (about: "Lists the functions with their cyclomatic complexity, coverage and CRAP score")
+                (@arg sort: --sort [KEY] possible_values(&["crap", "complexity", "coverage", "calls", "name"]) "Order of the functions, default to 'crap'")
+                (@arg limit: --limit -n [COUNT] "Number of functions to show, default to 20")
+                (args: &report_args(ReportArgs::all()))
+            )
+            (@subcommand hotspots =>
+                (about: "Lists the most called functions and most executed lines, using the coverage as a profile")
+                (@arg limit: --limit -n [COUNT] "Number of functions and lines to show, default to 20")
+                (@arg folded: --folded "Print the count of every executed line in the folded stack format for flame graphs")
+                (args: &report_args(ReportArgs::PACKAGE | ReportArgs::FILTER | ReportArgs::INPUT))
+            )
+            (@subcommand show =>
+                (about: "Shows a source file with the line counts, and how the unexecuted lines could have been reached")
+                (@arg path: +required "Path of the source file, or its trailing components like `src/lib.rs`")
+                (args: &report_args(ReportArgs::PANIC_PATHS | ReportArgs::INPUT))
+            )
+            (@subcommand review =>
+                (about: "Prints the uncovered lines and untaken branches added by a diff as code review annotations")
+                (@arg diff: --diff [PATH] +required "Unified diff to annotate (e.g. the output of `git diff`), or `-` to read from stdin")
+                (@arg format: --format [FORMAT] possible_values(&["github", "rdjson", "sarif"]) "Output format, default to 'github'")
+                (args: &report_args(ReportArgs::all()))
+            )
+            (@subcommand lsp =>
+                (about: "Runs a language server on stdio, publishing the unexecuted lines of open files as hint diagnostics")
+                (args: &report_args(ReportArgs::all()))
+            )
+            (@subcommand cfg =>
+                (about: "Exports the control-flow graphs of functions as a Mermaid flowchart or JSON (GCNO/GCDA only)")
+                (@arg format: --format [FORMAT] possible_values(&["mermaid", "json"]) "Output format, default to 'mermaid'")
+                (@arg file: --file [PATH]... number_of_values(1) "Only export the functions in this source file, or its trailing components like `src/lib.rs`")
+                (@arg lines: --lines [RANGE] "Only export the functions having code in this line range, as `START-END` or `LINE`")
+                (@arg function: --function [PATTERN] "Only export the functions whose demangled name matches this pattern, where `*` matches anything")
+                (args: &report_args(ReportArgs::INPUT))
+            )
+            (@subcommand history =>
+                (about: "Shows the coverage trend of previous reports")
+                (@arg limit: --limit -n [COUNT] "Number of recent reports to show, default to 20")
+                (args: &report_args(ReportArgs::PACKAGE | ReportArgs::FILTER | ReportArgs::PANIC_PATHS))
+                (@arg workspace: --workspace [PATH] "The directory of the Cargo workspace, default to the current one")
             )
         )
     ).get_matches()
 }
 
-/// Parses the command line arguments and forwards to [`report::generate()`].
+bitflags! {
+    /// Groups of the arguments shared by the subcommands reading the coverage data, see [`report_args()`].
+    ///
+    /// [`report_args()`]: ./fn.report_args.html
+    struct ReportArgs: u8 {
+        /// `--package`, only reporting the source files of a workspace package.
+        const PACKAGE = 1;
+        /// `--include` and `--category`, selecting the reported source files by type or glob.
+        const FILTER = 2;
+        /// `--exclude-panic-paths`.
+        const PANIC_PATHS = 4;
+        /// `--workspace`, `--gcno` and `--gcda`, locating the source code and the coverage data.
+        const INPUT = 8;
+    }
+}
+
+/// Creates the arguments shared by the subcommands reading the coverage data, which are parsed by
+/// [`ReportConfig::parse()`]. `--remap-path-prefix` is always included.
+///
+/// [`ReportConfig::parse()`]: argparse/struct.ReportConfig.html#method.parse
+fn report_args(groups: ReportArgs) -> Vec<Arg<'static, 'static>> {
+    let mut args = Vec::new();
+    if groups.contains(ReportArgs::PACKAGE) {
+        args.push(Arg::from_usage("-p, --package [NAME] 'Only report the source files of this workspace package'"));
+    }
+    if groups.contains(ReportArgs::FILTER) {
+        args.push(Arg::from_usage("--include [TYPES]... 'Source types (local, crates, git, all, ...) or --category names to report'").use_delimiter(true));
+        args.push(Arg::from_usage("--category [RULE]... 'Define a source category by glob as `NAME=GLOB`, usable in --include'").number_of_values(1));
+    }
+    args.push(Arg::from_usage("--remap-path-prefix [RULE]... 'Remap source paths starting with FROM to TO, as `FROM=TO`'").number_of_values(1));
+    if groups.contains(ReportArgs::PANIC_PATHS) {
+        args.push(Arg::from_usage("--exclude-panic-paths 'Count panic and unwind paths separately from branches and lines (GCNO/GCDA only)'"));
+    }
+    if groups.contains(ReportArgs::INPUT) {
+        args.push(Arg::from_usage("--workspace [PATH] 'The directory to find the source code, default to the current Cargo workspace'"));
+        args.push(Arg::from_usage("--gcno [PATH] 'The directory that contains all *.gcno files, default to `<src>/target/cov/build/gcno/`'"));
+        args.push(Arg::from_usage("--gcda [PATH] 'The directory that contains all *.gcda files, default to `<src>/target/cov/build/gcda/`'"));
+    }
+    args
+}
+
+/// Runs the cargo subcommand with coverage. With `--feature-matrix`, the coverage of all feature sets is merged into one
+/// report using [`report::generate()`] afterwards, unless only building.
+///
+/// [`report::generate()`]: report/fn.generate.html
+fn forward(mut cargo: Cargo, subcommand: &str, matches: &ArgMatches) -> Result<()> {
+    let has_feature_matrix = cargo.has_feature_matrix();
+    cargo.forward(subcommand)?;
+    if has_feature_matrix && subcommand != "build" {
+        let report_config = ReportConfig::parse(matches, Ok(cargo.into_cov_build_path_and_packages()), "report")?;
+        report::generate(&report_config)?;
+    }
+    Ok(())
+}
+
+/// Parses the command line arguments and forwards to [`report::generate()`], then records the report in the history
+/// with [`history::append()`].
 ///
 /// [`report::generate()`]: report/fn.generate.html
+/// [`history::append()`]: history/fn.append.html
 fn generate_reports(cargo: Result<Cargo>, matches: &ArgMatches) -> Result<()> {
-    let report_config = ReportConfig::parse(matches, cargo.map(Cargo::into_cov_build_path))?;
-    let open_path = report::generate(&report_config)?;
+    let report_config = ReportConfig::parse(matches, cargo.map(Cargo::into_cov_build_path_and_packages), "report")?;
+    let (open_path, history_entry) = report::generate(&report_config)?;
+    history::append(&report_config.history_path, &history_entry).chain_err(|| "Cannot update coverage history")?;
     if matches.is_present("open") {
         if let Some(path) = open_path {
             progress!("Opening", "{}", path.display());
@@ -210,6 +357,167 @@ fn generate_reports(cargo: Result<Cargo>, matches: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+/// Parses the command line arguments and forwards to [`serve::serve()`].
+///
+/// [`serve::serve()`]: serve/fn.serve.html
+fn serve_report(cargo: Result<Cargo>, matches: &ArgMatches) -> Result<()> {
+    let report_config = ReportConfig::parse(matches, cargo.map(Cargo::into_cov_build_path_and_packages), "report")?;
+    let port = if matches.is_present("port") {
+        value_t_or_exit!(matches, "port", u16)
+    } else {
+        8000
+    };
+    serve::serve(&report_config, port, matches.is_present("open"))
+}
+
+/// Parses the command line arguments and forwards to [`watch::watch()`].
+///
+/// [`watch::watch()`]: watch/fn.watch.html
+fn watch_tests(mut cargo: Cargo, matches: &ArgMatches) -> Result<()> {
+    let report_config = ReportConfig::parse(matches, Ok(cargo.cov_build_path_and_packages()), "report")?;
+    watch::watch(&mut cargo, &report_config)
+}
+
+/// Parses the command line arguments, summarizes the coverage with [`report::summarize()`], and writes the badges of
+/// the workspace and every package using [`badge::write_badges()`].
+///
+/// [`report::summarize()`]: report/fn.summarize.html
+/// [`badge::write_badges()`]: badge/fn.write_badges.html
+fn generate_badges(cargo: Result<Cargo>, matches: &ArgMatches) -> Result<()> {
+    let config = ReportConfig::parse(matches, cargo.map(Cargo::into_cov_build_path_and_packages), "badge")?;
+    let metrics = match matches.values_of("metric") {
+        Some(values) => values.map(|v| v.parse().expect("Metric")).collect(),
+        None => Metric::ALL.to_vec(),
+    };
+
+    let summaries = report::summarize(&config)?;
+    badge::write_badges(&config.output_path, &metrics, &sum_file_summaries(summaries.values()))?;
+
+    let mut package_summaries = HashMap::new();
+    for (path, summary) in &summaries {
+        if let Some(package) = config.package_of(path) {
+            package_summaries.entry(&*package.name).or_insert_with(Vec::new).push(summary);
+        }
+    }
+    for package in &config.packages {
+        let summary = sum_file_summaries(package_summaries.remove(&*package.name).unwrap_or_default());
+        badge::write_badges(&config.output_path.join(&package.name), &metrics, &summary)?;
+    }
+    Ok(())
+}
+
+/// Parses the command line arguments, and prints the functions found by [`report::list_functions()`] using
+/// [`functions::print()`].
+///
+/// [`report::list_functions()`]: report/fn.list_functions.html
+/// [`functions::print()`]: functions/fn.print.html
+fn list_functions(cargo: Result<Cargo>, matches: &ArgMatches) -> Result<()> {
+    let config = ReportConfig::parse(matches, cargo.map(Cargo::into_cov_build_path_and_packages), "report")?;
+    let key = matches.value_of("sort").map_or(SortKey::Crap, |key| key.parse().expect("SortKey"));
+    let limit = if matches.is_present("limit") {
+        value_t_or_exit!(matches, "limit", usize)
+    } else {
+        20
+    };
+
+    let mut functions = report::list_functions(&config)?;
+    functions::print(&mut functions, key, limit, &config.workspace_path)
+}
+
+/// Parses the command line arguments, and prints the hot spots of the files found by [`report::list_files()`] using
+/// [`hotspots::print()`].
+///
+/// [`report::list_files()`]: report/fn.list_files.html
+/// [`hotspots::print()`]: hotspots/fn.print.html
+fn show_hotspots(cargo: Result<Cargo>, matches: &ArgMatches) -> Result<()> {
+    let config = ReportConfig::parse(matches, cargo.map(Cargo::into_cov_build_path_and_packages), "report")?;
+    let limit = if matches.is_present("limit") {
+        value_t_or_exit!(matches, "limit", usize)
+    } else {
+        20
+    };
+
+    let files = report::list_files(&config)?;
+    hotspots::print(&files, limit, matches.is_present("folded"), &config.workspace_path)
+}
+
+/// Parses the command line arguments and forwards to [`report::export_cfg()`].
+///
+/// [`report::export_cfg()`]: report/fn.export_cfg.html
+fn export_cfg(cargo: Result<Cargo>, matches: &ArgMatches) -> Result<()> {
+    let config = ReportConfig::parse(matches, cargo.map(Cargo::into_cov_build_path_and_packages), "report")?;
+    let paths = matches.values_of_os("file").into_iter().flat_map(|it| it).map(Path::new).collect::<Vec<_>>();
+    let lines = match matches.value_of("lines") {
+        Some(range) => Some(parse_line_range(range).ok_or_else(|| ErrorKind::InvalidLineRange(range.to_owned()))?),
+        None => None,
+    };
+    let filter = CfgFilter {
+        filenames: Vec::new(),
+        lines,
+        name_pattern: matches.value_of("function").map(str::to_owned),
+    };
+    report::export_cfg(&config, &paths, filter, matches.value_of("format") == Some("json"))
+}
+
+/// Parses the command line arguments and forwards to [`report::show()`].
+///
+/// [`report::show()`]: report/fn.show.html
+fn show_source(cargo: Result<Cargo>, matches: &ArgMatches) -> Result<()> {
+    let config = ReportConfig::parse(matches, cargo.map(Cargo::into_cov_build_path_and_packages), "report")?;
+    let path = matches.value_of_os("path").expect("path");
+    report::show(&config, Path::new(path))
+}
+
+/// Parses the command line arguments and forwards to [`lsp::lsp()`].
+///
+/// [`lsp::lsp()`]: lsp/fn.lsp.html
+fn serve_lsp(cargo: Result<Cargo>, matches: &ArgMatches) -> Result<()> {
+    let config = ReportConfig::parse(matches, cargo.map(Cargo::into_cov_build_path_and_packages), "report")?;
+    lsp::lsp(&config)
+}
+
+/// Parses the command line arguments and the diff, and prints the annotations created by [`review::collect()`].
+///
+/// [`review::collect()`]: review/fn.collect.html
+fn review_diff(cargo: Result<Cargo>, matches: &ArgMatches) -> Result<()> {
+    let config = ReportConfig::parse(matches, cargo.map(Cargo::into_cov_build_path_and_packages), "report")?;
+    let format = matches.value_of("format").map_or(review::Format::GitHub, |format| format.parse().expect("Format"));
+    let diff_path = matches.value_of_os("diff").expect("diff");
+    let diff = if diff_path == "-" {
+        let stdin = io::stdin();
+        let lock = stdin.lock();
+        review::parse_diff(lock)?
+    } else {
+        let file = File::open(diff_path).chain_err(|| format!("Cannot open diff at `{}`", Path::new(diff_path).display()))?;
+        review::parse_diff(BufReader::new(file))?
+    };
+
+    let files = report::list_files_with_hints(&config)?;
+    let annotations = review::collect(&files, &diff, &config.workspace_path);
+    let stdout = io::stdout();
+    let mut lock = stdout.lock();
+    review::write(&mut lock, &annotations, format)
+}
+
+/// Parses the command line arguments and forwards to [`history::print()`].
+///
+/// [`history::print()`]: history/fn.print.html
+fn show_history(cargo: Result<Cargo>, matches: &ArgMatches) -> Result<()> {
+    let config = ReportConfig::parse(matches, cargo.map(Cargo::into_cov_build_path_and_packages), "report")?;
+    let limit = if matches.is_present("limit") {
+        value_t_or_exit!(matches, "limit", usize)
+    } else {
+        20
+    };
+
+    let entries = history::load(&config.history_path, &config.selection())?;
+    if entries.is_empty() {
+        warning!("no coverage history at {} of these options, run `cargo cov report` first", config.history_path.display());
+        return Ok(());
+    }
+    history::print(&entries, limit)
+}
+
 /// Parses the command line arguments and forwards to [`Cargo::clean()`].
 ///
 /// [`Cargo::clean()`]: cargo/struct.Cargo.html#method.clean
@@ -228,6 +536,9 @@ fn clean(cargo: &Cargo, matches: &ArgMatches) -> Result<()> {
     if matches.is_present("report") {
         clean_target |= CleanTargets::REPORT;
     }
+    if matches.is_present("history") {
+        clean_target |= CleanTargets::HISTORY;
+    }
 
     if clean_target.is_empty() {
         clean_target = CleanTargets::BUILD_GCDA | CleanTargets::BUILD_GCNO;
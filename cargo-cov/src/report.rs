@@ -47,15 +47,25 @@
 //! output = "summary.ext"
 //! ```
 //!
-//! The summary page will be rendered to the file `summary.ext` using this data:
+//! The summary page will be rendered to the file `summary.ext` using this data. The `host_kind` is `"build-script"` or
+//! `"proc-macro"` if the file is compiled into a build script or procedural macro, which are executed during the build.
+//! The `history` lists the summaries of the file in the recent 30 reports of the same `--include`, `--category`,
+//! `--package` etc. (including the current one) from `target/cov/history/`, oldest first, for drawing the coverage
+//! trend. The `package` is the name of the workspace package owning the file, or `null` for other sources. The
+//! `packages` groups the same `files` by package, with the summary of each package. With `--exclude-panic-paths`,
+//! `exclude_panic_paths` is `true`, and the branches on panic and unwind paths are counted in `panic_paths_count` and
+//! `panic_paths_taken` instead of the lines and branches:
 //!
 //! ```json
 //! {
 //!     "crate_path": "/path/to/workspace",
+//!     "exclude_panic_paths": false,
 //!     "files": [
 //!         {
 //!             "symbol": 123,
 //!             "path": "/path/to/workspace/src/lib.rs",
+//!             "host_kind": null,
+//!             "package": "crate_name",
 //!             "summary": {
 //!                 "lines_count": 500,
 //!                 "lines_covered": 499,
@@ -63,8 +73,35 @@
 //!                 "branches_executed": 650,
 //!                 "branches_taken": 520,
 //!                 "functions_count": 40,
-//!                 "functions_called": 39
-//!             }
+//!                 "functions_called": 39,
+//!                 "panic_paths_count": 0,
+//!                 "panic_paths_taken": 0
+//!             },
+//!             "history": [
+//!                 {
+//!                     "timestamp": 1514764800,
+//!                     "commit": "0123456789abcdef0123456789abcdef01234567",
+//!                     "summary": {
+//!                         "lines_count": 500,
+//!                         "lines_covered": 480,
+//!                         ...
+//!                     }
+//!                 },
+//!                 ...
+//!             ]
+//!         },
+//!         ...
+//!     ],
+//!     "packages": [
+//!         {
+//!             "name": "crate_name",
+//!             "summary": {
+//!                 "lines_count": 1500,
+//!                 ...
+//!             },
+//!             "files": [
+//!                 ...
+//!             ]
 //!         },
 //!         ...
 //!     ]
@@ -81,13 +118,27 @@
 //! output = "file_{{ symbol }}.ext"
 //! ```
 //!
-//! The output filename itself is a Tera template. The file pages will be rendered using this data:
+//! The output filename itself is a Tera template. The file pages will be rendered using this data. The `segments` split
+//! an executed line into parts, flagging those which have never been executed, e.g. the unused `else` branch of an `if`
+//! expression. They are only available when the coverage data has column information (`--backend source`), and are
+//! empty if the whole line is covered. The `loop_stats` is available on the header line of a loop (GCNO/GCDA only),
+//! counting how many times the loop is entered and iterated. The `uncovered_path` is available on unexecuted lines
+//! (GCNO/GCDA only), pointing to the nearest branch which has never been followed to reach the line, and whether that
+//! branch should have been `taken` or not. The `configurations` list the tags (`cargo cov --configuration`) of the
+//! builds which have executed the line, and the `count` is then the total of all configurations. Every monomorphized
+//! instance of a generic function appears separately in `functions`, together with the `configuration` it is built
+//! under, while `function_groups` merges the instances sharing the same definition. The `crap` is the [CRAP score]
+//! computed from the `complexity` and the block coverage. The `cfg` is the control-flow graph of the function as a
+//! Mermaid flowchart (GCNO/GCDA only):
 //!
 //! ```json
 //! {
 //!     "crate_path": "/path/to/workspace",
+//!     "exclude_panic_paths": false,
 //!     "symbol": 123,
 //!     "path": "/path/to/workspace/src/lib.rs",
+//!     "host_kind": null,
+//!     "package": "crate_name",
 //!     "summary": {
 //!         "lines_count": 500,
 //!         ...
@@ -97,7 +148,12 @@
 //!             "line": 1,
 //!             "source": "/// First line of the source code",
 //!             "count": null,
-//!             "branches": []
+//!             "branches": [],
+//!             "covered_by": [],
+//!             "configurations": [],
+//!             "segments": [],
+//!             "loop_stats": null,
+//!             "uncovered_path": null
 //!         },
 //!         {
 //!             "line": 2,
@@ -112,7 +168,55 @@
 //!                     "column: 0
 //!                 },
 //!                 ...
-//!             ]
+//!             ],
+//!             "covered_by": [
+//!                 "crate_name::tests::test_second_line"
+//!             ],
+//!             "configurations": [
+//!                 "--features=std",
+//!                 "--no-default-features"
+//!             ],
+//!             "segments": [
+//!                 {
+//!                     "source": "pub fn second_line_of_source_code() ",
+//!                     "uncovered": false
+//!                 },
+//!                 {
+//!                     "source": "{",
+//!                     "uncovered": true
+//!                 }
+//!             ],
+//!             "loop_stats": null,
+//!             "uncovered_path": null
+//!         },
+//!         {
+//!             "line": 3,
+//!             "source": "    for i in 0..n {",
+//!             "count": 132,
+//!             ...
+//!             "loop_stats": {
+//!                 "entry_count": 12,
+//!                 "iterations": 120,
+//!                 "mean_iterations": 10.0
+//!             },
+//!             "uncovered_path": null
+//!         },
+//!         {
+//!             "line": 4,
+//!             "source": "        if i > 100 {",
+//!             ...
+//!         },
+//!         {
+//!             "line": 5,
+//!             "source": "            return;",
+//!             "count": 0,
+//!             ...
+//!             "uncovered_path": {
+//!                 "symbol": 123,
+//!                 "path": "/path/to/workspace/src/lib.rs",
+//!                 "line": 4,
+//!                 "taken": false
+//!             }
 //!         },
 //!         ...
 //!     ],
@@ -120,6 +224,8 @@
 //!         {
 //!             "symbol": 789,
 //!             "name": "_ZN10crate_name26second_line_of_source_code17hce04ea776f1a67beE",
+//!             "demangled_name": "crate_name::second_line_of_source_code",
+//!             "generic_name": "crate_name::second_line_of_source_code",
 //!             "line": 2,
 //!             "column": 0,
 //!             "summary": {
@@ -129,56 +235,278 @@
 //!                 "exit_count": 10,
 //!                 "branches_count": 250,
 //!                 "branches_executed": 225,
-//!                 "branches_taken": 219
-//!             }
+//!                 "branches_taken": 219,
+//!                 "panic_paths_count": 0,
+//!                 "panic_paths_taken": 0,
+//!                 "complexity": 12,
+//!                 "block_executions": 4800
+//!             },
+//!             "crap": 12.14,
+//!             "cfg": "flowchart TD\n    ...",
+//!             "covered_by": [
+//!                 "crate_name::tests::test_second_line"
+//!             ],
+//!             "configuration": "--features=std"
+//!         },
+//!         ...
+//!     ],
+//!     "function_groups": [
+//!         {
+//!             "generic_name": "crate_name::largest",
+//!             "line": 5,
+//!             "column": 0,
+//!             "instances": [
+//!                 "crate_name::largest::<f64>",
+//!                 "crate_name::largest::<i32>"
+//!             ],
+//!             "summary": {
+//!                 ...
+//!             },
+//!             "crap": 3.0,
+//!             "covered_by": [
+//!                 "crate_name::tests::test_largest"
+//!             ],
+//!             "configurations": [
+//!                 "--features=std"
+//!             ]
 //!         },
 //!         ...
 //!     ]
 //! }
 //! ```
+//!
+//! [CRAP score]: ../../cov/report/struct.FunctionSummary.html#method.crap
 
-use argparse::ReportConfig;
-use error::{Result, ResultExt};
+use argparse::{HOST_CRATE_KINDS, ReportConfig};
+use error::{ErrorKind, Result, ResultExt};
+use history;
+use show;
 use sourcepath::{SourceType, identify_source_path};
 use template::new as new_template;
-use utils::clean_dir;
+use utils::{clean_dir, sum_file_summaries};
 
 use fs_extra::dir;
-use cov::{self, Gcov, Graph, Interner, Report, Symbol};
+use cov::{self, BranchDirection, Gcov, Graph, Interner, Report, SerializeWithInterner, Symbol, UncoveredPath, llvm};
+use cov::cfg::{CfgFilter, write_mermaid};
+use cov::intern::UNKNOWN_SYMBOL;
+use cov::report::{FileSummary, Function, Region};
 use serde_json::Value;
 use tera::{Context, Tera};
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsStr;
 use std::fs::{File, create_dir_all, read_dir};
-use std::io::{BufRead, BufReader, Read, Write};
-use std::path::PathBuf;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Maximum number of previous runs given to the summary page as the coverage trend of a file.
+const HISTORY_SERIES_LENGTH: usize = 30;
 
 /// Entry point of `cargo cov report` subcommand. Renders the coverage report using a template.
-pub fn generate(config: &ReportConfig) -> Result<Option<PathBuf>> {
+///
+/// Returns the path of the rendered summary if the template has a summary page, and the history entry of this report.
+/// The entry is not recorded here, the caller decides whether to [`append`] it to the history.
+///
+/// [`append`]: ../history/fn.append.html
+pub fn generate(config: &ReportConfig) -> Result<(Option<PathBuf>, history::Entry)> {
     let report_path = &config.output_path;
     clean_dir(report_path).chain_err(|| "Cannot clean report directory")?;
     create_dir_all(report_path)?;
 
     let mut interner = Interner::new();
-    let graph = create_graph(config, &mut interner).chain_err(|| "Cannot create graph")?;
-    let report = graph.report();
+    let analysis = analyze(config, &mut interner)?;
+    render(config, &analysis, &interner).chain_err(|| "Cannot render report")
+}
+
+/// Entry point of `cargo cov badge` subcommand. Summarizes every source file allowed by the configuration, keyed by
+/// the source path.
+pub fn summarize(config: &ReportConfig) -> Result<BTreeMap<String, FileSummary>> {
+    Ok(list_files(config)?.into_iter().map(|(path, file)| (path, file.summary())).collect())
+}
+
+/// Entry point of `cargo cov functions` subcommand. Lists every function in the source files allowed by the
+/// configuration, together with the (remapped) source path.
+pub fn list_functions(config: &ReportConfig) -> Result<Vec<(String, Function)>> {
+    let mut functions = Vec::new();
+    for (path, file) in list_files(config)? {
+        functions.extend(file.functions.into_iter().map(|f| (path.clone(), f)));
+    }
+    Ok(functions)
+}
+
+/// Entry point of `cargo cov hotspots` subcommand. Lists every source file allowed by the configuration, keyed by the
+/// (remapped) source path.
+pub fn list_files(config: &ReportConfig) -> Result<Vec<(String, ::cov::report::File)>> {
+    let mut interner = Interner::new();
+    let mut report = analyze(config, &mut interner)?.report;
+    let selected = selected_files(config, &report, &interner).map(|entry| (entry.symbol, entry.path.into_owned())).collect::<Vec<_>>();
+    Ok(selected.into_iter().map(|(symbol, path)| (path, report.files.remove(&symbol).expect("selected file"))).collect())
+}
+
+/// The path of a source file on disk, its coverage, and the explanation of its unexecuted lines keyed by the line
+/// number. Returned by [`list_files_with_hints()`].
+///
+/// [`list_files_with_hints()`]: ./fn.list_files_with_hints.html
+pub type FileWithHints = (PathBuf, ::cov::report::File, HashMap<u32, String>);
+
+/// Entry point of `cargo cov lsp` subcommand. Lists every source file allowed by the configuration like
+/// [`list_files()`], keyed by the path on disk, with the explanation of the unexecuted lines keyed by the line number.
+///
+/// [`list_files()`]: ./fn.list_files.html
+pub fn list_files_with_hints(config: &ReportConfig) -> Result<Vec<FileWithHints>> {
+    let mut interner = Interner::new();
+    let Analysis { mut report, uncovered_paths, .. } = analyze(config, &mut interner)?;
+    let selected = selected_files(config, &report, &interner)
+        .map(|entry| {
+            let hints = entry.file
+                .lines
+                .keys()
+                .filter_map(|&line| uncovered_paths.get(&(entry.symbol, line)).map(|p| (line, show::describe(p, entry.symbol, &interner))))
+                .collect();
+            (entry.symbol, config.workspace_path.join(&*entry.path), hints)
+        })
+        .collect::<Vec<_>>();
+    Ok(selected.into_iter().map(|(symbol, path, hints)| (path, report.files.remove(&symbol).expect("selected file"), hints)).collect())
+}
+
+/// Lists the source files of the `report` allowed by the configuration, i.e. selected by `--include` and `--category`,
+/// and owned by the `--package` if given. The `host_kind` of every entry is left empty.
+fn selected_files<'a>(config: &'a ReportConfig, report: &'a Report, interner: &'a Interner) -> impl Iterator<Item = ReportFileEntry<'a>> + 'a {
+    let workspace_str = config.workspace_path.to_string_lossy();
+    report.files.iter().filter_map(move |(&symbol, file)| {
+        let path = config.remap_path(&interner[symbol]);
+        let source_type = identify_source_path(&path, &workspace_str).0;
+        let package = config.package_of(&path).map(|pkg| &*pkg.name);
+        if config.is_included(&path, source_type) && config.package_filter.map_or(true, |name| package == Some(name)) {
+            Some(ReportFileEntry {
+                symbol,
+                source_type,
+                host_kind: None,
+                package,
+                path,
+                file,
+            })
+        } else {
+            None
+        }
+    })
+}
+
+/// Entry point of `cargo cov show` subcommand. Prints every source file whose (remapped) path ends with `path`, with
+/// the explanation of the unexecuted lines.
+pub fn show(config: &ReportConfig, path: &Path) -> Result<()> {
+    let mut interner = Interner::new();
+    let analysis = analyze(config, &mut interner)?;
+    let mut found = false;
+    for (&symbol, file) in &analysis.report.files {
+        let remapped_path = config.remap_path(&interner[symbol]);
+        if !Path::new(&*remapped_path).ends_with(path) {
+            continue;
+        }
+        found = true;
+        let hints = file.lines
+            .keys()
+            .filter_map(|&line| analysis.uncovered_paths.get(&(symbol, line)).map(|p| (line, show::describe(p, symbol, &interner))))
+            .collect();
+        show::print(&config.workspace_path.join(&*remapped_path), file, &hints)?;
+    }
+    ensure!(found, ErrorKind::SourceFileNotFound(path.display().to_string()));
+    Ok(())
+}
+
+/// Entry point of `cargo cov cfg` subcommand. Prints the control-flow graphs of the functions selected by the `filter`
+/// to stdout, as JSON or a Mermaid flowchart. If `paths` is not empty, only the source files whose (remapped) path ends
+/// with any of them are selected.
+pub fn export_cfg(config: &ReportConfig, paths: &[&Path], mut filter: CfgFilter, json: bool) -> Result<()> {
+    ensure!(find_coverage_objects(&config.gcno_path)?.is_empty(), ErrorKind::GcnoOnly("cargo cov cfg"));
+
+    let mut interner = Interner::new();
+    let batches = find_batches(config, &mut interner).chain_err(|| "Cannot find coverage data")?;
+    let graph = create_graph(&batches, &mut interner).chain_err(|| "Cannot create graph")?;
+    let filenames = graph.report().files.keys().cloned().collect::<Vec<_>>();
+    for &path in paths {
+        let matched = filenames.iter().filter(|&&symbol| Path::new(&*config.remap_path(&interner[symbol])).ends_with(path)).collect::<Vec<_>>();
+        ensure!(!matched.is_empty(), ErrorKind::SourceFileNotFound(path.display().to_string()));
+        filter.filenames.extend(matched);
+    }
+
+    let cfgs = graph.control_flow_graphs(&filter, &interner);
+    let stdout = io::stdout();
+    let mut lock = stdout.lock();
+    if json {
+        ::serde_json::to_writer_pretty(&mut lock, &cfgs.with_interner(&interner))?;
+        writeln!(lock)?;
+    } else {
+        write_mermaid(&cfgs, &interner, lock)?;
+    }
+    Ok(())
+}
+
+/// The coverage data read by [`analyze()`].
+///
+/// [`analyze()`]: ./fn.analyze.html
+struct Analysis {
+    report: Report,
+    /// The kinds of host crates (build scripts and procedural macros) of each source path.
+    host_kinds: HashMap<Symbol, &'static str>,
+    /// The explanation of every unexecuted line, keyed by the filename and line number (GCNO/GCDA only).
+    uncovered_paths: HashMap<(Symbol, u32), UncoveredPath>,
+    /// The analyzed graph, for exporting the control-flow graphs of the rendered files (GCNO/GCDA only).
+    graph: Option<Graph>,
+}
 
-    render(config, &report, &interner).chain_err(|| "Cannot render report")
+/// Reads all coverage data, and returns the report, the kinds of host crates and the explanation of unexecuted lines.
+fn analyze(config: &ReportConfig, interner: &mut Interner) -> Result<Analysis> {
+    let batches = find_batches(config, interner).chain_err(|| "Cannot find coverage data")?;
+    let objects = find_coverage_objects(&config.gcno_path).chain_err(|| "Cannot find source-based coverage data")?;
+    if objects.is_empty() {
+        let graph = create_graph(&batches, interner).chain_err(|| "Cannot create graph")?;
+        let mut report = graph.report_with_options(config.report_options);
+        attribute_tests(&batches, &graph, &mut report, interner).chain_err(|| "Cannot attribute coverage to tests")?;
+        report.demangle_functions(interner);
+        let host_kinds = find_host_kinds(&batches, interner).chain_err(|| "Cannot find build scripts and procedural macros")?;
+        let uncovered_paths = graph.uncovered_paths().into_iter().map(|path| ((path.filename, path.line), path)).collect();
+        Ok(Analysis {
+            report,
+            host_kinds,
+            uncovered_paths,
+            graph: Some(graph),
+        })
+    } else {
+        if config.report_options.exclude_panic_paths {
+            warning!("--exclude-panic-paths is ignored by the source-based coverage");
+        }
+        // `--configuration` is rejected when building with the source-based coverage, so there is a single batch.
+        ensure!(batches.len() == 1, ErrorKind::GcnoOnly("--configuration"));
+        let test_dirs = &batches[0].test_dirs;
+        let mut report = create_source_based_report(config, &objects, test_dirs, interner).chain_err(|| "Cannot read source-based coverage")?;
+        report.demangle_functions(interner);
+        Ok(Analysis {
+            report,
+            host_kinds: HashMap::new(),
+            uncovered_paths: HashMap::new(),
+            graph: None,
+        })
+    }
 }
 
-/// Creates an analyzed [`Graph`] from all GCNO and GCDA inside the `target/cov/build` folder.
+/// Creates an analyzed [`Graph`] from all GCNO and GCDA of every batch inside the `target/cov/build` folder.
 ///
 /// [`Graph`]: ../../cov/graph/struct.Graph.html
-fn create_graph(config: &ReportConfig, interner: &mut Interner) -> cov::Result<Graph> {
+fn create_graph(batches: &[Batch], interner: &mut Interner) -> cov::Result<Graph> {
     let mut graph = Graph::default();
 
-    for &(extension, dir_path) in &[("gcno", &config.gcno_path), ("gcda", &config.gcda_path)] {
-        progress!("Parsing", "{}/*.{}", dir_path.display(), extension);
-        for entry in read_dir(dir_path)? {
-            let path = entry?.path();
-            if path.extension() == Some(OsStr::new(extension)) {
-                trace!("merging {} {:?}", extension, path);
-                graph.merge(Gcov::open(path, interner)?)?;
+    for batch in batches {
+        progress!("Parsing", "{}/*.gcno", batch.gcno_path.display());
+        merge_gcno(&mut graph, batch, interner)?;
+        progress!("Parsing", "{}/*.gcda", batch.gcda_path.display());
+        merge_dir(&mut graph, &batch.gcda_path, "gcda", batch.configuration, interner)?;
+        if !batch.test_dirs.is_empty() {
+            progress!("Parsing", "{}/tests/*/*.gcda", batch.gcda_path.display());
+            for test_dir in &batch.test_dirs {
+                merge_dir(&mut graph, &test_dir.path, "gcda", batch.configuration, interner)?;
             }
         }
     }
@@ -187,10 +515,231 @@ fn create_graph(config: &ReportConfig, interner: &mut Interner) -> cov::Result<G
     Ok(graph)
 }
 
+/// The GCNO and GCDA files built under the same configuration.
+struct Batch {
+    /// The configuration tag given by `cargo cov --configuration`, or `UNKNOWN_SYMBOL` for untagged builds.
+    configuration: Symbol,
+    gcno_path: PathBuf,
+    gcda_path: PathBuf,
+    test_dirs: Vec<TestDir>,
+}
+
+/// Finds the batch of untagged files in `[gcno_path]` and `[gcda_path]`, followed by the batches of every tagged
+/// configuration in `[gcno_path]/configurations/«id»/` and `[gcda_path]/configurations/«id»/`, sorted by the tag. The
+/// tag is read from the `name` file in the GCNO folder.
+fn find_batches(config: &ReportConfig, interner: &mut Interner) -> io::Result<Vec<Batch>> {
+    let mut batches = vec![
+        Batch {
+            configuration: UNKNOWN_SYMBOL,
+            gcno_path: config.gcno_path.to_path_buf(),
+            gcda_path: config.gcda_path.to_path_buf(),
+            test_dirs: find_test_dirs(&config.gcda_path)?,
+        },
+    ];
+
+    let entries = match read_dir(config.gcno_path.join("configurations")) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(batches),
+        Err(e) => return Err(e),
+    };
+    let mut tagged_batches = Vec::new();
+    for entry in entries {
+        let gcno_path = entry?.path();
+        let mut name = String::new();
+        match File::open(gcno_path.join("name")) {
+            Ok(mut name_file) => name_file.read_to_string(&mut name)?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let gcda_path = config.gcda_path.join("configurations").join(gcno_path.file_name().expect("configuration folder"));
+        let test_dirs = find_test_dirs(&gcda_path)?;
+        tagged_batches.push((name, gcno_path, gcda_path, test_dirs));
+    }
+    tagged_batches.sort_by(|a, b| a.0.cmp(&b.0));
+    batches.extend(tagged_batches.into_iter().map(|(name, gcno_path, gcda_path, test_dirs)| Batch {
+        configuration: interner.intern(&*name),
+        gcno_path,
+        gcda_path,
+        test_dirs,
+    }));
+    Ok(batches)
+}
+
+/// Reads the `*.covobj` files inside `[gcno_path]`, and returns the paths of the executables built with the
+/// source-based coverage backend. Executables which no longer exist are skipped.
+fn find_coverage_objects(gcno_path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut objects = Vec::new();
+    for entry in read_dir(gcno_path)? {
+        let path = entry?.path();
+        if path.extension() != Some(OsStr::new("covobj")) {
+            continue;
+        }
+        let mut object = String::new();
+        File::open(&path)?.read_to_string(&mut object)?;
+        let object = PathBuf::from(object);
+        if object.is_file() {
+            objects.push(object);
+        } else {
+            warning!("skipping `{}`, which no longer exists", object.display());
+        }
+    }
+    objects.sort();
+    objects.dedup();
+    Ok(objects)
+}
+
+/// Creates a [`Report`] from the executables built with the source-based coverage backend, and all raw profiles
+/// (`*.profraw`) inside the `target/cov/build/gcda` folder.
+///
+/// Tests are attributed similar to [`attribute_tests()`], using the raw profiles of each test only.
+///
+/// [`Report`]: ../../cov/report/struct.Report.html
+/// [`attribute_tests()`]: ./fn.attribute_tests.html
+fn create_source_based_report(config: &ReportConfig, objects: &[PathBuf], test_dirs: &[TestDir], interner: &mut Interner) -> cov::Result<Report> {
+    fn merge_profraw_dir(coverage: &mut llvm::Coverage, dir_path: &Path) -> cov::Result<()> {
+        for entry in read_dir(dir_path)? {
+            let path = entry?.path();
+            if path.extension() == Some(OsStr::new("profraw")) {
+                trace!("merging profraw {:?}", path);
+                coverage.merge_profraw_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut coverage = llvm::Coverage::default();
+    progress!("Parsing", "{} executables", objects.len());
+    for object in objects {
+        coverage.merge_object_file(object, interner)?;
+    }
+    let objects_coverage = coverage.clone();
+
+    progress!("Parsing", "{}/*.profraw", config.gcda_path.display());
+    merge_profraw_dir(&mut coverage, &config.gcda_path)?;
+    for test_dir in test_dirs {
+        merge_profraw_dir(&mut coverage, &test_dir.path)?;
+    }
+    let mut report = coverage.report();
+
+    if !test_dirs.is_empty() {
+        progress!("Attributing", "{} tests", test_dirs.len());
+    }
+    for test_dir in test_dirs {
+        let mut coverage = objects_coverage.clone();
+        merge_profraw_dir(&mut coverage, &test_dir.path)?;
+        let tag = interner.intern(&*test_dir.name);
+        report.add_covered_by(&coverage.report().executed(), tag);
+    }
+    Ok(report)
+}
+
+/// Merges all files with the given `extension` inside `dir_path` into the graph, tagged with the `configuration`.
+fn merge_dir(graph: &mut Graph, dir_path: &Path, extension: &str, configuration: Symbol, interner: &mut Interner) -> cov::Result<()> {
+    for entry in read_dir(dir_path)? {
+        let path = entry?.path();
+        if path.extension() == Some(OsStr::new(extension)) {
+            trace!("merging {} {:?}", extension, path);
+            graph.merge_with_configuration(Gcov::open(path, interner)?, configuration)?;
+        }
+    }
+    Ok(())
+}
+
+/// Merges all GCNO files of a batch into the graph, including those of build scripts and procedural macros.
+fn merge_gcno(graph: &mut Graph, batch: &Batch, interner: &mut Interner) -> cov::Result<()> {
+    merge_dir(graph, &batch.gcno_path, "gcno", batch.configuration, interner)?;
+    for &(_, ref path) in &host_gcno_dirs(&batch.gcno_path) {
+        merge_dir(graph, path, "gcno", batch.configuration, interner)?;
+    }
+    Ok(())
+}
+
+/// Lists the existing folders containing GCNO files of build scripts and procedural macros, together with their kind.
+fn host_gcno_dirs(gcno_path: &Path) -> Vec<(&'static str, PathBuf)> {
+    HOST_CRATE_KINDS
+        .iter()
+        .map(|&kind| (kind, gcno_path.join(kind)))
+        .filter(|&(_, ref path)| path.is_dir())
+        .collect()
+}
+
+/// Finds the source files compiled into build scripts and procedural macros, which are executed on the host during the
+/// build. Returns a map from the source path to the kind of the crate (`"build-script"` or `"proc-macro"`).
+fn find_host_kinds(batches: &[Batch], interner: &mut Interner) -> cov::Result<HashMap<Symbol, &'static str>> {
+    let mut host_kinds = HashMap::new();
+    for batch in batches {
+        for (kind, path) in host_gcno_dirs(&batch.gcno_path) {
+            let mut graph = Graph::default();
+            merge_dir(&mut graph, &path, "gcno", batch.configuration, interner)?;
+            graph.analyze();
+            host_kinds.extend(graph.report().files.keys().map(|&symbol| (symbol, kind)));
+        }
+    }
+    Ok(host_kinds)
+}
+
+/// A folder containing the GCDA files of a single test, created by `cargo cov test --per-test`.
+struct TestDir {
+    name: String,
+    path: PathBuf,
+}
+
+/// Finds all per-test GCDA folders inside `[gcda_path]/tests/`, sorted by the test name.
+fn find_test_dirs(gcda_path: &Path) -> io::Result<Vec<TestDir>> {
+    let tests_path = gcda_path.join("tests");
+    let entries = match read_dir(&tests_path) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut test_dirs = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let mut name = String::new();
+        match File::open(path.join("name")) {
+            Ok(mut name_file) => name_file.read_to_string(&mut name)?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        test_dirs.push(TestDir {
+            name,
+            path,
+        });
+    }
+    test_dirs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(test_dirs)
+}
+
+/// Records which tests have covered every line and function in the report.
+///
+/// The counts of a copy of the `graph` are cleared, and then replaced by the GCDA files of each test in turn. The lines
+/// and functions executed with these counts are marked as covered by that test.
+fn attribute_tests(batches: &[Batch], graph: &Graph, report: &mut Report, interner: &mut Interner) -> cov::Result<()> {
+    let tests_count = batches.iter().map(|batch| batch.test_dirs.len()).sum::<usize>();
+    if tests_count == 0 {
+        return Ok(());
+    }
+
+    progress!("Attributing", "{} tests", tests_count);
+    let mut graph = graph.clone();
+    for batch in batches {
+        for test_dir in &batch.test_dirs {
+            graph.reset_counts();
+            merge_dir(&mut graph, &test_dir.path, "gcda", batch.configuration, interner)?;
+            graph.analyze();
+            let tag = interner.intern(&*test_dir.name);
+            report.add_covered_by(&graph.executed(), tag);
+        }
+    }
+    Ok(())
+}
+
 /// Renders the `report` into `report_path` using a template.
 ///
-/// If the template has a summary page, returns the path of the rendered summary.
-fn render(config: &ReportConfig, report: &Report, interner: &Interner) -> Result<Option<PathBuf>> {
+/// If the template has a summary page, returns the path of the rendered summary. Also returns the history entry of the
+/// rendered files, which the summary page already includes in the trend.
+fn render(config: &ReportConfig, analysis: &Analysis, interner: &Interner) -> Result<(Option<PathBuf>, history::Entry)> {
     use toml::de::from_slice;
 
     let mut template_path = [env!("CARGO_MANIFEST_DIR"), "res", "templates"].iter().collect::<PathBuf>();
@@ -215,50 +764,72 @@ fn render(config: &ReportConfig, report: &Report, interner: &Interner) -> Result
     template_path.set_file_name("tera");
     template_path.push("*");
 
-    let workspace_str = config.workspace_path.to_string_lossy();
-
     let mut tera = new_template(template_path.to_str().expect("UTF-8 template path"))?;
 
-    let mut report_files = report
-        .files
-        .iter()
-        .filter_map(|(&symbol, file)| {
-            let path = &interner[symbol];
-            let source_type = identify_source_path(path, &workspace_str).0;
-            if config.allowed_source_types.contains(source_type) {
-                Some(ReportFileEntry {
-                    symbol,
-                    source_type,
-                    path,
-                    file,
-                })
-            } else {
-                None
-            }
+    let mut report_files = selected_files(config, &analysis.report, interner)
+        .map(|entry| ReportFileEntry {
+            host_kind: analysis.host_kinds.get(&entry.symbol).cloned(),
+            ..entry
         })
         .collect::<Vec<_>>();
-    report_files.sort_by_key(|entry| (entry.source_type, entry.path));
+    // Files of workspace packages come first, followed by the other sources (external crates, etc).
+    report_files.sort_by(|a, b| (a.package.is_none(), a.package, a.source_type, &a.path).cmp(&(b.package.is_none(), b.package, b.source_type, &b.path)));
+
+    let selection = config.selection();
+    let history_entry = history::Entry::new(&config.workspace_path, selection.clone(), report_files.iter().map(|entry| (&*entry.path, entry.file.summary())));
+    let mut history = history::load(&config.history_path, &selection).chain_err(|| "Cannot read coverage history")?;
+    history.push(history_entry.clone());
 
     let summary_path = if let Some(summary) = template_config.summary {
-        Some(write_summary(config, &report_files, &tera, &summary).chain_err(|| "Cannot write summary")?)
+        Some(write_summary(config, &report_files, &history, &tera, &summary).chain_err(|| "Cannot write summary")?)
     } else {
         None
     };
 
     if let Some(files_config) = template_config.files {
+        let cfgs = match analysis.graph {
+            Some(ref graph) => {
+                let filter = CfgFilter {
+                    filenames: report_files.iter().map(|entry| entry.symbol).collect(),
+                    ..CfgFilter::default()
+                };
+                mermaid_flowcharts(graph, &filter, interner)?
+            },
+            None => HashMap::new(),
+        };
         tera.add_raw_template("<filename>", files_config.output)?;
         for entry in &report_files {
-            write_file(config, interner, entry, &tera, files_config.template).chain_err(|| format!("Cannot write file at `{}`", entry.path))?;
+            write_file(config, interner, analysis, &cfgs, entry, &tera, files_config.template).chain_err(|| format!("Cannot write file at `{}`", entry.path))?;
         }
     }
 
-    Ok(summary_path)
+    Ok((summary_path, history_entry))
+}
+
+/// The control-flow graphs of functions as Mermaid flowcharts, keyed by the filename and function name.
+type Flowcharts = HashMap<(Symbol, Symbol), String>;
+
+/// Writes the control-flow graphs of the functions selected by the `filter` as Mermaid flowcharts.
+fn mermaid_flowcharts(graph: &Graph, filter: &CfgFilter, interner: &Interner) -> Result<Flowcharts> {
+    let mut cfgs = HashMap::new();
+    for cfg in graph.control_flow_graphs(filter, interner) {
+        let key = (cfg.filename, cfg.name);
+        let mut mermaid = Vec::new();
+        write_mermaid(&[cfg], interner, &mut mermaid)?;
+        cfgs.insert(key, String::from_utf8(mermaid).expect("UTF-8 Mermaid flowchart"));
+    }
+    Ok(cfgs)
 }
 
 struct ReportFileEntry<'a> {
     symbol: Symbol,
     source_type: SourceType,
-    path: &'a str,
+    /// Whether the file belongs to a build script (`"build-script"`) or procedural macro (`"proc-macro"`).
+    host_kind: Option<&'static str>,
+    /// Name of the workspace package owning the file.
+    package: Option<&'a str>,
+    /// The source path, after applying `--remap-path-prefix`.
+    path: Cow<'a, str>,
     file: &'a ::cov::report::File,
 }
 
@@ -278,23 +849,61 @@ struct FileConfig<'a> {
 }
 
 /// Renders the summary page.
-fn write_summary(config: &ReportConfig, report_files: &[ReportFileEntry], tera: &Tera, file_config: &FileConfig) -> Result<PathBuf> {
+fn write_summary(config: &ReportConfig, report_files: &[ReportFileEntry], history: &[history::Entry], tera: &Tera, file_config: &FileConfig) -> Result<PathBuf> {
     let path = config.output_path.join(file_config.output);
     let mut context = Context::new();
 
+    let recent_history = &history[history.len().saturating_sub(HISTORY_SERIES_LENGTH)..];
     let files = report_files
         .iter()
         .map(|entry| {
+            let series = recent_history
+                .iter()
+                .filter_map(|run| {
+                    run.files.get(&*entry.path).map(|summary| {
+                        json!({
+                            "timestamp": run.timestamp,
+                            "commit": run.commit,
+                            "summary": summary,
+                        })
+                    })
+                })
+                .collect::<Vec<_>>();
             json!({
                 "symbol": entry.symbol,
                 "path": entry.path,
+                "host_kind": entry.host_kind,
+                "package": entry.package,
                 "summary": entry.file.summary(),
+                "history": series,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // The files are sorted by package, so every package occupies a consecutive range.
+    let mut package_ranges = Vec::<(Option<&str>, Range<usize>)>::new();
+    for (i, entry) in report_files.iter().enumerate() {
+        match package_ranges.last_mut() {
+            Some(&mut (package, ref mut range)) if package == entry.package => range.end = i + 1,
+            _ => package_ranges.push((entry.package, i..(i + 1))),
+        }
+    }
+    let packages = package_ranges
+        .into_iter()
+        .map(|(package, range)| {
+            let summaries = report_files[range.clone()].iter().map(|entry| entry.file.summary()).collect::<Vec<_>>();
+            json!({
+                "name": package,
+                "summary": sum_file_summaries(&summaries),
+                "files": &files[range],
             })
         })
         .collect::<Vec<_>>();
 
     context.add("crate_path", &config.workspace_path);
     context.add("files", &files);
+    context.add("packages", &packages);
+    context.add("exclude_panic_paths", &config.report_options.exclude_panic_paths);
     let rendered = tera.render(file_config.template, &context)?;
     let mut summary_file = File::create(&path)?;
     summary_file.write_all(rendered.as_bytes())?;
@@ -303,28 +912,47 @@ fn write_summary(config: &ReportConfig, report_files: &[ReportFileEntry], tera:
 }
 
 /// Renders report for a source path.
-fn write_file(config: &ReportConfig, interner: &Interner, entry: &ReportFileEntry, tera: &Tera, template_name: &str) -> Result<()> {
+fn write_file(
+    config: &ReportConfig,
+    interner: &Interner,
+    analysis: &Analysis,
+    cfgs: &Flowcharts,
+    entry: &ReportFileEntry,
+    tera: &Tera,
+    template_name: &str,
+) -> Result<()> {
     let mut context = Context::new();
 
     let mut lines = Vec::new();
     let mut source_line_number = 1;
 
     // Read the source file.
-    let path = config.workspace_path.join(entry.path);
+    let path = config.workspace_path.join(&*entry.path);
     if let Ok(source_file) = File::open(path) {
         let source_file = BufReader::new(source_file);
         for source_line in source_file.lines() {
-            let (count, branches) = if let Some(line) = entry.file.lines.get(&source_line_number) {
-                let (count, branches) = serialize_line(line, interner);
-                (Some(count), branches)
+            let source_line = source_line?;
+            let (count, branches, covered_by, segments) = if let Some(line) = entry.file.lines.get(&source_line_number) {
+                let (count, branches, covered_by) = serialize_line(line, interner);
+                let segments = if count > 0 {
+                    split_uncovered_segments(&source_line, source_line_number, &entry.file.regions)
+                } else {
+                    Vec::new()
+                };
+                (Some(count), branches, covered_by, segments)
             } else {
-                (None, Vec::new())
+                (None, Vec::new(), Vec::new(), Vec::new())
             };
             lines.push(json!({
                 "line": source_line_number,
-                "source": source_line?,
+                "source": source_line,
                 "count": count,
                 "branches": branches,
+                "covered_by": covered_by,
+                "configurations": entry.file.lines.get(&source_line_number).map_or_else(Vec::new, |l| serialize_symbols(&l.configurations, interner)),
+                "segments": segments,
+                "loop_stats": entry.file.lines.get(&source_line_number).and_then(serialize_loop_stats),
+                "uncovered_path": serialize_uncovered_path(count, analysis.uncovered_paths.get(&(entry.symbol, source_line_number)), interner),
             }));
             source_line_number += 1;
         }
@@ -332,12 +960,17 @@ fn write_file(config: &ReportConfig, interner: &Interner, entry: &ReportFileEntr
 
     // Add the remaining lines absent from the source file.
     lines.extend(entry.file.lines.range(source_line_number..).map(|(line_number, line)| {
-        let (count, branches) = serialize_line(line, interner);
+        let (count, branches, covered_by) = serialize_line(line, interner);
         json!({
             "line": *line_number,
             "count": Some(count),
             "source": Value::Null,
             "branches": branches,
+            "covered_by": covered_by,
+            "configurations": serialize_symbols(&line.configurations, interner),
+            "segments": Vec::<Value>::new(),
+            "loop_stats": serialize_loop_stats(line),
+            "uncovered_path": serialize_uncovered_path(Some(line.count), analysis.uncovered_paths.get(&(entry.symbol, *line_number)), interner),
         })
     }));
 
@@ -351,9 +984,40 @@ fn write_file(config: &ReportConfig, interner: &Interner, entry: &ReportFileEntr
             json!({
                 "symbol": f.name,
                 "name": name,
+                "demangled_name": f.demangled_name,
+                "generic_name": f.generic_name,
                 "line": f.line,
                 "column": f.column,
                 "summary": &f.summary,
+                "crap": f.summary.crap(),
+                "cfg": cfgs.get(&(entry.symbol, f.name)),
+                "covered_by": serialize_symbols(&f.covered_by, interner),
+                "configuration": f.configuration.map(|c| &interner[c]),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Group the instances of generic functions
+    let function_groups = entry
+        .file
+        .function_groups()
+        .into_iter()
+        .map(|group| {
+            let mut covered_by = group.instances.iter().flat_map(|f| serialize_symbols(&f.covered_by, interner)).collect::<Vec<_>>();
+            covered_by.sort();
+            covered_by.dedup();
+            let mut configurations = group.instances.iter().filter_map(|f| f.configuration).map(|c| &interner[c]).collect::<Vec<_>>();
+            configurations.sort();
+            configurations.dedup();
+            json!({
+                "generic_name": group.generic_name,
+                "line": group.line,
+                "column": group.column,
+                "instances": group.instances.iter().map(|f| &*f.demangled_name).collect::<Vec<_>>(),
+                "summary": &group.summary,
+                "crap": group.summary.crap(),
+                "covered_by": covered_by,
+                "configurations": configurations,
             })
         })
         .collect::<Vec<_>>();
@@ -361,9 +1025,13 @@ fn write_file(config: &ReportConfig, interner: &Interner, entry: &ReportFileEntr
     context.add("crate_path", &config.workspace_path);
     context.add("symbol", &entry.symbol);
     context.add("path", &entry.path);
+    context.add("host_kind", &entry.host_kind);
+    context.add("package", &entry.package);
     context.add("summary", &entry.file.summary());
     context.add("lines", &lines);
     context.add("functions", &functions);
+    context.add("function_groups", &function_groups);
+    context.add("exclude_panic_paths", &config.report_options.exclude_panic_paths);
 
     let filename = tera.render("<filename>", &context)?;
     let path = config.output_path.join(filename);
@@ -374,8 +1042,32 @@ fn write_file(config: &ReportConfig, interner: &Interner, entry: &ReportFileEntr
     Ok(())
 }
 
+/// Serializes the statistics of the loops starting at a source line into JSON value.
+fn serialize_loop_stats(line: &::cov::report::Line) -> Option<Value> {
+    line.loop_stats.map(|stats| {
+        json!({
+            "entry_count": stats.entry_count,
+            "iterations": stats.iterations,
+            "mean_iterations": stats.mean_iterations(),
+        })
+    })
+}
+
+/// Serializes the explanation of an unexecuted line into JSON value. Lines which are executed are not explained.
+fn serialize_uncovered_path(count: Option<u64>, path: Option<&UncoveredPath>, interner: &Interner) -> Option<Value> {
+    match (count, path) {
+        (Some(0), Some(path)) => Some(json!({
+            "symbol": path.branch_filename,
+            "path": &interner[path.branch_filename],
+            "line": path.branch_line,
+            "taken": path.direction == BranchDirection::Taken,
+        })),
+        _ => None,
+    }
+}
+
 /// Serializes a source line as a branch target into JSON value.
-fn serialize_line(line: &::cov::report::Line, interner: &Interner) -> (u64, Vec<Value>) {
+fn serialize_line<'a>(line: &::cov::report::Line, interner: &'a Interner) -> (u64, Vec<Value>, Vec<&'a str>) {
     (
         line.count,
         line.branches
@@ -390,5 +1082,69 @@ fn serialize_line(line: &::cov::report::Line, interner: &Interner) -> (u64, Vec<
                 })
             })
             .collect(),
+        serialize_symbols(&line.covered_by, interner),
     )
 }
+
+/// Splits a source line into segments, marking those where the innermost region covering it has never been executed.
+///
+/// The region columns are 1-based byte offsets, like those of LLVM, so a multibyte character is placed by its first
+/// byte. Returns an empty vector if no part of the line is uncovered, or the file has no region information.
+fn split_uncovered_segments(source: &str, line_number: u32, regions: &[Region]) -> Vec<Value> {
+    let regions = regions.iter().filter(|r| r.line_start <= line_number && line_number <= r.line_end).collect::<Vec<_>>();
+
+    let mut segments: Vec<(String, bool)> = Vec::new();
+    for (offset, c) in source.char_indices() {
+        let column = offset as u32 + 1;
+        let innermost = regions.iter().filter(|r| r.contains(line_number, column)).max_by_key(|r| (r.line_start, r.column_start));
+        let is_uncovered = innermost.map_or(false, |r| r.count == 0);
+        match segments.last_mut() {
+            Some(&mut (ref mut text, last_is_uncovered)) if last_is_uncovered == is_uncovered => text.push(c),
+            _ => segments.push((c.to_string(), is_uncovered)),
+        }
+    }
+
+    if segments.iter().all(|&(_, is_uncovered)| !is_uncovered) {
+        return Vec::new();
+    }
+    segments.into_iter().map(|(text, is_uncovered)| json!({"source": text, "uncovered": is_uncovered})).collect()
+}
+
+#[test]
+fn test_split_uncovered_segments() {
+    let region = |column_start, column_end, count| Region {
+        line_start: 1,
+        column_start,
+        line_end: 1,
+        column_end,
+        count,
+    };
+    let regions = [region(1, 31, 1), region(14, 19, 0), region(25, 30, 1)];
+    let source = "let y = if a { x } else { z };";
+    assert_eq!(
+        split_uncovered_segments(source, 1, &regions),
+        vec![
+            json!({"source": "let y = if a ", "uncovered": false}),
+            json!({"source": "{ x }", "uncovered": true}),
+            json!({"source": " else { z };", "uncovered": false}),
+        ]
+    );
+    assert!(split_uncovered_segments(source, 1, &regions[..1]).is_empty());
+    assert!(split_uncovered_segments(source, 2, &regions).is_empty());
+
+    // `é` takes 2 bytes, so the columns after it are one more than the character indices.
+    let regions = [region(1, 20, 1), region(15, 20, 0)];
+    let source = "/* é */ if a { x }";
+    assert_eq!(
+        split_uncovered_segments(source, 1, &regions),
+        vec![
+            json!({"source": "/* é */ if a ", "uncovered": false}),
+            json!({"source": "{ x }", "uncovered": true}),
+        ]
+    );
+}
+
+/// Resolves a list of symbols into strings.
+fn serialize_symbols<'a>(symbols: &[Symbol], interner: &'a Interner) -> Vec<&'a str> {
+    symbols.iter().map(|s| &interner[*s]).collect()
+}
@@ -0,0 +1,399 @@
+//! Code review annotations.
+//!
+//! `cargo cov review` reads a unified diff (e.g. from `git diff`), and reports the lines added by the diff which are
+//! not covered, and the added lines having branches never taken. The annotations can be written in these formats:
+//!
+//! * `github`: [Workflow commands] of GitHub Actions, which are shown inline in the pull request.
+//!
+//!     ```text
+//!     ::warning file=src/lib.rs,line=12,endLine=14,title=Not covered::Not covered by tests
+//!     ```
+//!
+//! * `rdjson`: The [Reviewdog Diagnostic Format], for `reviewdog -f=rdjson`.
+//!
+//! * `sarif`: [SARIF] 2.1.0, read by GitHub code scanning and many other tools.
+//!
+//! Only the source files of the workspace are considered. A source file is matched with a file in the diff if the path
+//! relative to the workspace (the path shown as `./…` by the `simplify_source_path` filter) is a suffix of the path in
+//! the diff, so the diff may be taken from a repository containing the workspace in a subfolder. The annotations always
+//! use the paths from the diff.
+//!
+//! Consecutive uncovered lines with the same explanation are merged into one annotation.
+//!
+//! [Workflow commands]: https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions
+//! [Reviewdog Diagnostic Format]: https://github.com/reviewdog/reviewdog/tree/master/proto/rdf
+//! [SARIF]: https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+
+use error::Result;
+use report::FileWithHints;
+use sourcepath::{SourceType, identify_source_path};
+
+use serde_json::{Value, to_writer_pretty};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, Write};
+use std::path::{MAIN_SEPARATOR, Path};
+use std::str::FromStr;
+
+/// The lines added by a diff, keyed by the path of the new file.
+pub type Diff = BTreeMap<String, BTreeSet<u32>>;
+
+/// Output format of the annotations.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// GitHub Actions workflow commands.
+    GitHub,
+    /// Reviewdog Diagnostic Format (JSON).
+    RdJson,
+    /// SARIF 2.1.0.
+    Sarif,
+}
+
+impl FromStr for Format {
+    type Err = ();
+    fn from_str(s: &str) -> ::std::result::Result<Format, ()> {
+        Ok(match s {
+            "github" => Format::GitHub,
+            "rdjson" => Format::RdJson,
+            "sarif" => Format::Sarif,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// The kind of an annotation.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Kind {
+    /// The lines are never executed.
+    UncoveredLines,
+    /// The line is executed, but some of its branches are never taken.
+    UntakenBranches,
+}
+
+impl Kind {
+    /// The rule ID of this kind, used in rdjson and SARIF.
+    fn rule_id(self) -> &'static str {
+        match self {
+            Kind::UncoveredLines => "uncovered-lines",
+            Kind::UntakenBranches => "untaken-branches",
+        }
+    }
+
+    /// Short description of this kind, used as the title of the annotation.
+    fn title(self) -> &'static str {
+        match self {
+            Kind::UncoveredLines => "Not covered",
+            Kind::UntakenBranches => "Branches not taken",
+        }
+    }
+}
+
+/// An annotation on a range of lines added by the diff.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Annotation {
+    /// The path of the file in the diff.
+    pub path: String,
+    /// The first line of the range.
+    pub line_start: u32,
+    /// The last line of the range (inclusive).
+    pub line_end: u32,
+    /// What is not covered.
+    pub kind: Kind,
+    /// Description of the annotation, with the explanation of the uncovered lines if any.
+    pub message: String,
+}
+
+/// Parses a unified diff, and collects the lines added to every new file. Deleted files are skipped.
+///
+/// The `a/` and `b/` prefixes written by `git diff` are removed from the paths.
+pub fn parse_diff<R: BufRead>(reader: R) -> io::Result<Diff> {
+    let mut diff = Diff::new();
+    let mut current_path = None;
+    let mut line_number = 0;
+    let mut remaining_new_lines = 0;
+    for line in reader.lines() {
+        let line = line?;
+        if remaining_new_lines == 0 && line.starts_with("+++ ") {
+            let path = line[4..].split('\t').next().unwrap_or("").trim();
+            current_path = if path == "/dev/null" {
+                None
+            } else if path.starts_with("b/") {
+                Some(path[2..].to_owned())
+            } else {
+                Some(path.to_owned())
+            };
+        } else if line.starts_with("@@ ") {
+            // The hunk header is `@@ -old_start,old_count +new_start,new_count @@`, where the counts default to 1.
+            let new_range = line.split_whitespace().nth(2).unwrap_or("");
+            let mut parts = new_range.trim_matches('+').splitn(2, ',');
+            line_number = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+            remaining_new_lines = parts.next().map_or(Some(1), |s| s.parse().ok()).unwrap_or(0);
+        } else if remaining_new_lines > 0 {
+            if line.starts_with('+') {
+                if let Some(ref path) = current_path {
+                    diff.entry(path.clone()).or_insert_with(BTreeSet::new).insert(line_number);
+                }
+            }
+            if !line.starts_with('-') && !line.starts_with('\\') {
+                line_number += 1;
+                remaining_new_lines -= 1;
+            }
+        }
+    }
+    Ok(diff)
+}
+
+/// Creates the annotations of the uncovered lines and untaken branches added by the diff. `files` should be obtained
+/// from [`report::list_files_with_hints()`].
+///
+/// [`report::list_files_with_hints()`]: ../report/fn.list_files_with_hints.html
+pub fn collect(files: &[FileWithHints], diff: &Diff, workspace_path: &Path) -> Vec<Annotation> {
+    let mut crate_path = workspace_path.to_string_lossy().into_owned();
+    crate_path.push(MAIN_SEPARATOR);
+
+    let mut annotations = Vec::new();
+    for &(ref path, ref file, ref hints) in files {
+        let path = path.to_string_lossy();
+        let (source_type, stripped_len) = identify_source_path(&path, &crate_path);
+        if source_type != SourceType::LOCAL {
+            continue;
+        }
+        let relative_path = Path::new(&path[stripped_len..]);
+        let (diff_path, added_lines) = match diff.iter().find(|&(diff_path, _)| Path::new(diff_path).ends_with(relative_path)) {
+            Some(entry) => entry,
+            None => continue,
+        };
+
+        let mut last_uncovered: Option<Annotation> = None;
+        for (&line_number, line) in &file.lines {
+            if !added_lines.contains(&line_number) {
+                continue;
+            }
+            if line.count == 0 {
+                let message = match hints.get(&line_number) {
+                    Some(hint) => format!("Not covered by tests, {}", hint),
+                    None => "Not covered by tests".to_owned(),
+                };
+                if let Some(ref mut last) = last_uncovered {
+                    if last.line_end + 1 == line_number && last.message == message {
+                        last.line_end = line_number;
+                        continue;
+                    }
+                }
+                annotations.extend(last_uncovered.take());
+                last_uncovered = Some(Annotation {
+                    path: diff_path.clone(),
+                    line_start: line_number,
+                    line_end: line_number,
+                    kind: Kind::UncoveredLines,
+                    message,
+                });
+            } else {
+                let untaken = line.branches.iter().filter(|b| b.count == 0).count();
+                if untaken > 0 {
+                    annotations.push(Annotation {
+                        path: diff_path.clone(),
+                        line_start: line_number,
+                        line_end: line_number,
+                        kind: Kind::UntakenBranches,
+                        message: format!("{} of {} branches not taken by tests", untaken, line.branches.len()),
+                    });
+                }
+            }
+        }
+        annotations.extend(last_uncovered);
+    }
+    annotations.sort_by(|a, b| (&a.path, a.line_start).cmp(&(&b.path, b.line_start)));
+    annotations
+}
+
+/// Writes the annotations in the given format.
+pub fn write<W: Write>(writer: &mut W, annotations: &[Annotation], format: Format) -> Result<()> {
+    match format {
+        Format::GitHub => for a in annotations {
+            writeln!(
+                writer,
+                "::warning file={},line={},endLine={},title={}::{}",
+                escape_property(&a.path),
+                a.line_start,
+                a.line_end,
+                escape_property(a.kind.title()),
+                escape_data(&a.message),
+            )?;
+        },
+        Format::RdJson => {
+            to_writer_pretty(&mut *writer, &rdjson(annotations))?;
+            writeln!(writer)?;
+        },
+        Format::Sarif => {
+            to_writer_pretty(&mut *writer, &sarif(annotations))?;
+            writeln!(writer)?;
+        },
+    }
+    Ok(())
+}
+
+/// Creates the `DiagnosticResult` of the Reviewdog Diagnostic Format.
+fn rdjson(annotations: &[Annotation]) -> Value {
+    let diagnostics = annotations
+        .iter()
+        .map(|a| {
+            json!({
+                "message": a.message,
+                "location": {
+                    "path": a.path,
+                    "range": {"start": {"line": a.line_start}, "end": {"line": a.line_end}},
+                },
+                "severity": "WARNING",
+                "code": {"value": a.kind.rule_id()},
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "source": {"name": "cargo-cov", "url": env!("CARGO_PKG_REPOSITORY")},
+        "severity": "WARNING",
+        "diagnostics": diagnostics,
+    })
+}
+
+/// Creates a SARIF 2.1.0 log with a single run.
+fn sarif(annotations: &[Annotation]) -> Value {
+    let rules = [Kind::UncoveredLines, Kind::UntakenBranches]
+        .iter()
+        .map(|kind| json!({"id": kind.rule_id(), "shortDescription": {"text": kind.title()}}))
+        .collect::<Vec<_>>();
+    let results = annotations
+        .iter()
+        .map(|a| {
+            json!({
+                "ruleId": a.kind.rule_id(),
+                "level": "warning",
+                "message": {"text": a.message},
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": {"uri": a.path},
+                        "region": {"startLine": a.line_start, "endLine": a.line_end},
+                    },
+                }],
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "cargo-cov",
+                    "version": crate_version!(),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+/// Escapes the message of a workflow command.
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escapes a property value of a workflow command.
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[test]
+fn test_parse_diff() {
+    let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,4 +1,5 @@
+ fn a() {
+-    old();
++    new();
++    newer();
+ }
+@@ -10 +11,2 @@ fn b() {
++++ counter;
++    x
+diff --git a/README.md b/README.md
+deleted file mode 100644
+--- a/README.md
++++ /dev/null
+@@ -1 +0,0 @@
+-readme
+--- /dev/null
++++ new.rs\t2018-01-01 00:00:00
+@@ -0,0 +1 @@
++fn main() {}
+";
+    let diff = parse_diff(diff.as_bytes()).unwrap();
+    assert_eq!(diff.len(), 2);
+    assert_eq!(diff["src/lib.rs"].iter().cloned().collect::<Vec<_>>(), vec![2, 3, 11, 12]);
+    assert_eq!(diff["new.rs"].iter().cloned().collect::<Vec<_>>(), vec![1]);
+}
+
+#[test]
+fn test_collect() {
+    use cov::report::{Branch, File, Line};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    let mut file = File::default();
+    let branch = |count| Branch { count, ..Branch::default() };
+    for &(line_number, count) in &[(1, 5), (2, 0), (3, 0), (4, 0), (5, 0), (6, 5)] {
+        file.lines.insert(line_number, Line { count, ..Line::default() });
+    }
+    file.lines.get_mut(&1).unwrap().branches = vec![branch(5), branch(0)];
+    file.lines.get_mut(&6).unwrap().branches = vec![branch(0), branch(5)];
+    let mut hints = HashMap::new();
+    hints.insert(4, "reachable from line 1 when the branch is not taken".to_owned());
+    hints.insert(5, "reachable from line 1 when the branch is not taken".to_owned());
+
+    let workspace_path = Path::new("/workspace");
+    let files = vec![
+        (workspace_path.join("src").join("lib.rs"), file.clone(), hints),
+        (PathBuf::from("/elsewhere/src/lib.rs"), file, HashMap::new()),
+    ];
+    let mut diff = Diff::new();
+    diff.insert("crate/src/lib.rs".to_owned(), [1, 2, 3, 4, 5].iter().cloned().collect());
+
+    let annotation = |line_start, line_end, kind, message: &str| Annotation {
+        path: "crate/src/lib.rs".to_owned(),
+        line_start,
+        line_end,
+        kind,
+        message: message.to_owned(),
+    };
+    assert_eq!(
+        collect(&files, &diff, workspace_path),
+        vec![
+            annotation(1, 1, Kind::UntakenBranches, "1 of 2 branches not taken by tests"),
+            annotation(2, 3, Kind::UncoveredLines, "Not covered by tests"),
+            annotation(4, 5, Kind::UncoveredLines, "Not covered by tests, reachable from line 1 when the branch is not taken"),
+        ]
+    );
+}
+
+#[test]
+fn test_write_github() {
+    let annotations = [
+        Annotation {
+            path: "src/a,b.rs".to_owned(),
+            line_start: 2,
+            line_end: 3,
+            kind: Kind::UncoveredLines,
+            message: "Not covered by tests, 100%".to_owned(),
+        },
+    ];
+    let mut output = Vec::new();
+    write(&mut output, &annotations, Format::GitHub).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "::warning file=src/a%2Cb.rs,line=2,endLine=3,title=Not covered::Not covered by tests, 100%25\n"
+    );
+}
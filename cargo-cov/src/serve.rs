@@ -0,0 +1,235 @@
+//! Local HTTP server of the report.
+//!
+//! `cargo cov serve` generates the report, and serves the report folder (`target/cov/report/` by default) on
+//! `http://127.0.0.1:«port»/`. The profile data folder (`target/cov/build/gcda/` by default) is polled for changes, e.g.
+//! after running `cargo cov test` in another terminal, and the report is regenerated once the files stop changing. The
+//! regenerated reports are not recorded in the coverage history, only `cargo cov report` does.
+//!
+//! Every HTML page is served with a small script which polls the version of the report from `/__cov_reload`, and
+//! reloads the page after the report has been regenerated.
+//!
+//! The server only speaks enough HTTP/1.1 for a browser to fetch static files: `GET` and `HEAD` requests, with one
+//! request per connection.
+
+use argparse::ReportConfig;
+use error::{Result, ResultExt};
+use report;
+use ui;
+use utils::{Snapshot, snapshot_files, wait_for_change};
+
+use open;
+
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Interval between two checks of the profile data folder.
+const POLL_INTERVAL: u64 = 500;
+
+/// The request path which returns the current version of the report.
+const RELOAD_PATH: &str = "/__cov_reload";
+
+/// Entry point of `cargo cov serve` subcommand. Generates the report, and serves it on `port` until the program is
+/// interrupted, regenerating it whenever the profile data is changed. If `port` is 0, a free port is picked.
+///
+/// Errors while regenerating the report are printed, and the previous pages are kept being served. The history entries
+/// returned by [`report::generate()`] are discarded.
+///
+/// [`report::generate()`]: ../report/fn.generate.html
+pub fn serve(config: &ReportConfig, port: u16, open: bool) -> Result<()> {
+    let (open_path, _) = report::generate(config)?;
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).chain_err(|| format!("Cannot listen on port {}", port))?;
+    let root = config.output_path.to_path_buf();
+    let page = open_path.as_ref().and_then(|path| path.strip_prefix(&root).ok()).map_or_else(String::new, url_path);
+    let url = format!("http://{}/{}", listener.local_addr()?, page);
+
+    let version = Arc::new(AtomicUsize::new(0));
+    {
+        let version = version.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(|stream| stream.ok()) {
+                let version = version.clone();
+                let root = root.clone();
+                thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream, &root, &version) {
+                        debug!("cannot respond to the request: {}", e);
+                    }
+                });
+            }
+        });
+    }
+
+    progress!("Serving", "{}", url);
+    if open {
+        let status = open::that(&url)?;
+        if !status.success() {
+            warning!("failed to open report, result: {}", status);
+        }
+    }
+
+    let take_snapshot = || {
+        let mut snapshot = Snapshot::new();
+        snapshot_files(&mut snapshot, &config.gcda_path, |entry| {
+            let extension = entry.path().extension();
+            entry.file_type().is_dir() || extension == Some(OsStr::new("gcda")) || extension == Some(OsStr::new("profraw"))
+        });
+        snapshot
+    };
+    let mut snapshot = take_snapshot();
+    loop {
+        snapshot = wait_for_change(&snapshot, Duration::from_millis(POLL_INTERVAL), &take_snapshot);
+        progress!("Regenerating", "{}", config.output_path.display());
+        match report::generate(config) {
+            Ok(_) => {
+                version.fetch_add(1, Ordering::SeqCst);
+            },
+            Err(e) => ui::print_error(&e)?,
+        }
+    }
+}
+
+/// Converts a relative path into the path component of a URL.
+fn url_path(path: &Path) -> String {
+    let components = path.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>();
+    components.join("/")
+}
+
+/// Reads a request from the connection, and writes back the response.
+fn handle_connection(stream: TcpStream, root: &Path, version: &AtomicUsize) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers, nothing in them affects the response.
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+    let mut stream = reader.into_inner();
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let target = parts.next().unwrap_or("/");
+    let target = target.split(&['?', '#'][..]).next().unwrap_or("/");
+    if method != "GET" && method != "HEAD" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"method not allowed", false);
+    }
+    let is_head = method == "HEAD";
+
+    let current_version = version.load(Ordering::SeqCst);
+    if target == RELOAD_PATH {
+        return respond(&mut stream, "200 OK", "text/plain", current_version.to_string().as_bytes(), is_head);
+    }
+
+    let path = match resolve_request_path(root, target) {
+        Some(path) => path,
+        None => return respond(&mut stream, "404 Not Found", "text/plain", b"not found", is_head),
+    };
+    let mut content = Vec::new();
+    if File::open(&path).and_then(|mut file| file.read_to_end(&mut content)).is_err() {
+        return respond(&mut stream, "404 Not Found", "text/plain", b"not found", is_head);
+    }
+
+    let content_type = content_type(&path);
+    if content_type.starts_with("text/html") {
+        let html = inject_reload_script(&String::from_utf8_lossy(&content), current_version);
+        respond(&mut stream, "200 OK", content_type, html.as_bytes(), is_head)
+    } else {
+        respond(&mut stream, "200 OK", content_type, &content, is_head)
+    }
+}
+
+/// Writes a complete response. The body is omitted for a `HEAD` request.
+fn respond<W: Write>(stream: &mut W, status: &str, content_type: &str, body: &[u8], is_head: bool) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    if !is_head {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+/// Maps the path of a request to a file inside `root`. A folder is mapped to its `index.html`.
+///
+/// Returns `None` if the path tries to escape from `root`, e.g. by `..`.
+fn resolve_request_path(root: &Path, target: &str) -> Option<PathBuf> {
+    let relative = if target.starts_with('/') { &target[1..] } else { target };
+    let mut path = root.to_path_buf();
+    for component in Path::new(relative).components() {
+        match component {
+            Component::Normal(name) => path.push(name),
+            Component::CurDir => {},
+            _ => return None,
+        }
+    }
+    if path.is_dir() {
+        path.push("index.html");
+    }
+    Some(path)
+}
+
+/// Guesses the content type of a file served from the report folder by its extension.
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(OsStr::to_str) {
+        Some("html") | Some("htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "application/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Inserts the script reloading the page when the report version is no longer `version` before `</body>`, or at the
+/// end if the page has no `</body>`.
+fn inject_reload_script(html: &str, version: usize) -> String {
+    let script = format!(
+        "<script>setInterval(function() {{ \
+         fetch('{}', {{cache: 'no-store'}}).then(function(r) {{ return r.text(); }}).then(function(v) {{ \
+         if (v !== '{}') {{ location.reload(); }} }}, function() {{}}); }}, 1000);</script>\n",
+        RELOAD_PATH,
+        version
+    );
+    let position = html.rfind("</body>").unwrap_or(html.len());
+    let mut result = String::with_capacity(html.len() + script.len());
+    result.push_str(&html[..position]);
+    result.push_str(&script);
+    result.push_str(&html[position..]);
+    result
+}
+
+#[test]
+fn test_resolve_request_path() {
+    let root = Path::new("/nonexistent/report");
+    assert_eq!(resolve_request_path(root, "/1.html"), Some(root.join("1.html")));
+    assert_eq!(resolve_request_path(root, "/static/common.css"), Some(root.join("static").join("common.css")));
+    assert_eq!(resolve_request_path(root, "/./index.html"), Some(root.join("index.html")));
+    assert_eq!(resolve_request_path(root, "/../secret"), None);
+    assert_eq!(resolve_request_path(root, "/static/../../secret"), None);
+}
+
+#[test]
+fn test_inject_reload_script() {
+    let html = inject_reload_script("<html><body><p>a</p></body></html>", 3);
+    assert!(html.starts_with("<html><body><p>a</p><script>"));
+    assert!(html.ends_with("</script>\n</body></html>"));
+    assert!(html.contains("if (v !== '3')"));
+
+    let html = inject_reload_script("<p>a</p>", 0);
+    assert!(html.starts_with("<p>a</p><script>"));
+}
@@ -18,24 +18,44 @@
 //! shim will then immediately scan the build directory and move the file inside `target/cov/build/{gcno,gcda}` under a
 //! unique new name.
 //!
+//! With the source-based coverage backend (`--backend source`), `rustc` is given `-Cinstrument-coverage` instead. The
+//! coverage mapping is embedded in the built executables, so the shim records the path of every executable as a
+//! `*.covobj` file in `target/cov/build/gcno`, and the raw profiles (`*.profraw`) are moved along with the GCDA files.
+//!
 //! The renaming is necessary for non-nightly Rust where `-Zprofile` is not supported. The GCNO/GCDA produced will be
 //! named after the crate, which both the doc-test and normal test coincide (`-Zprofile` fixes the problem by including
 //! the hash as well). This will cause one GCNO to overwrite another, and GCDA-merge will produce a corrupt report.
 //! `cargo cov` works-around this by moving these files to a unique location as soon as they are generated.
+//!
+//! Configurations
+//! --------------
+//!
+//! A build tagged by `--configuration` (e.g. the feature set or the target triple) is placed in its own target folder
+//! `target/cov/build/configurations/«id»/`, and its GCNO and GCDA files are moved to
+//! `target/cov/build/{gcno,gcda}/configurations/«id»/` instead. The tag itself is written into the `name` file of the
+//! GCNO folder, so the report can tell which configurations have covered a line.
 
-use argparse::is_rustc_compiling_local_crate;
-use error::{Result, ResultExt};
+use argparse::{is_rustc_compiling_local_crate, rustc_has_debuginfo, rustc_host_crate_kind, rustc_output_executable, split_shell_words,
+               strip_libtest_filters};
+use error::{ErrorKind, Result, ResultExt};
 use utils::{CommandExt, join_2, parent_3};
 
+use cov::Interner;
+use cov::reader::Reader;
 use fs2::FileExt;
+use md5;
 use rand::{Rng, thread_rng};
+use tempfile::TempDir;
 use walkdir::WalkDir;
 
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::env;
 use std::ffi::{OsStr, OsString};
-use std::fs::{File, rename};
-use std::path::Path;
-use std::process::Command;
+use std::fs::{File, copy, create_dir_all, read_dir, remove_file, rename};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
 /// Builds a crate by forwarding `args` to `rustc`.
 ///
@@ -47,6 +67,8 @@ use std::process::Command;
 /// | `COV_BUILD_PATH` | Path to `target/cov/build/` of the workspace |
 /// | `COV_PROFILER_LIB_PATH` | Path to folder containing `libclang_rt.profile*.a`, or the string `"@native"` |
 /// | `COV_PROFILER_LIB_NAME` | Library name e.g. `clang_rt.profile-x86_64`, or the string `"@native"` |
+/// | `COV_BACKEND` | (Optional) `source` to use source-based coverage instead of GCNO/GCDA |
+/// | `COV_CONFIGURATION` | (Optional) Folder name of the configuration, see [`gcov_dir()`] |
 ///
 /// If the crate to build is in the current workspace, several flags will be added to the command line:
 ///
@@ -58,25 +80,41 @@ use std::process::Command;
 /// | `-Coverflow-checks=off` | Disable overflow checks, which create unnecessary branches. |
 /// | `-Cinline-threshold=0` | Disable inlining, which complicates control flow. |
 /// | `-Ccodegen-units=1` | Disable ThinLTO which corrupts debuginfo (see [rustc issue #45511]). |
+/// | `-Cdebuginfo=1` (if unspecified) | Line information is needed for the GCNO, e.g. when building for `cargo bench` |
+///
+/// With the source-based coverage backend, only `-Cinstrument-coverage` and `-Clink-dead-code` are added, and the path
+/// of the built executable is recorded as `$COV_BUILD_PATH/gcno/*.covobj`.
 ///
-/// Additionally, all GCNO files generated will be moved to `$COV_BUILD_PATH/gcno/` after the build succeeds.
+/// Additionally, all GCNO files generated will be moved to `$COV_BUILD_PATH/gcno/` after the build succeeds. Build
+/// scripts and procedural macros are executed on the host during the build, so their GCNO files are stored separately
+/// in `$COV_BUILD_PATH/gcno/build-script/` and `$COV_BUILD_PATH/gcno/proc-macro/`. The GCDA files written by the
+/// procedural macros used by this crate are moved to `$COV_BUILD_PATH/gcda/` as well.
 ///
 /// # Panics
 ///
 /// Panics when any of the above environment variables is not set.
 ///
 /// [rustc issue #45511]: https://github.com/rust-lang/rust/issues/45511
+/// [`gcov_dir()`]: ./fn.gcov_dir.html
 pub fn rustc<'a, I: Iterator<Item = &'a OsStr> + Clone>(args: I) -> Result<()> {
     let rustc_path = env::var_os("COV_RUSTC").expect("COV_RUSTC");
     let cov_build_path_os = env::var_os("COV_BUILD_PATH").expect("COV_BUILD_PATH");
     let cov_build_path = Path::new(&cov_build_path_os);
     let workspace_path = parent_3(cov_build_path);
     let is_local = is_rustc_compiling_local_crate(args.clone(), workspace_path);
+    let host_kind = rustc_host_crate_kind(args.clone());
+    let has_debuginfo = rustc_has_debuginfo(args.clone());
+    let is_source_based = env::var_os("COV_BACKEND").map_or(false, |backend| backend == OsStr::new("source"));
+    let output_executable = rustc_output_executable(args.clone());
+    let configuration = env::var_os("COV_CONFIGURATION");
+    let configuration = configuration.as_ref().map(|c| &**c);
 
     let mut cmd = Command::new(rustc_path);
     cmd.args(args);
 
-    if is_local {
+    if is_local && is_source_based {
+        cmd.args(&["-Cinstrument-coverage", "-Clink-dead-code"]);
+    } else if is_local {
         let profiler_lib_path = env::var_os("COV_PROFILER_LIB_PATH").expect("COV_PROFILER_LIB_PATH");
         let profiler_lib_name = env::var_os("COV_PROFILER_LIB_NAME").expect("COV_PROFILER_LIB_NAME");
         debug!("Profiler: -L {:?} -l {:?}", profiler_lib_path, profiler_lib_name);
@@ -92,13 +130,29 @@ pub fn rustc<'a, I: Iterator<Item = &'a OsStr> + Clone>(args: I) -> Result<()> {
             "-Ccodegen-units=1",
             // "-Zdebug-macros", // don't enable, makes the gcno graph involving `assert!` even worse.
         ]);
+        if !has_debuginfo {
+            cmd.arg("-Cdebuginfo=1");
+        }
     }
 
     debug!("Executing {:?}", cmd);
 
     cmd.ensure_success("rustc")?;
-    if is_local {
-        move_gcov_files(cov_build_path, OsStr::new("gcno"))?;
+    if is_local && is_source_based {
+        if let Some(executable) = output_executable {
+            record_coverage_object(cov_build_path, &executable)?;
+        }
+        move_gcov_files(cov_build_path, OsStr::new("gcda"), configuration)?;
+    } else if is_local {
+        match host_kind {
+            Some(kind) => {
+                let dest_dir = gcov_dir(cov_build_path, OsStr::new("gcno"), configuration).join(kind);
+                create_dir_all(&dest_dir)?;
+                move_gcov_files_to(cov_build_path, OsStr::new("gcno"), &dest_dir)?;
+            },
+            None => move_gcov_files(cov_build_path, OsStr::new("gcno"), configuration)?,
+        }
+        move_gcov_files(cov_build_path, OsStr::new("gcda"), configuration)?;
     }
 
     Ok(())
@@ -113,12 +167,15 @@ pub fn rustc<'a, I: Iterator<Item = &'a OsStr> + Clone>(args: I) -> Result<()> {
 /// | `COV_RUSTDOC` | Path to `rustdoc` executable |
 /// | `COV_BUILD_PATH` | Path to `target/cov/build/` of the workspace |
 /// | `COV_PROFILER_LIB_PATH` | Path to folder containing `libclang_rt.profile*.a`, or the string `"@native"` |
+/// | `COV_CONFIGURATION` | (Optional) Folder name of the configuration, see [`gcov_dir()`] |
 ///
 /// All GCDA files generated will be moved to `$COV_BUILD_PATH/gcda/` after the test succeeds.
 ///
 /// # Panics
 ///
 /// Panics when any of the above environment variables is not set.
+///
+/// [`gcov_dir()`]: ./fn.gcov_dir.html
 pub fn rustdoc<'a, I: Iterator<Item = &'a OsStr>>(args: I) -> Result<()> {
     let rustdoc_path = env::var_os("COV_RUSTDOC").expect("COV_RUSTDOC");
     let cov_build_path_os = env::var_os("COV_BUILD_PATH").expect("COV_BUILD_PATH");
@@ -135,7 +192,8 @@ pub fn rustdoc<'a, I: Iterator<Item = &'a OsStr>>(args: I) -> Result<()> {
     debug!("Executing {:?}", cmd);
 
     cmd.ensure_success("rustdoc")?;
-    move_gcov_files(cov_build_path, OsStr::new("gcda"))?;
+    let configuration = env::var_os("COV_CONFIGURATION");
+    move_gcov_files(cov_build_path, OsStr::new("gcda"), configuration.as_ref().map(|c| &**c))?;
 
     Ok(())
 }
@@ -148,28 +206,206 @@ pub fn rustdoc<'a, I: Iterator<Item = &'a OsStr>>(args: I) -> Result<()> {
 /// | Environment variable | Meaning |
 /// |----------------------|---------|
 /// | `COV_BUILD_PATH` | Path to `target/cov/build/` of the workspace |
+/// | `COV_PER_TEST` | (Optional) If set, run each test of a libtest program in isolation |
+/// | `COV_RUNNER` | (Optional) Command which runs the program, e.g. `qemu-arm -L /sysroot` for cross-targets |
+/// | `COV_GCOV_PREFIX` | (Optional) Folder the program writes GCDA files into, passed as `GCOV_PREFIX` |
+/// | `COV_GCOV_PREFIX_STRIP` | (Optional) Number of leading path components to strip, passed as `GCOV_PREFIX_STRIP` |
+/// | `COV_GCDA_SYSROOT` | (Optional) Local folder of `COV_GCOV_PREFIX`, collected after every test with `COV_PER_TEST` |
+/// | `COV_CONFIGURATION` | (Optional) Folder name of the configuration, see [`gcov_dir()`] |
+///
+/// All GCDA files generated will be moved to `$COV_BUILD_PATH/gcda/` after the program exits, even if it failed. This
+/// shim is used for tests, benchmarks and binaries (`cargo cov run --example`) alike.
 ///
-/// All GCDA files generated will be moved to `$COV_BUILD_PATH/gcda/` after the program succeeds.
+/// When `COV_PER_TEST` is set and the program is a libtest test harness, every test will be executed one by one, and
+/// the GCDA files of each test will be kept separately in `$COV_BUILD_PATH/gcda/tests/*/`, so the report can tell
+/// which tests have covered a line.
 ///
 /// # Panics
 ///
 /// Panics when any of the above environment variables is not set.
-pub fn run<'a, I: Iterator<Item = &'a OsStr>>(mut args: I) -> Result<()> {
+///
+/// [`gcov_dir()`]: ./fn.gcov_dir.html
+pub fn run<'a, I: Iterator<Item = &'a OsStr> + Clone>(mut args: I) -> Result<()> {
     let cov_build_path_os = env::var_os("COV_BUILD_PATH").expect("COV_BUILD_PATH");
     let cov_build_path = Path::new(&cov_build_path_os);
 
-    let mut cmd = Command::new(args.next().expect("launcher"));
+    let program = args.next().expect("launcher");
+    let configuration = env::var_os("COV_CONFIGURATION");
+    let gcda_dir = gcov_dir(cov_build_path, OsStr::new("gcda"), configuration.as_ref().map(|c| &**c));
+
+    if env::var_os("COV_PER_TEST").is_some() && uses_libtest(Path::new(program)) {
+        let tests = list_tests(cov_build_path, &gcda_dir, program, args.clone())?;
+        if !tests.is_empty() {
+            return run_per_test(cov_build_path, &gcda_dir, program, args, &tests);
+        }
+        debug!("{:?} has no libtest tests, running as a whole", program);
+    }
+
+    let mut cmd = program_command(program);
     cmd.args(args);
     debug!("Executing {:?}", cmd);
 
-    cmd.ensure_success("test")?;
-    move_gcov_files(cov_build_path, OsStr::new("gcda"))?;
+    let status = cmd.status()?;
+    move_gcov_files_to(cov_build_path, OsStr::new("gcda"), &gcda_dir)?;
+    ensure!(status.success(), ErrorKind::ForwardFailed("test", status));
+
+    Ok(())
+}
+
+/// Creates a command which executes `program`, through the runner in `$COV_RUNNER` if it is set.
+///
+/// The runner is split into words by [`split_shell_words()`], and `program` is appended as the last argument. If
+/// `$COV_GCOV_PREFIX` is set, it is passed to the program as `$GCOV_PREFIX`, together with `$GCOV_PREFIX_STRIP`. Only the
+/// covered program receives them, so the build scripts and procedural macros executed by cargo still write their GCDA
+/// files locally.
+///
+/// [`split_shell_words()`]: ../argparse/fn.split_shell_words.html
+fn program_command(program: &OsStr) -> Command {
+    let runner = env::var("COV_RUNNER").ok().and_then(|runner| split_shell_words(&runner)).unwrap_or_default();
+    let mut cmd = match runner.split_first() {
+        Some((runner_program, runner_args)) => {
+            let mut cmd = Command::new(runner_program);
+            cmd.args(runner_args).arg(program);
+            cmd
+        },
+        None => Command::new(program),
+    };
+    if let Some(gcov_prefix) = env::var_os("COV_GCOV_PREFIX") {
+        cmd.env("GCOV_PREFIX", gcov_prefix);
+        if let Some(strip) = env::var_os("COV_GCOV_PREFIX_STRIP") {
+            cmd.env("GCOV_PREFIX_STRIP", strip);
+        }
+    }
+    cmd
+}
+
+/// The help text of an option of the libtest test harness, which is embedded in every program linking to libtest.
+const LIBTEST_MARKER: &[u8] = b"Exactly match filters rather than by substring";
+
+/// Checks whether `program` links to libtest, by searching its content for [`LIBTEST_MARKER`]. Programs with
+/// `harness = false` usually do not, and are thus run as a whole once, instead of being probed with `--list` first.
+///
+/// [`LIBTEST_MARKER`]: ./constant.LIBTEST_MARKER.html
+fn uses_libtest(program: &Path) -> bool {
+    let mut content = Vec::new();
+    if let Err(e) = File::open(program).and_then(|mut file| file.read_to_end(&mut content)) {
+        debug!("cannot read {:?}: {}", program, e);
+        return false;
+    }
+    contains_bytes(&content, LIBTEST_MARKER)
+}
+
+/// Checks whether `needle` appears anywhere in `haystack`.
+fn contains_bytes(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+#[test]
+fn test_contains_bytes() {
+    let program = b"\x7fELF\0\0--exact  Exactly match filters rather than by substring\0";
+    assert!(contains_bytes(program, LIBTEST_MARKER));
+    assert!(!contains_bytes(b"\x7fELF\0\0fn main() {}", LIBTEST_MARKER));
+    assert!(!contains_bytes(b"", LIBTEST_MARKER));
+}
+
+/// Lists the name of all tests in a libtest program by running it with `--list`. Returns an empty vector if the output
+/// does not look like that of a libtest test harness.
+///
+/// The GCDA files produced by the listing itself are discarded.
+fn list_tests<'a, I: Iterator<Item = &'a OsStr>>(cov_build_path: &Path, gcda_dir: &Path, program: &OsStr, args: I) -> Result<Vec<String>> {
+    let mut cmd = program_command(program);
+    cmd.args(args).arg("--list").stdin(Stdio::null()).stderr(Stdio::null());
+    debug!("Executing {:?}", cmd);
+    let output = cmd.output()?;
+
+    let discarded_gcda = TempDir::new_in(gcda_dir)?;
+    move_gcov_files_to(cov_build_path, OsStr::new("gcda"), discarded_gcda.path())?;
+
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_test_list(&stdout).into_iter().map(str::to_owned).collect())
+}
+
+/// Extracts the test names from the output of a libtest program executed with `--list`.
+///
+/// Every test is printed as `name: test` in the output. Benchmarks (`name: benchmark`) and other lines are skipped.
+fn parse_test_list(output: &str) -> Vec<&str> {
+    const SUFFIX: &str = ": test";
+    output.lines().filter(|line| line.ends_with(SUFFIX)).map(|line| &line[..(line.len() - SUFFIX.len())]).collect()
+}
+
+#[test]
+fn test_parse_test_list() {
+    let output = "tests::it_works: test\ntests::bench_it: benchmark\nlookup::test_foo: test\n\n2 tests, 1 benchmark\n";
+    assert_eq!(parse_test_list(output), vec!["tests::it_works", "lookup::test_foo"]);
+}
+
+/// Runs every test of a libtest program in isolation, and moves the GCDA files of each test into its own folder at
+/// `[gcda_dir]/tests/«random»/`. The name of the test is written into the `name` file of that folder. If
+/// `$COV_GCDA_SYSROOT` is set, the GCDA files written under `$GCOV_PREFIX` are collected into that folder as well.
+///
+/// The test name is prefixed by the crate name of the program, so that tests in different targets can be told apart.
+fn run_per_test<'a, I: Iterator<Item = &'a OsStr>>(cov_build_path: &Path, gcda_dir: &Path, program: &OsStr, args: I, tests: &[String]) -> Result<()> {
+    let options = strip_libtest_filters(args);
+    let crate_name = Path::new(program).file_stem().map_or(Cow::Borrowed("?"), OsStr::to_string_lossy);
+    let crate_name = crate_name.rsplitn(2, '-').last().unwrap_or("?");
+
+    let tests_path = gcda_dir.join("tests");
+    create_dir_all(&tests_path)?;
+    let gcda_sysroot = env::var_os("COV_GCDA_SYSROOT");
+    let configuration = env::var_os("COV_CONFIGURATION");
+
+    let mut rng = thread_rng();
+    let mut failure = None;
+    for test in tests {
+        let mut cmd = program_command(program);
+        cmd.args(&options).args(&["--exact", test]);
+        debug!("Executing {:?}", cmd);
+        let status = cmd.status()?;
+        if !status.success() {
+            failure = Some(status);
+        }
+
+        let test_path = loop {
+            let test_path = tests_path.join(format!("{:016x}", rng.gen::<u64>()));
+            if !test_path.exists() {
+                break test_path;
+            }
+        };
+        create_dir_all(&test_path)?;
+        let mut name_file = File::create(test_path.join("name"))?;
+        write!(name_file, "{}::{}", crate_name, test)?;
+        move_gcov_files_to(cov_build_path, OsStr::new("gcda"), &test_path)?;
+        if let Some(ref sysroot) = gcda_sysroot {
+            // The GCDA files written under `$GCOV_PREFIX` must be collected before the next test adds to them.
+            collect_gcda_from_sysroot_to(cov_build_path, Path::new(sysroot), configuration.as_ref().map(|c| &**c), &test_path)?;
+        }
+    }
+
+    if let Some(status) = failure {
+        bail!(ErrorKind::ForwardFailed("test", status));
+    }
+    Ok(())
+}
 
+/// Records the path of an executable built with `-Cinstrument-coverage` as `[cov_build_path]/gcno/*.covobj`, so the
+/// report can read the coverage mapping embedded in it.
+fn record_coverage_object(cov_build_path: &Path, executable: &Path) -> Result<()> {
+    let mut dest_path = cov_build_path.join("gcno").join("*");
+    set_unique_file_name(&mut thread_rng(), &mut dest_path, executable, OsStr::new("covobj"));
+    trace!("record {:?} in {:?}", executable, dest_path);
+    let mut file = File::create(&dest_path)?;
+    file.write_all(executable.to_string_lossy().as_bytes())?;
     Ok(())
 }
 
-/// Moves all files with the given `extension` to `[cov_build_path]/[extension]/`, and renames them uniquely so that
-/// there won't be file name collision inside that folder.
+/// Moves all files with the given `extension` to `[cov_build_path]/[extension]/` (or the folder of the `configuration`,
+/// see [`gcov_dir()`]), and renames them uniquely so that there won't be file name collision inside that folder.
+///
+/// When moving `gcda`, the raw profiles (`*.profraw`) written by programs built with the source-based coverage backend
+/// are moved together, keeping their extension.
 ///
 /// # Examples
 ///
@@ -180,14 +416,68 @@ pub fn run<'a, I: Iterator<Item = &'a OsStr>>(mut args: I) -> Result<()> {
 /// # fn main() { run().unwrap(); }
 /// # fn run() -> ::std::io::Result<()> {
 /// let build_folder = Path::new("workspace/target/cov/build");
-/// move_gcov_files(build_folder, OsStr::new("gcda"))?;
+/// move_gcov_files(build_folder, OsStr::new("gcda"), None)?;
 /// // All `*.gcda` files found inside `workspace/target/cov/build` will now be moved to
 /// // `workspace/target/cov/build/gcda`.
 /// # }
 /// ```
-pub fn move_gcov_files(cov_build_path: &Path, extension: &OsStr) -> Result<()> {
+///
+/// [`gcov_dir()`]: ./fn.gcov_dir.html
+pub fn move_gcov_files(cov_build_path: &Path, extension: &OsStr, configuration: Option<&OsStr>) -> Result<()> {
+    let dest_dir = gcov_dir(cov_build_path, extension, configuration);
+    create_dir_all(&dest_dir)?;
+    move_gcov_files_to(cov_build_path, extension, &dest_dir)
+}
+
+/// Returns the folder storing the files with the given `extension` (`gcno` or `gcda`), i.e.
+/// `[cov_build_path]/[extension]/`, or `[cov_build_path]/[extension]/configurations/[configuration]/` for a build
+/// tagged by `--configuration`. The `configuration` here is the folder name returned from [`configuration_id()`].
+///
+/// [`configuration_id()`]: ./fn.configuration_id.html
+pub fn gcov_dir(cov_build_path: &Path, extension: &OsStr, configuration: Option<&OsStr>) -> PathBuf {
+    let dir = cov_build_path.join(extension);
+    match configuration {
+        Some(configuration) => join_2(&dir, "configurations", configuration),
+        None => dir,
+    }
+}
+
+/// Converts a configuration tag (e.g. `--features=a b`) into a folder name, by replacing every character other than
+/// ASCII alphanumerics, `-`, `_` and `.` with `_`. A leading `.` is replaced as well. The first 8 hex digits of the MD5
+/// of the tag are appended, so distinct tags replaced into the same text (e.g. `--features=a,b` and `--features=a_b`)
+/// are still built in different folders.
+pub fn configuration_id(tag: &str) -> String {
+    let mut id = tag.char_indices()
+        .map(|(i, c)| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || (c == '.' && i > 0) { c } else { '_' })
+        .collect::<String>();
+    let hash = format!("{:x}", md5::compute(tag));
+    id.push('-');
+    id.push_str(&hash[..8]);
+    id
+}
+
+#[test]
+fn test_configuration_id() {
+    let id = configuration_id("--features=a b");
+    assert!(id.starts_with("--features_a_b-"));
+    assert_eq!(id.len(), "--features_a_b-".len() + 8);
+    assert!(configuration_id("x86_64-unknown-linux-gnu").starts_with("x86_64-unknown-linux-gnu-"));
+    assert!(configuration_id("../x").starts_with("_._x-"));
+    assert_ne!(configuration_id("--features=a,b"), configuration_id("--features=a_b"));
+    assert_eq!(configuration_id("--features=a,b"), configuration_id("--features=a,b"));
+}
+
+/// Moves all files with the given `extension` to `dest_dir`, and renames them uniquely. See [`move_gcov_files()`] for
+/// detail.
+///
+/// `dest_dir` should be a descendant of `[cov_build_path]/gcda/` or `[cov_build_path]/gcno/`, otherwise the files may
+/// be moved again by the next call.
+///
+/// [`move_gcov_files()`]: ./fn.move_gcov_files.html
+pub fn move_gcov_files_to(cov_build_path: &Path, extension: &OsStr, dest_dir: &Path) -> Result<()> {
     let mut rng = thread_rng();
-    let mut dest_path = join_2(cov_build_path, extension, "*");
+    let mut dest_path = dest_dir.join("*");
+    let is_profile_data = |ext: Option<&OsStr>| ext == Some(extension) || (extension == "gcda" && ext == Some(OsStr::new("profraw")));
 
     let mut lock_file = LockFile::new(cov_build_path)?;
 
@@ -199,7 +489,7 @@ pub fn move_gcov_files(cov_build_path: &Path, extension: &OsStr) -> Result<()> {
             if file_name == Some(OsStr::new("gcda")) || file_name == Some(OsStr::new("gcno")) {
                 return false;
             }
-        } else if file_type.is_file() && path.extension() != Some(extension) {
+        } else if file_type.is_file() && !is_profile_data(path.extension()) {
             return false;
         }
         true
@@ -212,23 +502,106 @@ pub fn move_gcov_files(cov_build_path: &Path, extension: &OsStr) -> Result<()> {
         }
 
         let source_path = entry.path();
+        set_unique_file_name(&mut rng, &mut dest_path, source_path, source_path.extension().unwrap_or(extension));
+        trace!("mv {:?} {:?}", source_path, dest_path);
+        rename(source_path, &dest_path).chain_err(|| format!("cannot move `{}` to `{}`", source_path.display(), dest_path.display()))?;
+    }
 
-        loop {
-            let mut filename = OsString::from(format!("{:016x}.", rng.gen::<u64>()));
-            filename.push(source_path.file_stem().unwrap_or_else(|| OsStr::new("?")));
-            filename.push(OsStr::new("."));
-            filename.push(extension);
-            dest_path.set_file_name(filename);
-            if !dest_path.exists() {
-                break;
-            }
+    lock_file.unlock()
+}
+
+/// Collects the GCDA files written under `$GCOV_PREFIX` by a cross-target runner, and moves them to
+/// `[cov_build_path]/gcda/` (or the folder of the `configuration`, see [`gcov_dir()`]). Returns the number of files
+/// collected.
+///
+/// `sysroot` is the local folder corresponding to `$GCOV_PREFIX` of the device. Since `$GCOV_PREFIX_STRIP` removes the
+/// `[cov_build_path]` part, the file `[sysroot]/«rel»/x.gcda` is produced by the object `[cov_build_path]/«rel»/x.o`.
+/// The GCDA will only be collected if the folder `[cov_build_path]/«rel»` exists, and a GCNO originated from `x.gcno`
+/// with the same stamp is found in the GCNO folder. Other GCDA files are stale and will be left untouched.
+///
+/// [`gcov_dir()`]: ./fn.gcov_dir.html
+pub fn collect_gcda_from_sysroot(cov_build_path: &Path, sysroot: &Path, configuration: Option<&OsStr>) -> Result<usize> {
+    let dest_dir = gcov_dir(cov_build_path, OsStr::new("gcda"), configuration);
+    collect_gcda_from_sysroot_to(cov_build_path, sysroot, configuration, &dest_dir)
+}
+
+/// Collects the GCDA files written under `$GCOV_PREFIX` to `dest_dir`. See [`collect_gcda_from_sysroot()`] for detail.
+///
+/// [`collect_gcda_from_sysroot()`]: ./fn.collect_gcda_from_sysroot.html
+pub fn collect_gcda_from_sysroot_to(cov_build_path: &Path, sysroot: &Path, configuration: Option<&OsStr>, dest_dir: &Path) -> Result<usize> {
+    let mut interner = Interner::new();
+    let mut gcno_identities = HashSet::new();
+    for entry in read_dir(gcov_dir(cov_build_path, OsStr::new("gcno"), configuration))? {
+        let path = entry?.path();
+        if path.extension() != Some(OsStr::new("gcno")) {
+            continue;
+        }
+        if let Some(stem) = original_file_stem(&path) {
+            let stamp = Reader::new(File::open(&path)?, &mut interner)?.stamp();
+            gcno_identities.insert((stem.to_owned(), stamp));
+        }
+    }
+
+    let mut rng = thread_rng();
+    let mut dest_path = dest_dir.join("*");
+    let mut count = 0;
+
+    let mut lock_file = LockFile::new(cov_build_path)?;
+
+    for entry in WalkDir::new(sysroot) {
+        let entry = entry?;
+        let source_path = entry.path();
+        if !entry.file_type().is_file() || source_path.extension() != Some(OsStr::new("gcda")) {
+            continue;
         }
 
+        let rel_path = source_path.strip_prefix(sysroot).expect("walkdir entry is inside the root");
+        let is_build_path = rel_path.parent().map_or(false, |parent| cov_build_path.join(parent).is_dir());
+        let stamp = Reader::new(File::open(source_path)?, &mut interner)?.stamp();
+        let stem = source_path.file_stem().and_then(OsStr::to_str).unwrap_or("");
+        if !is_build_path || !gcno_identities.contains(&(stem.to_owned(), stamp)) {
+            warning!("skipping `{}`, which does not match any GCNO", source_path.display());
+            continue;
+        }
+
+        set_unique_file_name(&mut rng, &mut dest_path, source_path, OsStr::new("gcda"));
         trace!("mv {:?} {:?}", source_path, dest_path);
-        rename(source_path, &dest_path).chain_err(|| format!("cannot move `{}` to `{}`", source_path.display(), dest_path.display()))?;
+        // The sysroot may be on another file system, so `rename` cannot be used.
+        copy(source_path, &dest_path).chain_err(|| format!("cannot copy `{}` to `{}`", source_path.display(), dest_path.display()))?;
+        remove_file(source_path)?;
+        count += 1;
     }
 
-    lock_file.unlock()
+    lock_file.unlock()?;
+    Ok(count)
+}
+
+/// Changes the file name of `dest_path` to a unique name derived from `source_path`, i.e. `«random».«stem».«ext»`.
+fn set_unique_file_name<R: Rng>(rng: &mut R, dest_path: &mut PathBuf, source_path: &Path, extension: &OsStr) {
+    loop {
+        let mut filename = OsString::from(format!("{:016x}.", rng.gen::<u64>()));
+        filename.push(source_path.file_stem().unwrap_or_else(|| OsStr::new("?")));
+        filename.push(OsStr::new("."));
+        filename.push(extension);
+        dest_path.set_file_name(filename);
+        if !dest_path.exists() {
+            break;
+        }
+    }
+}
+
+/// Recovers the file stem before [`set_unique_file_name()`] renamed the file.
+///
+/// [`set_unique_file_name()`]: ./fn.set_unique_file_name.html
+fn original_file_stem(path: &Path) -> Option<&str> {
+    let stem = path.file_stem()?.to_str()?;
+    stem.find('.').map(|i| &stem[(i + 1)..])
+}
+
+#[test]
+fn test_original_file_stem() {
+    assert_eq!(original_file_stem(Path::new("gcno/0123456789abcdef.foo-1a2b3c.gcno")), Some("foo-1a2b3c"));
+    assert_eq!(original_file_stem(Path::new("gcno/foo.gcno")), None);
 }
 
 struct LockFile(Option<File>);
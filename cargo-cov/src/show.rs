@@ -0,0 +1,119 @@
+//! Annotated source code in the terminal.
+//!
+//! `cargo cov show` prints a source file with the execution count of every line, similar to `gcov`. Unexecuted lines
+//! are marked by `#####`, and explained by the nearest branch which has never been followed to reach them (GCNO/GCDA
+//! only). The explanation is only repeated when it changes:
+//!
+//! ```text
+//!    Count  Line  Source
+//!        5     1  fn f(x: bool) {
+//!        5     2      if x {
+//!    #####     3          a();
+//!                         ^ reachable from line 2 when the branch is not taken
+//!    #####     4          b();
+//!        5     5      }
+//!        5     6  }
+//! ```
+
+use error::Result;
+
+use cov::{BranchDirection, Interner, Symbol, UncoveredPath};
+use cov::report::File;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Describes how an unexecuted line in the file `symbol` could have been reached. The path of the branch is only
+/// mentioned if it is in another file.
+pub fn describe(path: &UncoveredPath, symbol: Symbol, interner: &Interner) -> String {
+    let direction = match path.direction {
+        BranchDirection::Taken => "taken",
+        BranchDirection::NotTaken => "not taken",
+    };
+    if path.branch_filename == symbol {
+        format!("reachable from line {} when the branch is {}", path.branch_line, direction)
+    } else {
+        format!("reachable from {}:{} when the branch is {}", &interner[path.branch_filename], path.branch_line, direction)
+    }
+}
+
+/// Annotates the source lines with the execution counts, and the `hints` explaining the unexecuted lines keyed by the
+/// line number. Lines absent from the source are listed at the end.
+fn annotate(source_lines: &[String], file: &File, hints: &HashMap<u32, String>) -> Vec<String> {
+    let last_line_number = file.lines.keys().next_back().map_or(0, |&ln| ln as usize);
+    let mut output = Vec::new();
+    let mut last_hint = None;
+    for line_number in 1..(source_lines.len().max(last_line_number) + 1) {
+        let line_number = line_number as u32;
+        let source = source_lines.get(line_number as usize - 1).map_or("/* EOF */", |s| &**s);
+        let line = file.lines.get(&line_number);
+        let count = match line {
+            None => String::new(),
+            Some(line) if line.count == 0 => "#####".to_owned(),
+            Some(line) => line.count.to_string(),
+        };
+        output.push(format!("{:>8}  {:>4}  {}", count, line_number, source));
+
+        let hint = match line {
+            Some(line) if line.count == 0 => hints.get(&line_number),
+            _ => None,
+        };
+        if hint.is_some() && hint != last_hint {
+            let indent = source.chars().take_while(|c| c.is_whitespace()).collect::<String>();
+            output.push(format!("{:>8}  {:>4}  {}^ {}", "", "", indent, hint.unwrap()));
+        }
+        last_hint = hint;
+    }
+    output
+}
+
+#[test]
+fn test_annotate() {
+    use cov::report::Line;
+
+    let source_lines = ["fn f(x: bool) {", "    if x {", "        a();", "        b();", "    }", "}"];
+    let source_lines = source_lines.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let mut file = File::default();
+    for &(line_number, count) in &[(1, 5), (2, 5), (3, 0), (4, 0), (5, 5), (6, 5), (8, 0)] {
+        file.lines.insert(line_number, Line { count, ..Line::default() });
+    }
+    let mut hints = HashMap::new();
+    hints.insert(3, "reachable from line 2 when the branch is not taken".to_owned());
+    hints.insert(4, "reachable from line 2 when the branch is not taken".to_owned());
+
+    assert_eq!(
+        annotate(&source_lines, &file, &hints),
+        vec![
+            "       5     1  fn f(x: bool) {",
+            "       5     2      if x {",
+            "   #####     3          a();",
+            "                        ^ reachable from line 2 when the branch is not taken",
+            "   #####     4          b();",
+            "       5     5      }",
+            "       5     6  }",
+            "             7  /* EOF */",
+            "   #####     8  /* EOF */",
+        ]
+    );
+}
+
+/// Prints the annotated source file at `source_path` to stdout. See [`annotate()`] for the format.
+///
+/// [`annotate()`]: ./fn.annotate.html
+pub fn print(source_path: &Path, file: &File, hints: &HashMap<u32, String>) -> Result<()> {
+    let source_lines = match fs::File::open(source_path) {
+        Ok(source_file) => BufReader::new(source_file).lines().collect::<io::Result<Vec<_>>>()?,
+        Err(_) => Vec::new(),
+    };
+
+    let stdout = io::stdout();
+    let mut lock = stdout.lock();
+    writeln!(lock, "{}", source_path.display())?;
+    writeln!(lock, "{:>8}  {:>4}  Source", "Count", "Line")?;
+    for line in annotate(&source_lines, file, hints) {
+        writeln!(lock, "{}", line)?;
+    }
+    Ok(())
+}
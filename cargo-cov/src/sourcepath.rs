@@ -6,12 +6,23 @@
 //!
 //! This module provides a function to analyze the source path and determines if it is interesting or not. User can then
 //! selectively hide those reports if the category is not interesting.
+//!
+//! | Category    | `--include` | Display prefix | Path                                                                   |
+//! |:------------|:------------|:---------------|:-----------------------------------------------------------------------|
+//! | `LOCAL`     | `local`     | `.`            | The workspace                                                          |
+//! | `MACROS`    | `macros`    |                | `<std macros>` etc                                                     |
+//! | `CRATES`    | `crates`    | `«crates»`     | `$CARGO_HOME/registry/src/`                                            |
+//! | `GIT`       | `git`       | `«git»`        | `$CARGO_HOME/git/checkouts/`                                           |
+//! | `VENDORED`  | `vendored`  | `«vendor»`     | `vendor/` created by `cargo vendor`                                    |
+//! | `GENERATED` | `generated` | `«out»`        | `…/build/«package»-«hash»/out/`, the `$OUT_DIR` of build scripts       |
+//! | `RUSTSRC`   | `rustsrc`   | `«rust»`       | The rustup `rust-src` component, or where the official builds are made |
+//! | `UNKNOWN`   | `unknown`   |                | Anything else                                                          |
 
 use cov::IntoStringLossy;
 
-use home::cargo_home;
+use home::{cargo_home, rustup_home};
 
-use std::path::MAIN_SEPARATOR;
+use std::path::{Path, MAIN_SEPARATOR};
 use std::str::FromStr;
 
 /// Path to the hard-coded Rust source of libraries built by macOS builders on Travis CI.
@@ -34,6 +45,41 @@ lazy_static! {
         registry_path.push(MAIN_SEPARATOR);
         registry_path
     };
+
+    /// The path where Cargo checks out git dependencies.
+    ///
+    /// The string should be equal to `$CARGO_HOME/git/checkouts`.
+    static ref GIT_CHECKOUTS_PATH: String = {
+        let mut cargo_home = cargo_home().expect("$CARGO_HOME is undefined");
+        cargo_home.push("git");
+        cargo_home.push("checkouts");
+        let mut git_path = cargo_home.into_string_lossy();
+        git_path.push(MAIN_SEPARATOR);
+        git_path
+    };
+
+    /// The path where rustup installs the toolchains. The Rust standard libraries from the `rust-src` component are
+    /// found in `«toolchain»/lib/rustlib/src/rust/` inside this folder.
+    ///
+    /// The string should be equal to `$RUSTUP_HOME/toolchains` where `$RUSTUP_HOME` is `~/.rustup` by default.
+    static ref RUSTUP_TOOLCHAINS_PATH: Option<String> = rustup_home().ok().map(|mut rustup_home| {
+        rustup_home.push("toolchains");
+        let mut toolchains_path = rustup_home.into_string_lossy();
+        toolchains_path.push(MAIN_SEPARATOR);
+        toolchains_path
+    });
+
+    /// The folder of the `rust-src` component relative to the toolchain, `lib/rustlib/src/rust/`.
+    static ref RUST_SRC_COMPONENT_DIR: String = ["lib", "rustlib", "src", "rust", ""].join(&MAIN_SEPARATOR.to_string());
+
+    /// The `/build/` path component which contains the build script outputs.
+    static ref BUILD_DIR_COMPONENT: String = format!("{0}build{0}", MAIN_SEPARATOR);
+
+    /// The `/out/` path component which is the `$OUT_DIR` of a build script.
+    static ref OUT_DIR_COMPONENT: String = format!("{0}out{0}", MAIN_SEPARATOR);
+
+    /// The `/vendor/` path component which contains sources vendored by `cargo vendor`.
+    static ref VENDOR_DIR_COMPONENT: String = format!("{0}vendor{0}", MAIN_SEPARATOR);
 }
 
 bitflags! {
@@ -49,12 +95,33 @@ bitflags! {
         const CRATES = 8;
         /// The path is in the Rust standard libraries.
         const RUSTSRC = 16;
+        /// The path is of git dependencies, checked out by Cargo.
+        const GIT = 32;
+        /// The path is of external crates vendored by `cargo vendor`.
+        const VENDORED = 64;
+        /// The path is a file generated by a build script into its `$OUT_DIR`.
+        const GENERATED = 128;
 
         /// The default set of interesting source paths.
         const DEFAULT = SourceType::LOCAL.bits | SourceType::MACROS.bits | SourceType::UNKNOWN.bits;
     }
 }
 
+/// The names of the source types in `--include`, in alphabetical order. `all` is accepted by [`SourceType::from_str()`]
+/// as well.
+///
+/// [`SourceType::from_str()`]: ./struct.SourceType.html#method.from_str
+const SOURCE_TYPE_NAMES: &[(&str, SourceType)] = &[
+    ("crates", SourceType::CRATES),
+    ("generated", SourceType::GENERATED),
+    ("git", SourceType::GIT),
+    ("local", SourceType::LOCAL),
+    ("macros", SourceType::MACROS),
+    ("rustsrc", SourceType::RUSTSRC),
+    ("unknown", SourceType::UNKNOWN),
+    ("vendored", SourceType::VENDORED),
+];
+
 /// The error raised when [`SourceType::from_str()`] encounters an unrecognized string.
 ///
 /// [`SourceType::from_str()`]: ./struct.SourceType.html#method.from_str
@@ -81,12 +148,20 @@ impl SourceType {
         Ok(res)
     }
 
+    /// Lists the names of the source types in this set, in alphabetical order.
+    pub fn names(self) -> Vec<&'static str> {
+        SOURCE_TYPE_NAMES.iter().filter(|&&(_, source_type)| self.contains(source_type)).map(|&(name, _)| name).collect()
+    }
+
     /// Obtains the path prefix so that
     pub fn prefix(self) -> &'static str {
         match self {
             SourceType::LOCAL => ".",
             SourceType::RUSTSRC => "«rust»",
             SourceType::CRATES => "«crates»",
+            SourceType::GIT => "«git»",
+            SourceType::VENDORED => "«vendor»",
+            SourceType::GENERATED => "«out»",
             _ => "",
         }
     }
@@ -96,15 +171,13 @@ impl SourceType {
 impl FromStr for SourceType {
     type Err = UnsupportedSourceTypeName;
     fn from_str(s: &str) -> Result<SourceType, UnsupportedSourceTypeName> {
-        Ok(match s {
-            "local" => SourceType::LOCAL,
-            "macros" => SourceType::MACROS,
-            "rustsrc" => SourceType::RUSTSRC,
-            "crates" => SourceType::CRATES,
-            "unknown" => SourceType::UNKNOWN,
-            "all" => SourceType::all(),
-            _ => return Err(UnsupportedSourceTypeName),
-        })
+        if s == "all" {
+            return Ok(SourceType::all());
+        }
+        match SOURCE_TYPE_NAMES.iter().find(|&&(name, _)| name == s) {
+            Some(&(_, source_type)) => Ok(source_type),
+            None => Err(UnsupportedSourceTypeName),
+        }
     }
 }
 
@@ -134,12 +207,22 @@ impl FromStr for SourceType {
 /// [`SourceType`]: ./struct.SourceType.html
 /// [`SourceType::LOCAL`]: ./constant.SourceType::LOCAL.html
 pub fn identify_source_path(path: &str, crates_path: &str) -> (SourceType, usize) {
-    if path.starts_with(crates_path) {
-        (SourceType::LOCAL, crates_path.len())
+    if let Some(out_dir_parent_len) = find_out_dir(path) {
+        // Checked before LOCAL, since the target directory is usually inside the workspace.
+        (SourceType::GENERATED, out_dir_parent_len)
     } else if path.starts_with(&*REGISTRY_PATH) {
+        // Checked before VENDORED, since a published crate may contain a `vendor/` folder of its own.
         let subpath = &path[REGISTRY_PATH.len()..];
         let first_slash = subpath.find(MAIN_SEPARATOR).map_or(0, |s| s + MAIN_SEPARATOR.len_utf8());
         (SourceType::CRATES, REGISTRY_PATH.len() + first_slash)
+    } else if path.starts_with(&*GIT_CHECKOUTS_PATH) {
+        (SourceType::GIT, GIT_CHECKOUTS_PATH.len())
+    } else if let Some(vendor_dir_len) = find_vendor_dir(path, crates_path) {
+        (SourceType::VENDORED, vendor_dir_len)
+    } else if path.starts_with(crates_path) {
+        (SourceType::LOCAL, crates_path.len())
+    } else if let Some(rust_src_len) = find_rust_src_component(path) {
+        (SourceType::RUSTSRC, rust_src_len)
     } else if path.starts_with('<') && path.ends_with(" macros>") {
         (SourceType::MACROS, 0)
     } else if path.starts_with(MACOS_RUSTSRC_DIR) {
@@ -152,3 +235,108 @@ pub fn identify_source_path(path: &str, crates_path: &str) -> (SourceType, usize
         (SourceType::UNKNOWN, 0)
     }
 }
+
+/// Finds the `…/build/«package»-«hash»/out/` folder in the path, i.e. the `$OUT_DIR` of a build script. Returns the
+/// length of the prefix up to `…/build/`.
+fn find_out_dir(path: &str) -> Option<usize> {
+    path.match_indices(&*BUILD_DIR_COMPONENT).map(|(i, _)| i + BUILD_DIR_COMPONENT.len()).find(|&start| {
+        let subpath = &path[start..];
+        match subpath.find(MAIN_SEPARATOR) {
+            Some(s) => subpath[s..].starts_with(&*OUT_DIR_COMPONENT) && has_hash_suffix(&subpath[..s]),
+            None => false,
+        }
+    })
+}
+
+/// Checks whether the folder name ends with the `-«hash»` added by Cargo, e.g. `foo-0123456789abcdef`.
+fn has_hash_suffix(name: &str) -> bool {
+    match name.rfind('-') {
+        Some(i) => {
+            let hash = &name[(i + 1)..];
+            i > 0 && !hash.is_empty() && hash.chars().all(|c| c.is_ascii_hexdigit())
+        },
+        None => false,
+    }
+}
+
+/// Finds the `vendor/` folder created by `cargo vendor`. Inside the workspace, only the top-level `vendor/` folder is
+/// recognized. Outside of it, a `vendor/` folder is accepted if the crate inside it has the `.cargo-checksum.json`
+/// written by `cargo vendor`. Returns the length of the prefix up to `vendor/`.
+fn find_vendor_dir(path: &str, crates_path: &str) -> Option<usize> {
+    if path.starts_with(crates_path) {
+        let mut subpath = &path[crates_path.len()..];
+        if subpath.starts_with(MAIN_SEPARATOR) {
+            subpath = &subpath[MAIN_SEPARATOR.len_utf8()..];
+        }
+        let vendor_dir = &VENDOR_DIR_COMPONENT[MAIN_SEPARATOR.len_utf8()..];
+        if subpath.starts_with(vendor_dir) {
+            Some(path.len() - subpath.len() + vendor_dir.len())
+        } else {
+            None
+        }
+    } else {
+        path.match_indices(&*VENDOR_DIR_COMPONENT).map(|(i, _)| i + VENDOR_DIR_COMPONENT.len()).find(|&start| {
+            let subpath = &path[start..];
+            subpath.find(MAIN_SEPARATOR).map_or(false, |s| Path::new(&path[..(start + s)]).join(".cargo-checksum.json").is_file())
+        })
+    }
+}
+
+/// Finds the `lib/rustlib/src/rust/` folder of the `rust-src` component installed by rustup. Returns the length of the
+/// prefix up to that folder.
+fn find_rust_src_component(path: &str) -> Option<usize> {
+    let toolchains_path = RUSTUP_TOOLCHAINS_PATH.as_ref()?;
+    if !path.starts_with(toolchains_path) {
+        return None;
+    }
+    let subpath = &path[toolchains_path.len()..];
+    let toolchain_len = subpath.find(MAIN_SEPARATOR)? + MAIN_SEPARATOR.len_utf8();
+    if subpath[toolchain_len..].starts_with(&*RUST_SRC_COMPONENT_DIR) {
+        Some(toolchains_path.len() + toolchain_len + RUST_SRC_COMPONENT_DIR.len())
+    } else {
+        None
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_identify_source_path() {
+    fn simplify(path: &str) -> String {
+        let (source_type, prefix_len) = identify_source_path(path, "/ws/");
+        format!("{}/{}", source_type.prefix(), &path[prefix_len..])
+    }
+
+    assert_eq!(simplify("/ws/src/lib.rs"), "./src/lib.rs");
+    assert_eq!(simplify("/ws/vendor/libc/src/lib.rs"), "«vendor»/libc/src/lib.rs");
+    assert_eq!(simplify("/ws/src/vendor/mod.rs"), "./src/vendor/mod.rs");
+    assert_eq!(identify_source_path("/nonexistent/vendor/libc/src/lib.rs", "/ws/"), (SourceType::UNKNOWN, 0));
+    assert_eq!(simplify("/ws/target/cov/build/debug/build/foo-0123abcd/out/gen.rs"), "«out»/foo-0123abcd/out/gen.rs");
+    assert_eq!(simplify("/ws/src/build/out.rs"), "./src/build/out.rs");
+    assert_eq!(simplify("/ws/src/build/codegen/out/mod.rs"), "./src/build/codegen/out/mod.rs");
+
+    let git_path = format!("{}serde-0123abcd/4567ef0/src/lib.rs", *GIT_CHECKOUTS_PATH);
+    assert_eq!(identify_source_path(&git_path, "/ws/"), (SourceType::GIT, GIT_CHECKOUTS_PATH.len()));
+    let registry_path = format!("{}github.com-1ecc6299db9ec823/openssl-src-111.0.0/openssl/vendor/x.rs", *REGISTRY_PATH);
+    assert_eq!(simplify(&registry_path), "«crates»/openssl-src-111.0.0/openssl/vendor/x.rs");
+
+    if let Some(ref toolchains_path) = *RUSTUP_TOOLCHAINS_PATH {
+        let rust_src_path = format!("{}stable-x86_64-unknown-linux-gnu/lib/rustlib/src/rust/library/core/src/option.rs", toolchains_path);
+        assert_eq!(simplify(&rust_src_path), "«rust»/library/core/src/option.rs");
+    }
+}
+
+#[test]
+fn test_find_vendor_dir() {
+    use std::fs::{File, create_dir_all};
+    use tempfile::TempDir;
+
+    let dir = TempDir::new().expect("created temporary directory");
+    let vendored_crate = dir.path().join("vendor").join("libc");
+    create_dir_all(&vendored_crate).unwrap();
+    let path = vendored_crate.join("src").join("lib.rs").into_string_lossy();
+    assert_eq!(find_vendor_dir(&path, "/ws/"), None);
+
+    File::create(vendored_crate.join(".cargo-checksum.json")).unwrap();
+    let vendor_dir_len = dir.path().join("vendor").into_string_lossy().len() + MAIN_SEPARATOR.len_utf8();
+    assert_eq!(find_vendor_dir(&path, "/ws/"), Some(vendor_dir_len));
+}
@@ -2,10 +2,13 @@
 
 use error::{ErrorKind, Result as CargoCovResult};
 
+use cov::report::FileSummary;
 use natord::compare_iter;
 use serde_json::Value;
+use walkdir::{DirEntry, WalkDir};
 
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fs::{File, Permissions, remove_dir_all};
 use std::io;
 #[cfg(target_os = "redox")]
@@ -18,6 +21,8 @@ use std::os::unix::fs::PermissionsExt;
 use std::os::windows::ffi::{OsStrExt, OsStringExt};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
 
 pub trait OptionExt {
     type Value;
@@ -110,6 +115,22 @@ impl CommandExt for Command {
     }
 }
 
+/// Adds up the file summaries, e.g. to obtain the summary of a whole package.
+pub fn sum_file_summaries<'a, I: IntoIterator<Item = &'a FileSummary>>(summaries: I) -> FileSummary {
+    summaries.into_iter().fold(FileSummary::default(), |mut total, s| {
+        total.lines_count += s.lines_count;
+        total.lines_covered += s.lines_covered;
+        total.branches_count += s.branches_count;
+        total.branches_executed += s.branches_executed;
+        total.branches_taken += s.branches_taken;
+        total.functions_count += s.functions_count;
+        total.functions_called += s.functions_called;
+        total.panic_paths_count += s.panic_paths_count;
+        total.panic_paths_taken += s.panic_paths_taken;
+        total
+    })
+}
+
 /// Short circuit of `path.parent().parent().parent()`.
 ///
 /// # Panics
@@ -133,3 +154,44 @@ pub fn join_3<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(path: &Path, a:
     path.push(c);
     path
 }
+
+/// The length and modification time of files, keyed by the path. See [`snapshot_files()`].
+///
+/// [`snapshot_files()`]: ./fn.snapshot_files.html
+pub type Snapshot = BTreeMap<PathBuf, (u64, Option<SystemTime>)>;
+
+/// Records the length and modification time of every file inside `dir` (recursively). Only the entries accepted by
+/// `filter` are visited, so rejecting a folder skips everything inside. Changes of the files are detected by comparing
+/// two snapshots. Entries which cannot be read are skipped, and a missing `dir` gives an empty snapshot.
+pub fn snapshot_files<F: FnMut(&DirEntry) -> bool>(snapshot: &mut Snapshot, dir: &Path, filter: F) {
+    for entry in WalkDir::new(dir).into_iter().filter_entry(filter).filter_map(|entry| entry.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if let Ok(metadata) = entry.metadata() {
+            snapshot.insert(entry.path().to_owned(), (metadata.len(), metadata.modified().ok()));
+        }
+    }
+}
+
+/// Blocks until the snapshot taken by `take_snapshot` differs from `last`, polling every `interval`. To avoid reacting
+/// to a batch of files being written halfway, it then waits until the snapshot stays unchanged for one more `interval`.
+///
+/// Returns the new snapshot.
+pub fn wait_for_change<F: FnMut() -> Snapshot>(last: &Snapshot, interval: Duration, mut take_snapshot: F) -> Snapshot {
+    let mut current = loop {
+        sleep(interval);
+        let current = take_snapshot();
+        if current != *last {
+            break current;
+        }
+    };
+    loop {
+        sleep(interval);
+        let next = take_snapshot();
+        if next == current {
+            return current;
+        }
+        current = next;
+    }
+}
@@ -0,0 +1,94 @@
+//! Continuous testing with coverage.
+//!
+//! `cargo cov watch` runs `cargo cov test` and regenerates the report whenever a source file of the workspace is
+//! changed. The Rust sources and `Cargo.toml` of the workspace and its packages are polled for changes, skipping the
+//! `target/` and hidden folders.
+//!
+//! Only the profile data is cleaned between two runs, so unchanged crates are not rebuilt. After every run, the total
+//! coverage is printed together with the change since the previous run:
+//!
+//! ```text
+//!     Coverage lines 85.00% (+2.50), branches 60.00%, functions 90.00% (+5.00)
+//! ```
+//!
+//! The report is still regenerated when some tests failed, as long as profile data has been written. If nothing was
+//! written, e.g. due to a compile error, the previous report is kept. The runs are not recorded in the coverage
+//! history, so the first run is printed without a change.
+
+use argparse::ReportConfig;
+use cargo::{Cargo, CleanTargets};
+use error::Result;
+use history;
+use report;
+use ui;
+use utils::{Snapshot, snapshot_files, wait_for_change};
+
+use cov::report::FileSummary;
+use walkdir::DirEntry;
+
+use std::ffi::OsStr;
+use std::time::Duration;
+
+/// Interval between two checks of the source files.
+const POLL_INTERVAL: u64 = 500;
+
+/// Entry point of `cargo cov watch` subcommand. Tests the workspace and regenerates the report, and then repeats after
+/// every change of the sources until the program is interrupted.
+///
+/// Errors of every run are printed, and the watch continues.
+pub fn watch(cargo: &mut Cargo, config: &ReportConfig) -> Result<()> {
+    let mut roots = vec![config.workspace_path.to_path_buf()];
+    roots.extend(config.packages.iter().map(|p| p.root.clone()).filter(|root| !root.starts_with(&config.workspace_path)));
+
+    let take_snapshot = || {
+        let mut snapshot = Snapshot::new();
+        for root in &roots {
+            snapshot_files(&mut snapshot, root, is_watched_source);
+        }
+        snapshot
+    };
+
+    let mut previous = None;
+    let mut snapshot = take_snapshot();
+    loop {
+        match run_once(cargo, config) {
+            Ok(Some(total)) => {
+                progress!("Coverage", "{}", history::format_summary(&total, previous.as_ref()));
+                previous = Some(total);
+            },
+            Ok(None) => warning!("no profile data written, the report is not regenerated"),
+            Err(e) => ui::print_error(&e)?,
+        }
+        progress!("Watching", "{} source files for changes", snapshot.len());
+        snapshot = wait_for_change(&snapshot, Duration::from_millis(POLL_INTERVAL), &take_snapshot);
+    }
+}
+
+/// Cleans the profile data, runs the tests and regenerates the report. Returns the total coverage of the regenerated
+/// report, or `None` if no profile data has been written.
+fn run_once(cargo: &mut Cargo, config: &ReportConfig) -> Result<Option<FileSummary>> {
+    cargo.clean(CleanTargets::BUILD_GCDA)?;
+    if let Err(e) = cargo.forward("test") {
+        ui::print_error(&e)?;
+    }
+
+    let mut profile_data = Snapshot::new();
+    snapshot_files(&mut profile_data, &config.gcda_path, |_| true);
+    if profile_data.is_empty() {
+        return Ok(None);
+    }
+
+    let (_, history_entry) = report::generate(config)?;
+    Ok(Some(history_entry.total()))
+}
+
+/// Whether the entry is a folder to descend into, or a file whose change triggers another run.
+fn is_watched_source(entry: &DirEntry) -> bool {
+    let name = entry.file_name();
+    if entry.file_type().is_dir() {
+        let name = name.to_string_lossy();
+        entry.depth() == 0 || !(name == "target" || name.starts_with('.'))
+    } else {
+        name == "Cargo.toml" || entry.path().extension() == Some(OsStr::new("rs"))
+    }
+}
@@ -0,0 +1,293 @@
+//! Structured export of the control-flow graphs.
+//!
+//! The [`FunctionCfg`] lists the blocks and arcs of a function together with their source lines, counts and attributes.
+//! It is obtained from [`Graph::control_flow_graphs()`], and can be serialized to JSON (via [`SerializeWithInterner`])
+//! or drawn as a [Mermaid](https://mermaid.js.org/) flowchart with [`write_mermaid()`].
+//!
+//! [`FunctionCfg`]: ./struct.FunctionCfg.html
+//! [`Graph::control_flow_graphs()`]: ../graph/struct.Graph.html#method.control_flow_graphs
+//! [`SerializeWithInterner`]: ../intern/trait.SerializeWithInterner.html
+//! [`write_mermaid()`]: ./fn.write_mermaid.html
+
+#[cfg(feature = "serde")]
+use intern::SerializeWithInterner;
+use intern::{Interner, Symbol};
+use raw::{ArcAttr, BlockAttr};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+
+use std::io;
+
+/// Selects the functions to export from a [`Graph`]. The default filter selects every function.
+///
+/// [`Graph`]: ../graph/struct.Graph.html
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct CfgFilter {
+    /// Only selects the functions defined in these files. Every file is allowed if empty.
+    pub filenames: Vec<Symbol>,
+
+    /// Only selects the functions having a block within this range of lines (both inclusive).
+    pub lines: Option<(u32, u32)>,
+
+    /// Only selects the functions whose demangled name matches this pattern, where `*` matches any sequence of
+    /// characters.
+    pub name_pattern: Option<String>,
+}
+
+impl CfgFilter {
+    /// Checks whether the function defined in `filename` with the demangled `name` and the source `lines` is selected.
+    pub fn matches<I: IntoIterator<Item = u32>>(&self, filename: Symbol, name: &str, lines: I) -> bool {
+        if !self.filenames.is_empty() && !self.filenames.contains(&filename) {
+            return false;
+        }
+        if let Some(ref pattern) = self.name_pattern {
+            if !matches_wildcard(pattern, name) {
+                return false;
+            }
+        }
+        match self.lines {
+            Some((start, end)) => lines.into_iter().any(|line| start <= line && line <= end),
+            None => true,
+        }
+    }
+}
+
+/// Checks whether `text` matches the `pattern` entirely, where `*` in the pattern matches any sequence of characters.
+fn matches_wildcard(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !text.starts_with(first) {
+        return false;
+    }
+    let mut rest = &text[first.len()..];
+    let mut parts = parts.collect::<Vec<_>>();
+    let last = match parts.pop() {
+        Some(last) => last,
+        None => return rest.is_empty(),
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[(index + part.len())..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[test]
+fn test_matches_wildcard() {
+    assert!(matches_wildcard("x::f", "x::f"));
+    assert!(!matches_wildcard("x::f", "x::f::g"));
+    assert!(matches_wildcard("x::*", "x::f::g"));
+    assert!(matches_wildcard("*::parse*", "x::Parser::parse_args"));
+    assert!(matches_wildcard("*", ""));
+    assert!(matches_wildcard("a*b*a", "aba"));
+    assert!(!matches_wildcard("a*b*a", "ab"));
+    assert!(!matches_wildcard("*::f", "x::g"));
+}
+
+/// The role of a block in the control-flow graph.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum CfgBlockKind {
+    /// The entry block of the function.
+    Entry,
+    /// The exit block of the function.
+    Exit,
+    /// Any other block.
+    Normal,
+}
+
+derive_serialize_with_interner! {
+    /// The control-flow graph of a function.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    #[cfg_attr(feature="serde", derive(Serialize, Deserialize))]
+    pub struct FunctionCfg {
+        /// The (mangled) function name.
+        pub name: Symbol,
+
+        /// The demangled function name.
+        pub demangled_name: String,
+
+        /// The file where the function is defined.
+        pub filename: Symbol,
+
+        /// The line number where the function is defined.
+        pub line: u32,
+
+        /// The blocks of the function, ordered by the block number.
+        pub blocks: Vec<CfgBlock>,
+
+        /// The arcs between the blocks.
+        pub arcs: Vec<CfgArc>,
+    }
+}
+
+derive_serialize_with_interner! {
+    /// A basic block in a [`FunctionCfg`].
+    ///
+    /// [`FunctionCfg`]: ./struct.FunctionCfg.html
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    #[cfg_attr(feature="serde", derive(Serialize, Deserialize))]
+    pub struct CfgBlock {
+        /// The block number within the function.
+        pub block: usize,
+
+        /// Whether this is the entry or exit block.
+        pub kind: CfgBlockKind,
+
+        /// Number of times the block has been executed. `None` if unknown.
+        pub count: Option<u64>,
+
+        /// Attributes of the block.
+        pub attr: BlockAttr,
+
+        /// The file of the source lines, or `UNKNOWN_SYMBOL` if the block has no lines.
+        pub filename: Symbol,
+
+        /// The source lines of the block.
+        pub lines: Vec<u32>,
+    }
+}
+
+derive_serialize_with_interner! {
+    /// An arc in a [`FunctionCfg`].
+    ///
+    /// [`FunctionCfg`]: ./struct.FunctionCfg.html
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    #[cfg_attr(feature="serde", derive(Serialize, Deserialize))]
+    pub struct CfgArc {
+        /// The block number of the source.
+        pub source: usize,
+
+        /// The block number of the target.
+        pub target: usize,
+
+        /// Number of times the arc has been taken. `None` if unknown.
+        pub count: Option<u64>,
+
+        /// Attributes of the arc.
+        pub attr: ArcAttr,
+    }
+}
+
+derive_serialize_with_interner! {
+    direct: CfgBlockKind
+}
+
+impl FunctionCfg {
+    /// Checks whether the arc has never been taken, although its source block has been executed, i.e. the arc is the
+    /// decision which would have led to the uncovered code.
+    pub fn is_uncovered_arc(&self, arc: &CfgArc) -> bool {
+        arc.count == Some(0) && self.blocks.iter().any(|b| b.block == arc.source && b.count > Some(0)) && !arc.attr.contains(ArcAttr::FAKE)
+    }
+}
+
+/// Draws the control-flow graphs as a Mermaid flowchart, one subgraph per function.
+///
+/// Every block is labeled by its source lines and count. Blocks never executed are filled in red, and the
+/// [uncovered arcs] are drawn in thick red lines. Fake arcs (e.g. unwinding) are dotted.
+///
+/// [uncovered arcs]: ./struct.FunctionCfg.html#method.is_uncovered_arc
+pub fn write_mermaid<W: io::Write>(cfgs: &[FunctionCfg], interner: &Interner, mut writer: W) -> io::Result<()> {
+    fn count_label(count: Option<u64>) -> String {
+        count.map_or_else(|| "?".to_owned(), |c| c.to_string())
+    }
+
+    writeln!(writer, "flowchart TD")?;
+    writeln!(writer, "    classDef zero fill:#fbb,stroke:#c00")?;
+    let mut link_index = 0;
+    let mut uncovered_links = Vec::new();
+    for (i, cfg) in cfgs.iter().enumerate() {
+        let name = cfg.demangled_name.replace('"', "#quot;");
+        writeln!(writer, "    subgraph f{} [\"{}\"]", i, name)?;
+        for block in &cfg.blocks {
+            let location = match block.kind {
+                CfgBlockKind::Entry => "ENTRY".to_owned(),
+                CfgBlockKind::Exit => "EXIT".to_owned(),
+                CfgBlockKind::Normal if block.lines.is_empty() => format!("@{}", block.block),
+                CfgBlockKind::Normal => {
+                    let lines = block.lines.iter().map(|l| l.to_string()).collect::<Vec<_>>().join(",");
+                    if block.filename == cfg.filename {
+                        format!("L{}", lines)
+                    } else {
+                        format!("{}:{}", interner[block.filename].replace('"', "#quot;"), lines)
+                    }
+                },
+            };
+            let class = if block.count == Some(0) { ":::zero" } else { "" };
+            writeln!(writer, "        f{}b{}[\"{} ×{}\"]{}", i, block.block, location, count_label(block.count), class)?;
+        }
+        for arc in &cfg.arcs {
+            let link = if arc.attr.contains(ArcAttr::FAKE) { "-.->" } else { "-->" };
+            writeln!(writer, "        f{}b{} {}|{}| f{}b{}", i, arc.source, link, count_label(arc.count), i, arc.target)?;
+            if cfg.is_uncovered_arc(arc) {
+                uncovered_links.push(link_index);
+            }
+            link_index += 1;
+        }
+        writeln!(writer, "    end")?;
+    }
+    if !uncovered_links.is_empty() {
+        let links = uncovered_links.iter().map(|l| l.to_string()).collect::<Vec<_>>().join(",");
+        writeln!(writer, "    linkStyle {} stroke:#c00,stroke-width:3px", links)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_write_mermaid() {
+    use intern::UNKNOWN_SYMBOL;
+
+    let mut interner = Interner::new();
+    let filename = interner.intern("x.rs");
+    let block = |block, kind, count, lines: &[u32]| CfgBlock {
+        block,
+        kind,
+        count: Some(count),
+        attr: BlockAttr::empty(),
+        filename: if lines.is_empty() { UNKNOWN_SYMBOL } else { filename },
+        lines: lines.to_vec(),
+    };
+    let arc = |source, target, count| CfgArc {
+        source,
+        target,
+        count: Some(count),
+        attr: ArcAttr::empty(),
+    };
+    let cfg = FunctionCfg {
+        name: interner.intern("_ZN1x1fE"),
+        demangled_name: "x::f".to_owned(),
+        filename,
+        line: 1,
+        blocks: vec![
+            block(0, CfgBlockKind::Entry, 5, &[]),
+            block(1, CfgBlockKind::Exit, 5, &[]),
+            block(2, CfgBlockKind::Normal, 5, &[1, 2]),
+            block(3, CfgBlockKind::Normal, 0, &[3]),
+        ],
+        arcs: vec![arc(0, 2, 5), arc(2, 1, 5), arc(2, 3, 0), arc(3, 1, 0)],
+    };
+
+    let mut output = Vec::new();
+    write_mermaid(&[cfg], &interner, &mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "flowchart TD
+    classDef zero fill:#fbb,stroke:#c00
+    subgraph f0 [\"x::f\"]
+        f0b0[\"ENTRY ×5\"]
+        f0b1[\"EXIT ×5\"]
+        f0b2[\"L1,2 ×5\"]
+        f0b3[\"L3 ×0\"]:::zero
+        f0b0 -->|5| f0b2
+        f0b2 -->|5| f0b1
+        f0b2 -->|0| f0b3
+        f0b3 -->|0| f0b1
+    end
+    linkStyle 2 stroke:#c00,stroke-width:3px
+"
+    );
+}
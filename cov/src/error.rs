@@ -79,6 +79,30 @@ error_chain! {
             description("counts mismatch")
             display("{0} counts mismatch on *.{3}, expecting {1} {0}, received {2} {0}", kind, expected, actual, ty)
         }
+
+        /// The raw profile (`*.profraw`) is created by an LLVM version not recognized by the `cov` crate.
+        UnsupportedProfrawVersion(version: u64) {
+            description("unsupported raw profile version")
+            display("unsupported raw profile version {}", version)
+        }
+
+        /// The coverage mapping embedded in an object file is created by an LLVM version not recognized by the `cov`
+        /// crate.
+        UnsupportedCovmapVersion(version: u32) {
+            description("unsupported coverage mapping version")
+            display("unsupported coverage mapping version {}", version)
+        }
+
+        /// The raw profile or coverage mapping is truncated or corrupted.
+        MalformedLlvmData(reason: &'static str) {
+            description("malformed LLVM coverage data")
+            display("malformed LLVM coverage data: {}", reason)
+        }
+
+        /// Trying to read coverage mapping from a file which is not an ELF, Mach-O or PE/COFF object file.
+        UnknownObjectFormat {
+            description("unknown object file format")
+        }
     }
 }
 
@@ -1,20 +1,23 @@
 //! Combine the raw coverage statistics into a single control-flow graph, and perform analysis to obtain a coverage
 //! report.
 
+use cfg::{CfgArc, CfgBlock, CfgBlockKind, CfgFilter, FunctionCfg};
 use error::*;
-use intern::{Symbol, UNKNOWN_SYMBOL};
+use intern::{Interner, Symbol, UNKNOWN_SYMBOL};
 use raw::*;
 use report::{self, Report};
 use utils::*;
 
 use fixedbitset::FixedBitSet;
+use rustc_demangle::demangle;
 use petgraph::Direction;
+use petgraph::algo::dominators::simple_fast;
 use petgraph::graph::{DiGraph, EdgeIndex, EdgeReference, NodeIndex};
 use petgraph::visit::{Dfs, EdgeFiltered, EdgeRef, IntoNodeReferences};
 
 use std::{cmp, io, mem, usize};
 use std::borrow::Cow;
-use std::collections::{BTreeMap, Bound, HashSet};
+use std::collections::{BTreeMap, Bound, HashSet, VecDeque};
 use std::collections::hash_map::{Entry, HashMap};
 use std::ops::{Index, IndexMut};
 
@@ -31,6 +34,57 @@ pub struct Graph {
     graph: DiGraph<BlockInfo, ArcInfo>,
 }
 
+/// Options controlling how a [`Graph`] is turned into a [`Report`].
+///
+/// [`Graph`]: ./struct.Graph.html
+/// [`Report`]: ../report/struct.Report.html
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct ReportOptions {
+    /// Leaves the panic and unwind paths out of the line, block and branch statistics.
+    ///
+    /// A block is on a panic path if it is [exceptional], i.e. only reachable through arcs [throwing] or [not returning]
+    /// (e.g. the landing pads dropping the locals while unwinding), or if it can only end in such an arc instead of
+    /// reaching the function exit (e.g. the call to `core::panicking::panic` after an overflow check or an `unwrap`).
+    /// Such arcs, and arcs entering or leaving such blocks, are counted as [`panic_paths_count`] instead of branches.
+    /// The loops whose header is on a panic path are not reported either. Blocks which merely never reach the function
+    /// exit, e.g. an infinite `loop {}`, are still counted as usual.
+    ///
+    /// [exceptional]: ../raw/struct.BlockAttr.html#associatedconstant.EXCEPTIONAL
+    /// [throwing]: ../raw/struct.ArcAttr.html#associatedconstant.THROW
+    /// [not returning]: ../raw/struct.ArcAttr.html#associatedconstant.CALL_NON_RETURN
+    /// [`panic_paths_count`]: ../report/struct.FunctionSummary.html#structfield.panic_paths_count
+    pub exclude_panic_paths: bool,
+}
+
+/// Explains why a source line has never been executed, returned from [`Graph::uncovered_paths()`].
+///
+/// The line belongs to an unexecuted block, which could only be reached through an arc never taken from an executed
+/// block (the branch), possibly followed by other unexecuted blocks.
+///
+/// [`Graph::uncovered_paths()`]: ./struct.Graph.html#method.uncovered_paths
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct UncoveredPath {
+    /// Filename of the unexecuted line.
+    pub filename: Symbol,
+    /// Line number of the unexecuted line.
+    pub line: u32,
+    /// Filename of the branch leading to the line.
+    pub branch_filename: Symbol,
+    /// Line number of the branch leading to the line, i.e. the last line of the executed block.
+    pub branch_line: u32,
+    /// The direction of the branch which has never been followed.
+    pub direction: BranchDirection,
+}
+
+/// The direction of a conditional branch.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum BranchDirection {
+    /// The branch jumps to another block.
+    Taken,
+    /// The branch is not taken, and falls through to the next block.
+    NotTaken,
+}
+
 impl Graph {
     /// Creates a new graph.
     pub fn new() -> Graph {
@@ -50,7 +104,19 @@ impl Graph {
     /// [`DuplicatedFunction`]: ../error/enum.ErrorKind.html#variant.DuplicatedFunction
     /// [`MissingFunction`]: ../error/enum.ErrorKind.html#variant.MissingFunction
     /// [`CountsMismatch`]: ../error/enum.ErrorKind.html#variant.MissingFunction
-    pub fn merge(&mut self, mut gcov: Gcov) -> Result<()> {
+    pub fn merge(&mut self, gcov: Gcov) -> Result<()> {
+        self.merge_with_configuration(gcov, UNKNOWN_SYMBOL)
+    }
+
+    /// Merges a parsed GCNO/GCDA built under the given configuration (e.g. a feature set or a target triple) into the
+    /// graph. Functions are only shared between files of the same configuration, so the GCDA of a configuration is
+    /// never matched against the GCNO of another one, and the report can tell which configurations have executed every
+    /// line. Use `UNKNOWN_SYMBOL` for untagged files.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`merge()`](#method.merge).
+    pub fn merge_with_configuration(&mut self, mut gcov: Gcov, configuration: Symbol) -> Result<()> {
         let source_location = match gcov.src.take() {
             Some(path) => Location::File(path),
             None => Location::None,
@@ -61,8 +127,8 @@ impl Graph {
                 v => ensure!(v == gcov.version, ErrorKind::VersionMismatch(v, gcov.version)),
             }
             match gcov.ty {
-                Type::Gcno => self.merge_gcno(gcov),
-                Type::Gcda => self.merge_gcda(gcov),
+                Type::Gcno => self.merge_gcno(gcov, configuration),
+                Type::Gcda => self.merge_gcda(gcov, configuration),
             }
         })
     }
@@ -74,7 +140,7 @@ impl Graph {
     /// * Returns [`DuplicatedFunction`] if the same function is merged twice.
     ///
     /// [`DuplicatedFunction`]: ../error/enum.ErrorKind.html#variant.DuplicatedFunction
-    fn merge_gcno(&mut self, gcno: Gcov) -> Result<()> {
+    fn merge_gcno(&mut self, gcno: Gcov, configuration: Symbol) -> Result<()> {
         let checksum = gcno.stamp;
 
         let mut fis = Vec::new();
@@ -90,7 +156,7 @@ impl Graph {
             }
 
             match record {
-                Record::Function(ident, function) => fis.push((ident, GcnoFunctionIdentity::new(function))),
+                Record::Function(ident, function) => fis.push((ident, GcnoFunctionIdentity::new(function, configuration))),
                 Record::Blocks(blocks) => last_fi!().blocks = blocks,
                 Record::Arcs(arcs) => last_fi!().arcs.push(arcs),
                 Record::Lines(lines) => last_fi!().lines.push(lines),
@@ -102,7 +168,7 @@ impl Graph {
         let mut gcno_index = mem::replace(&mut self.gcno_index, HashMap::new());
         // ^ move the GCNO index out temporarily, so that we can mutate `self` in the loop.
         for (ident, fi) in fis {
-            let gcda_identity = GcdaFunctionIdentity::new(checksum, ident, &fi.function);
+            let gcda_identity = GcdaFunctionIdentity::new(checksum, ident, &fi.function, configuration);
             match gcno_index.entry(fi) {
                 // Existing entry: Just add a GCDA index.
                 Entry::Occupied(entry) => {
@@ -131,13 +197,13 @@ impl Graph {
     ///
     /// [`MissingFunction`]: ../error/enum.ErrorKind.html#variant.MissingFunction
     /// [`CountsMismatch`]: ../error/enum.ErrorKind.html#variant.CountsMismatch
-    fn merge_gcda(&mut self, gcda: Gcov) -> Result<()> {
+    fn merge_gcda(&mut self, gcda: Gcov, configuration: Symbol) -> Result<()> {
         let mut cur = INVALID_FUNCTION_INDEX;
         let checksum = gcda.stamp;
 
         for (index, record) in gcda.records.into_iter().enumerate() {
             match record {
-                Record::Function(ident, function) => cur = Location::RecordIndex(index).wrap(|| self.find_function(checksum, ident, function))?,
+                Record::Function(ident, function) => cur = Location::RecordIndex(index).wrap(|| self.find_function(checksum, ident, function, configuration))?,
                 Record::ArcCounts(ac) => self.add_arc_counts(cur, ac)?,
                 Record::Summary(_) => {},
                 _ => trace!("gcda-unknown-record: {:?}", record),
@@ -167,14 +233,34 @@ impl Graph {
 
     /// Obtains a coverage report from the graph.
     pub fn report(&self) -> Report {
+        self.report_with_options(ReportOptions::default())
+    }
+
+    /// Obtains a coverage report from the graph, with extra options for the analysis.
+    pub fn report_with_options(&self, options: ReportOptions) -> Report {
         let mut r = Report::default();
 
+        let panic_blocks = if options.exclude_panic_paths {
+            self.find_panic_blocks()
+        } else {
+            FixedBitSet::with_capacity(0)
+        };
+        let is_panic_arc = |er: EdgeReference<ArcInfo>| {
+            options.exclude_panic_paths
+                && (er.weight().attr.intersects(ArcAttr::THROW | ArcAttr::CALL_NON_RETURN) || panic_blocks[er.source().index()]
+                    || panic_blocks[er.target().index()])
+        };
+
         for function in &self.functions {
-            self.report_function(function, &mut r);
+            self.report_function(function, &panic_blocks, &is_panic_arc, &mut r);
         }
 
+        let mut line_counts = LineCounts::new();
         for (src, block) in self.graph.node_references() {
-            if let Some(last_line) = self.report_block(block, &mut r) {
+            if panic_blocks[src.index()] {
+                continue;
+            }
+            if let Some(last_line) = self.report_block(block, &mut line_counts, &mut r) {
                 let function = &self[block.index];
                 let exit_block = function.exit_block(self.version);
                 // BTreeMap does not have IndexMut: See https://github.com/rust-lang/rust/issues/32170
@@ -182,7 +268,7 @@ impl Graph {
                 let branches = &mut file.lines.get_mut(&last_line.1).unwrap().branches;
                 for edge_ref in self.graph.edges(src) {
                     // ignore zero arcs leading to exit block.
-                    if edge_ref.target() == exit_block && edge_ref.weight().count == Some(0) {
+                    if edge_ref.target() == exit_block && edge_ref.weight().count == Some(0) || is_panic_arc(edge_ref) {
                         continue;
                     }
                     let branch = self.report_arc(edge_ref);
@@ -191,22 +277,138 @@ impl Graph {
             }
         }
 
+        for ((filename, line_number), counts) in line_counts {
+            let line = r.files.get_mut(&filename).unwrap().lines.get_mut(&line_number).unwrap();
+            line.count = counts.values().sum();
+            line.configurations = counts.into_iter().filter(|&(c, count)| c != UNKNOWN_SYMBOL && count > 0).map(|(c, _)| c).collect();
+        }
+
+        for function in &self.functions {
+            self.report_loops(function, &panic_blocks, &mut r);
+        }
+
         r
     }
 
+    /// Clears the counts of every block and arc, as if no GCDA has been merged.
+    ///
+    /// Merging the GCDA of a single run and [analyzing](#method.analyze) the graph again then finds what that run has
+    /// [executed](#method.executed), without parsing the GCNO into another graph.
+    pub fn reset_counts(&mut self) {
+        for block in self.graph.node_weights_mut() {
+            block.count = None;
+        }
+        for arc in self.graph.edge_weights_mut() {
+            arc.count = if arc.attr.contains(ArcAttr::ON_TREE) { None } else { Some(0) };
+        }
+    }
+
+    /// Lists the lines and functions executed according to the counts of the graph. This is much cheaper than creating
+    /// a whole [report](#method.report).
+    pub fn executed(&self) -> report::Executed {
+        let mut executed = report::Executed::default();
+        for (_, block) in self.graph.node_references().filter(|&(_, block)| block.count > Some(0)) {
+            executed.lines.extend(block.iter_lines());
+        }
+        for function in self.functions.iter().filter(|f| self.graph[f.entry_block()].count > Some(0)) {
+            let source = function.source.unwrap_or_default();
+            let configuration = if function.configuration == UNKNOWN_SYMBOL { None } else { Some(function.configuration) };
+            executed.functions.insert((source.filename, source.name, source.line, configuration));
+        }
+        executed
+    }
+
+    /// Explains every line of the unexecuted blocks, by the nearest executed block and its branch which has never been
+    /// followed. The result is sorted by the filename and line number of the unexecuted lines, and every line appears
+    /// at most once.
+    ///
+    /// The blocks are searched breadth-first, starting from the arcs from executed blocks to unexecuted ones, so the
+    /// path through the fewest unexecuted blocks is chosen. The blocks of functions never called are not reachable from
+    /// any executed block, and thus left unexplained.
+    pub fn uncovered_paths(&self) -> Vec<UncoveredPath> {
+        let is_executed = |ni: NodeIndex| self.graph[ni].count > Some(0);
+        let is_real_edge = |er: &EdgeReference<ArcInfo>| !er.weight().attr.contains(ArcAttr::FAKE);
+
+        let mut branches = HashMap::new();
+        let mut queue = VecDeque::new();
+        for er in self.graph.edge_references().filter(&is_real_edge) {
+            if is_executed(er.source()) && !is_executed(er.target()) {
+                if let Entry::Vacant(e) = branches.entry(er.target()) {
+                    e.insert(er.id());
+                    queue.push_back(er.target());
+                }
+            }
+        }
+        while let Some(ni) = queue.pop_front() {
+            let branch = branches[&ni];
+            for er in self.graph.edges(ni).filter(&is_real_edge) {
+                if !is_executed(er.target()) {
+                    if let Entry::Vacant(e) = branches.entry(er.target()) {
+                        e.insert(branch);
+                        queue.push_back(er.target());
+                    }
+                }
+            }
+        }
+
+        let mut paths = Vec::new();
+        for (ni, branch) in branches {
+            let (src, _) = self.graph.edge_endpoints(branch).unwrap();
+            let (branch_filename, branch_line) = match self.graph[src].iter_lines().last() {
+                Some(last_line) => last_line,
+                None => continue,
+            };
+            let direction = if self.graph[branch].attr.contains(ArcAttr::FALLTHROUGH) {
+                BranchDirection::NotTaken
+            } else {
+                BranchDirection::Taken
+            };
+            paths.extend(self.graph[ni].iter_lines().map(|(filename, line)| UncoveredPath {
+                filename,
+                line,
+                branch_filename,
+                branch_line,
+                direction,
+            }));
+        }
+        paths.sort();
+        paths.dedup_by_key(|path| (path.filename, path.line));
+        paths
+    }
+
     /// Populates the report with information about a function.
-    fn report_function(&self, function: &FunctionInfo, r: &mut Report) {
+    fn report_function<F>(&self, function: &FunctionInfo, panic_blocks: &FixedBitSet, is_panic_arc: F, r: &mut Report)
+    where
+        F: Fn(EdgeReference<ArcInfo>) -> bool,
+    {
         let source = function.source.unwrap_or_default();
         let entry_block = function.entry_block();
         let exit_block = function.exit_block(self.version);
 
-        let blocks_count = function.nodes.len();
-        let blocks_executed = function.nodes.iter().filter(|ni| self.graph[**ni].count > Some(0)).count();
+        let blocks = function.nodes.iter().filter(|ni| !panic_blocks[ni.index()]).collect::<Vec<_>>();
+        let blocks_count = blocks.len();
+        let blocks_executed = blocks.iter().filter(|ni| self.graph[***ni].count > Some(0)).count();
+        let block_executions = blocks.iter().map(|ni| self.graph[**ni].count.unwrap_or(0)).sum::<u64>();
+
+        // Blocks without any real successors (e.g. ending with a call to `panic`) are treated as having an arc to the
+        // exit block, so that every early exit adds an independent path.
+        let real_arcs_count = blocks
+            .iter()
+            .map(|ni| {
+                let arcs = self.graph.edges(**ni).filter(|er| !er.weight().attr.contains(ArcAttr::FAKE) && !is_panic_arc(*er)).count();
+                if arcs == 0 && **ni != exit_block {
+                    1
+                } else {
+                    arcs
+                }
+            })
+            .sum::<usize>();
+        let complexity = cmp::max((real_arcs_count + 2).saturating_sub(blocks_count), 1);
 
         // Do not use `function.arcs` here, the number of arcs will be under-estimating for gcc7
         // since non-fall-through arcs are not instrumented.
 
-        let (branches_count, branches_executed, branches_taken) = function
+        let (branches_count, branches_executed, branches_taken, panic_paths_count, panic_paths_taken) = function
             .nodes
             .iter()
             .flat_map(|ni| self.graph.edges(*ni))
@@ -215,12 +417,15 @@ impl Graph {
                 if arc.attr.intersects(ArcAttr::UNCONDITIONAL | ArcAttr::FAKE) {
                     return None;
                 }
-                let arc_taken = arc.count > Some(0);
+                let arc_taken = (arc.count > Some(0)) as usize;
+                if is_panic_arc(er) {
+                    return Some((0, 0, 0, 1, arc_taken));
+                }
                 let src = er.source();
                 let src_executed = self.graph[src].count > Some(0);
-                Some((1, src_executed as usize, arc_taken as usize))
+                Some((1, src_executed as usize, arc_taken, 0, 0))
             })
-            .fold((0, 0, 0), tuple_3_add);
+            .fold((0, 0, 0, 0, 0), tuple_5_add);
 
         let entry_count = self.graph[entry_block].count.unwrap_or(0);
         let mut exit_count = self.graph[exit_block].count.unwrap_or(0);
@@ -238,6 +443,8 @@ impl Graph {
 
         let report_function = report::Function {
             name: source.name,
+            demangled_name: String::new(),
+            generic_name: String::new(),
             line: source.line,
             column: 0,
             summary: report::FunctionSummary {
@@ -248,20 +455,133 @@ impl Graph {
                 branches_count,
                 branches_executed,
                 branches_taken,
+                panic_paths_count,
+                panic_paths_taken,
+                complexity,
+                block_executions,
             },
+            covered_by: Vec::new(),
+            configuration: if function.configuration == UNKNOWN_SYMBOL { None } else { Some(function.configuration) },
         };
         r.files.entry(source.filename).or_default_().functions.push(report_function);
     }
 
+    /// Finds the blocks on panic paths, which are
+    ///
+    /// * the blocks marked [exceptional] by [`mark_exceptional_blocks()`], which are only reachable through arcs throwing
+    ///   or not returning, e.g. the landing pads, and
+    /// * the blocks which cannot reach the exit block through real arcs, but can reach an arc throwing or not returning,
+    ///   e.g. the block calling `core::panicking::panic` after an overflow check or an `unwrap`.
+    ///
+    /// The entry and exit blocks are never included. Blocks reaching neither, e.g. an infinite `loop {}`, are not on
+    /// panic paths.
+    ///
+    /// [exceptional]: ../raw/struct.BlockAttr.html#associatedconstant.EXCEPTIONAL
+    /// [`mark_exceptional_blocks()`]: #method.mark_exceptional_blocks
+    fn find_panic_blocks(&self) -> FixedBitSet {
+        let exit_blocks = self.functions.iter().map(|function| function.exit_block(self.version));
+        let reaching_exit = self.find_blocks_reaching(exit_blocks);
+        let panic_sources = self.graph
+            .edge_references()
+            .filter(|er| er.weight().attr.intersects(ArcAttr::THROW | ArcAttr::CALL_NON_RETURN))
+            .map(|er| er.source());
+        let reaching_panic = self.find_blocks_reaching(panic_sources);
+
+        let mut panic_blocks = FixedBitSet::with_capacity(self.graph.node_count());
+        for function in &self.functions {
+            let exit_block = function.exit_block(self.version);
+            for &ni in &function.nodes {
+                let block = &self.graph[ni];
+                let i = ni.index();
+                let is_panic_block = block.attr.contains(BlockAttr::EXCEPTIONAL) || (!reaching_exit[i] && reaching_panic[i]);
+                if is_panic_block && !block.is_entry_block() && ni != exit_block {
+                    panic_blocks.insert(i);
+                }
+            }
+        }
+        panic_blocks
+    }
+
+    /// Finds the blocks which can reach any of the `targets` (including the targets themselves) through real arcs, i.e.
+    /// arcs which are neither fake nor throwing.
+    fn find_blocks_reaching<I: IntoIterator<Item = NodeIndex>>(&self, targets: I) -> FixedBitSet {
+        let mut reaching = FixedBitSet::with_capacity(self.graph.node_count());
+        let mut stack = targets.into_iter().collect::<Vec<_>>();
+        while let Some(ni) = stack.pop() {
+            if reaching.put(ni.index()) {
+                continue;
+            }
+            let sources = self.graph
+                .edges_directed(ni, Direction::Incoming)
+                .filter(|er| !er.weight().attr.intersects(ArcAttr::FAKE | ArcAttr::THROW))
+                .map(|er| er.source());
+            stack.extend(sources);
+        }
+        reaching
+    }
+
+    /// Populates the report with the statistics of the natural loops in a function.
+    ///
+    /// A loop is found from its back-edges, i.e. the arcs going to a block (the loop header) which dominates the
+    /// source. The other arcs going to the header enter the loop from outside.
+    fn report_loops(&self, function: &FunctionInfo, panic_blocks: &FixedBitSet, r: &mut Report) {
+        fn is_real_edge(er: EdgeReference<ArcInfo>) -> bool {
+            !er.weight().attr.contains(ArcAttr::FAKE)
+        }
+
+        // The dominators are computed on a copy of the function alone, indexed by the block number, since the traversal
+        // allocates a visit map as large as the graph it runs on.
+        let local = |ni: NodeIndex| NodeIndex::new(self.graph[ni].block);
+        let mut blocks = DiGraph::<(), ()>::with_capacity(function.nodes.len(), function.arcs.len());
+        for _ in &function.nodes {
+            blocks.add_node(());
+        }
+        for &ni in &function.nodes {
+            for er in self.graph.edges(ni).filter(|er| is_real_edge(*er)) {
+                blocks.add_edge(local(er.source()), local(er.target()), ());
+            }
+        }
+        let dominators = simple_fast(&blocks, local(function.entry_block()));
+
+        for &header in &function.nodes {
+            if panic_blocks[header.index()] {
+                continue;
+            }
+            let mut is_loop = false;
+            let mut stats = report::Loop::default();
+            for er in self.graph.edges_directed(header, Direction::Incoming).filter(|er| is_real_edge(*er)) {
+                let count = er.weight().count.unwrap_or(0);
+                let is_back_edge = dominators.dominators(local(er.source())).map_or(false, |mut doms| doms.any(|ni| ni == local(header)));
+                if is_back_edge {
+                    is_loop = true;
+                    stats.iterations += count;
+                } else {
+                    stats.entry_count += count;
+                }
+            }
+            if !is_loop {
+                continue;
+            }
+            if let Some((filename, line_number)) = self.graph[header].iter_lines().next() {
+                let line = r.files.entry(filename).or_default_().lines.entry(line_number).or_default_();
+                let total = line.loop_stats.get_or_insert_with(report::Loop::default);
+                total.entry_count += stats.entry_count;
+                total.iterations += stats.iterations;
+            }
+        }
+    }
+
     /// Populates the report with information about a block (source code lines).
-    fn report_block(&self, block: &BlockInfo, r: &mut Report) -> Option<(Symbol, u32)> {
+    fn report_block(&self, block: &BlockInfo, line_counts: &mut LineCounts, r: &mut Report) -> Option<(Symbol, u32)> {
         let block_count = block.count.unwrap_or(0);
+        let configuration = self[block.index].configuration;
 
         let mut last_line = None;
         for (filename, line_number) in block.iter_lines() {
             let file = r.files.entry(filename).or_default_();
             let line = file.lines.entry(line_number).or_default_();
-            line.count = cmp::max(line.count, block_count);
+            let count = line_counts.entry((filename, line_number)).or_default_().entry(configuration).or_insert(0);
+            *count = cmp::max(*count, block_count);
             line.attr |= block.attr;
             last_line = Some((filename, line_number));
         }
@@ -291,6 +611,301 @@ impl Graph {
     }
 }
 
+/// The execution count of every source line, keyed by the filename and line number, and then by the configuration.
+type LineCounts = HashMap<(Symbol, u32), BTreeMap<Symbol, u64>>;
+
+/// Creates the GCNO and GCDA of a single function in `filename` for testing. The `arcs` list the destination blocks and
+/// the flags of the arcs from every source block, the `lines` list the line number of the blocks, and the `counts` are
+/// the counts of every arc in order.
+#[cfg(test)]
+fn single_function_gcov(filename: Symbol, blocks_count: usize, arcs: &[(u32, Vec<(u32, ArcAttr)>)], lines: &[(u32, u32)], counts: Vec<u64>) -> (Gcov, Gcov) {
+    let source = Source {
+        name: filename,
+        filename,
+        line: 1,
+    };
+    let function = Function {
+        lineno_checksum: 1,
+        cfg_checksum: 2,
+        source: Some(source),
+    };
+
+    let mut records = vec![
+        Record::Function(Ident(0), function.clone()),
+        Record::Blocks(Blocks {
+            flags: vec![BlockAttr::empty(); blocks_count],
+        }),
+    ];
+    records.extend(arcs.iter().map(|&(src_block, ref arcs)| {
+        Record::Arcs(Arcs {
+            src_block: BlockIndex(src_block),
+            arcs: arcs.iter()
+                .map(|&(dest_block, flags)| Arc {
+                    dest_block: BlockIndex(dest_block),
+                    flags,
+                })
+                .collect(),
+        })
+    }));
+    records.extend(lines.iter().map(|&(block_number, line)| {
+        Record::Lines(Lines {
+            block_number: BlockIndex(block_number),
+            lines: vec![Line::FileName(filename), Line::LineNumber(line)],
+        })
+    }));
+    let gcno = Gcov {
+        ty: Type::Gcno,
+        version: VERSION_4_7,
+        stamp: 0,
+        records,
+        src: None,
+    };
+    let gcda = Gcov {
+        ty: Type::Gcda,
+        version: VERSION_4_7,
+        stamp: 0,
+        records: vec![
+            Record::Function(Ident(0), Function { source: None, ..function }),
+            Record::ArcCounts(ArcCounts { counts }),
+        ],
+        src: None,
+    };
+    (gcno, gcda)
+}
+
+/// Creates an analyzed graph of a single function for testing. See [`single_function_gcov()`] for the arguments.
+///
+/// [`single_function_gcov()`]: ./fn.single_function_gcov.html
+#[cfg(test)]
+fn single_function_graph(filename: Symbol, blocks_count: usize, arcs: &[(u32, Vec<(u32, ArcAttr)>)], lines: &[(u32, u32)], counts: Vec<u64>) -> Graph {
+    let (gcno, gcda) = single_function_gcov(filename, blocks_count, arcs, lines, counts);
+    let mut graph = Graph::new();
+    graph.merge(gcno).unwrap();
+    graph.merge(gcda).unwrap();
+    graph.analyze();
+    graph
+}
+
+#[test]
+fn test_exclude_panic_paths() {
+    use intern::Interner;
+
+    // fn f(x: Vec<u32>) -> u32 {   // block 2
+    //     g(&x)                    // block 2, returning to block 3 or unwinding to block 4
+    // }                            // block 3 drops x, or block 4 drops x and resumes unwinding
+    let mut interner = Interner::new();
+    let filename = interner.intern("x.rs");
+    let graph = single_function_graph(
+        filename,
+        5,
+        &[
+            (0, vec![(2, ArcAttr::FALLTHROUGH)]),
+            (2, vec![(3, ArcAttr::FALLTHROUGH), (4, ArcAttr::empty()), (1, ArcAttr::FAKE)]),
+            (3, vec![(1, ArcAttr::FALLTHROUGH)]),
+        ],
+        &[(2, 1), (3, 3), (4, 3)],
+        vec![5, 5, 0, 0, 5],
+    );
+
+    let summary = graph.report().files[&filename].functions[0].summary;
+    assert_eq!((summary.blocks_count, summary.blocks_executed), (5, 4));
+    assert_eq!((summary.branches_count, summary.branches_taken), (2, 1));
+    assert_eq!(summary.complexity, 2);
+    assert_eq!((summary.panic_paths_count, summary.panic_paths_taken), (0, 0));
+
+    let report = graph.report_with_options(ReportOptions { exclude_panic_paths: true });
+    let file = &report.files[&filename];
+    let summary = file.functions[0].summary;
+    assert_eq!((summary.blocks_count, summary.blocks_executed), (4, 4));
+    assert_eq!((summary.branches_count, summary.branches_taken), (1, 1));
+    assert_eq!(summary.complexity, 1);
+    assert_eq!((summary.panic_paths_count, summary.panic_paths_taken), (1, 0));
+    assert_eq!(file.lines[&1].branches.len(), 1);
+    assert_eq!(file.summary().panic_paths_count, 1);
+}
+
+#[test]
+fn test_exclude_panic_paths_overflow_check() {
+    use intern::Interner;
+
+    // fn f(x: u32) -> u32 {   // block 2
+    //     x + 1               // block 2, returning in block 3, or panicking on overflow in block 4
+    // }
+    let mut interner = Interner::new();
+    let filename = interner.intern("x.rs");
+    let graph = single_function_graph(
+        filename,
+        5,
+        &[
+            (0, vec![(2, ArcAttr::FALLTHROUGH)]),
+            (2, vec![(3, ArcAttr::FALLTHROUGH), (4, ArcAttr::empty())]),
+            (3, vec![(1, ArcAttr::FALLTHROUGH)]),
+            (4, vec![(1, ArcAttr::FAKE)]),
+        ],
+        &[(2, 2), (3, 3), (4, 2)],
+        vec![5, 5, 0, 5, 0],
+    );
+
+    let summary = graph.report().files[&filename].functions[0].summary;
+    assert_eq!((summary.blocks_count, summary.blocks_executed), (5, 4));
+    assert_eq!((summary.branches_count, summary.branches_taken), (2, 1));
+    assert_eq!(summary.complexity, 2);
+
+    let report = graph.report_with_options(ReportOptions { exclude_panic_paths: true });
+    let file = &report.files[&filename];
+    let summary = file.functions[0].summary;
+    assert_eq!((summary.blocks_count, summary.blocks_executed), (4, 4));
+    assert_eq!((summary.branches_count, summary.branches_taken), (1, 1));
+    assert_eq!(summary.complexity, 1);
+    assert_eq!((summary.panic_paths_count, summary.panic_paths_taken), (1, 0));
+    assert_eq!(file.lines[&2].count, 5);
+    assert_eq!(file.lines[&2].branches.len(), 1);
+}
+
+#[test]
+fn test_exclude_panic_paths_infinite_loop() {
+    use intern::Interner;
+
+    // fn f() -> ! {   // block 2
+    //     loop {}     // block 3, never reaching the exit block
+    // }
+    let mut interner = Interner::new();
+    let filename = interner.intern("x.rs");
+    let graph = single_function_graph(
+        filename,
+        4,
+        &[(0, vec![(2, ArcAttr::FALLTHROUGH)]), (2, vec![(3, ArcAttr::FALLTHROUGH)]), (3, vec![(3, ArcAttr::empty())])],
+        &[(2, 1), (3, 2)],
+        vec![1, 1, 100],
+    );
+
+    let report = graph.report_with_options(ReportOptions { exclude_panic_paths: true });
+    let file = &report.files[&filename];
+    let summary = file.functions[0].summary;
+    assert_eq!((summary.blocks_count, summary.blocks_executed), (4, 3));
+    assert_eq!((summary.panic_paths_count, summary.panic_paths_taken), (0, 0));
+    assert_eq!(file.lines[&2].count, 100);
+    let loop_stats = file.lines[&2].loop_stats.expect("loop stats");
+    assert_eq!((loop_stats.entry_count, loop_stats.iterations), (1, 100));
+}
+
+#[test]
+fn test_uncovered_paths() {
+    use intern::Interner;
+
+    // fn f(x: bool) {         // block 2
+    //     if x {
+    //         a();            // block 4
+    //         b();            // block 5
+    //     }                   // block 3
+    // }
+    let mut interner = Interner::new();
+    let filename = interner.intern("x.rs");
+    let graph = single_function_graph(
+        filename,
+        6,
+        &[
+            (0, vec![(2, ArcAttr::FALLTHROUGH)]),
+            (2, vec![(3, ArcAttr::empty()), (4, ArcAttr::FALLTHROUGH)]),
+            (3, vec![(1, ArcAttr::FALLTHROUGH)]),
+            (4, vec![(5, ArcAttr::FALLTHROUGH)]),
+            (5, vec![(3, ArcAttr::FALLTHROUGH)]),
+        ],
+        &[(2, 2), (3, 5), (4, 3), (5, 4)],
+        vec![5, 5, 0, 5, 0, 0],
+    );
+
+    let path = |line| UncoveredPath {
+        filename,
+        line,
+        branch_filename: filename,
+        branch_line: 2,
+        direction: BranchDirection::NotTaken,
+    };
+    assert_eq!(graph.uncovered_paths(), vec![path(3), path(4)]);
+}
+
+#[test]
+fn test_reset_counts() {
+    use intern::Interner;
+
+    // fn f(x: bool) {         // block 2
+    //     if x {
+    //         a();            // block 3
+    //     }                   // block 4
+    // }
+    let mut interner = Interner::new();
+    let filename = interner.intern("x.rs");
+    let arcs = [
+        (0, vec![(2, ArcAttr::FALLTHROUGH)]),
+        (2, vec![(4, ArcAttr::empty()), (3, ArcAttr::FALLTHROUGH)]),
+        (3, vec![(4, ArcAttr::FALLTHROUGH)]),
+        (4, vec![(1, ArcAttr::FALLTHROUGH)]),
+    ];
+    let lines = [(2, 2), (3, 3), (4, 4)];
+
+    let mut graph = single_function_graph(filename, 5, &arcs, &lines, vec![3, 1, 2, 2, 3]);
+    let executed = graph.executed();
+    assert_eq!(executed.lines, [(filename, 2), (filename, 3), (filename, 4)].iter().cloned().collect());
+    assert_eq!(executed.functions, [(filename, filename, 1, None)].iter().cloned().collect());
+
+    graph.reset_counts();
+    let (_, gcda) = single_function_gcov(filename, 5, &arcs, &lines, vec![1, 1, 0, 0, 1]);
+    graph.merge(gcda).unwrap();
+    graph.analyze();
+    assert_eq!(graph.executed().lines, [(filename, 2), (filename, 4)].iter().cloned().collect());
+    assert_eq!(graph.report().files[&filename].lines[&2].count, 1);
+
+    graph.reset_counts();
+    graph.analyze();
+    assert_eq!(graph.executed(), report::Executed::default());
+}
+
+#[test]
+fn test_merge_with_configuration() {
+    use intern::Interner;
+
+    // fn f(x: bool) {         // block 2
+    //     if x {
+    //         a();            // block 3
+    //     }                   // block 4
+    // }
+    let mut interner = Interner::new();
+    let filename = interner.intern("x.rs");
+    let feature_a = interner.intern("--features=a");
+    let feature_b = interner.intern("--features=b");
+    let arcs = [
+        (0, vec![(2, ArcAttr::FALLTHROUGH)]),
+        (2, vec![(4, ArcAttr::empty()), (3, ArcAttr::FALLTHROUGH)]),
+        (3, vec![(4, ArcAttr::FALLTHROUGH)]),
+        (4, vec![(1, ArcAttr::FALLTHROUGH)]),
+    ];
+    let lines = [(2, 2), (3, 3), (4, 4)];
+
+    // Both builds have the same structure and stamp, but must not be merged together.
+    let mut graph = Graph::new();
+    for &(configuration, ref counts) in &[(feature_a, vec![2, 2, 0, 0, 2]), (feature_b, vec![3, 1, 2, 2, 3])] {
+        let (gcno, gcda) = single_function_gcov(filename, 5, &arcs, &lines, counts.clone());
+        graph.merge_with_configuration(gcno, configuration).unwrap();
+        graph.merge_with_configuration(gcda, configuration).unwrap();
+    }
+    graph.analyze();
+    let report = graph.report();
+
+    let file = &report.files[&filename];
+    let configurations = file.functions.iter().map(|f| f.configuration).collect::<Vec<_>>();
+    assert_eq!(configurations, vec![Some(feature_a), Some(feature_b)]);
+    let lines = file.lines.iter().map(|(&ln, line)| (ln, line.count, line.configurations.clone())).collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        vec![
+            (2, 5, vec![feature_a, feature_b]),
+            (3, 2, vec![feature_b]),
+            (4, 5, vec![feature_a, feature_b]),
+        ]
+    );
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct FunctionIndex(usize);
@@ -318,15 +933,17 @@ struct GcdaFunctionIdentity {
     ident: Ident,
     lineno_checksum: u32,
     cfg_checksum: u32,
+    configuration: Symbol,
 }
 
 impl GcdaFunctionIdentity {
-    fn new(file_checksum: u32, ident: Ident, function: &Function) -> GcdaFunctionIdentity {
+    fn new(file_checksum: u32, ident: Ident, function: &Function, configuration: Symbol) -> GcdaFunctionIdentity {
         GcdaFunctionIdentity {
             file_checksum,
             ident,
             lineno_checksum: function.lineno_checksum,
             cfg_checksum: function.cfg_checksum,
+            configuration,
         }
     }
 }
@@ -339,15 +956,17 @@ struct GcnoFunctionIdentity {
     blocks: Blocks,
     arcs: Vec<Arcs>,
     lines: Vec<Lines>,
+    configuration: Symbol,
 }
 
 impl GcnoFunctionIdentity {
-    fn new(function: Function) -> GcnoFunctionIdentity {
+    fn new(function: Function, configuration: Symbol) -> GcnoFunctionIdentity {
         GcnoFunctionIdentity {
             function,
             blocks: Blocks { flags: Vec::new() },
             arcs: Vec::new(),
             lines: Vec::new(),
+            configuration,
         }
     }
 }
@@ -599,7 +1218,7 @@ impl Graph {
         let mut dfs = Dfs::empty(&EdgeFiltered(&self.graph, is_non_exc_edge));
         dfs.stack = stack;
 
-        while let Some(non_exc_ni) = dfs.next(&self.graph) {
+        while let Some(non_exc_ni) = dfs.next(&EdgeFiltered(&self.graph, is_non_exc_edge)) {
             self.graph[non_exc_ni].attr.remove(BlockAttr::EXCEPTIONAL);
         }
     }
@@ -647,6 +1266,7 @@ impl Graph {
             arcs: Vec::with_capacity(fi.arcs.iter().map(|a| a.arcs.len()).sum()),
             nodes: Vec::with_capacity(fi.blocks.flags.len()),
             source: fi.function.source,
+            configuration: fi.configuration,
         };
 
         self.add_blocks(&mut function, new_index, &fi.blocks);
@@ -762,9 +1382,9 @@ impl Graph {
     /// # Errors
     ///
     /// Returns `MissingFunction` if not found.
-    fn find_function(&self, checksum: u32, ident: Ident, function: Function) -> Result<FunctionIndex> {
+    fn find_function(&self, checksum: u32, ident: Ident, function: Function, configuration: Symbol) -> Result<FunctionIndex> {
         trace!("gcda-function #{}@{}: {:?}", ident, checksum, function);
-        let identity = GcdaFunctionIdentity::new(checksum, ident, &function);
+        let identity = GcdaFunctionIdentity::new(checksum, ident, &function, configuration);
         self.gcda_index.get(&identity).cloned().ok_or_else(|| ErrorKind::MissingFunction(checksum, ident).into())
     }
 
@@ -860,6 +1480,8 @@ struct FunctionInfo {
     arcs: Vec<EdgeIndex>,
     nodes: Vec<NodeIndex>,
     source: Option<Source>,
+    /// The configuration of the GCNO defining this function, or `UNKNOWN_SYMBOL` if untagged.
+    configuration: Symbol,
 }
 
 impl FunctionInfo {
@@ -889,6 +1511,71 @@ impl FunctionInfo {
 //{{{ Graphvis
 
 impl Graph {
+    /// Exports the control-flow graphs of the functions selected by the `filter`, in the order they are merged.
+    ///
+    /// The interner is used to demangle the function names for matching the [`name_pattern`].
+    ///
+    /// [`name_pattern`]: ../cfg/struct.CfgFilter.html#structfield.name_pattern
+    pub fn control_flow_graphs(&self, filter: &CfgFilter, interner: &Interner) -> Vec<FunctionCfg> {
+        let mut cfgs = Vec::new();
+        for function in &self.functions {
+            let source = function.source.unwrap_or_default();
+            let demangled_name = format!("{:#}", demangle(&interner[source.name]));
+            let lines = function.nodes.iter().flat_map(|ni| self.graph[*ni].iter_lines()).map(|(_, line)| line);
+            if !filter.matches(source.filename, &demangled_name, lines) {
+                continue;
+            }
+
+            let entry_block = function.entry_block();
+            let exit_block = function.exit_block(self.version);
+            let blocks = function
+                .nodes
+                .iter()
+                .map(|&ni| {
+                    let block = &self.graph[ni];
+                    let kind = if ni == entry_block {
+                        CfgBlockKind::Entry
+                    } else if ni == exit_block {
+                        CfgBlockKind::Exit
+                    } else {
+                        CfgBlockKind::Normal
+                    };
+                    CfgBlock {
+                        block: block.block,
+                        kind,
+                        count: block.count,
+                        attr: block.attr,
+                        filename: block.iter_lines().next().map_or(UNKNOWN_SYMBOL, |(filename, _)| filename),
+                        lines: block.iter_lines().map(|(_, line)| line).collect(),
+                    }
+                })
+                .collect();
+            let arcs = function
+                .nodes
+                .iter()
+                .flat_map(|ni| self.graph.edges(*ni))
+                .map(|er| {
+                    let arc = er.weight();
+                    CfgArc {
+                        source: self.graph[er.source()].block,
+                        target: self.graph[er.target()].block,
+                        count: arc.count,
+                        attr: arc.attr,
+                    }
+                })
+                .collect();
+            cfgs.push(FunctionCfg {
+                name: source.name,
+                demangled_name,
+                filename: source.filename,
+                line: source.line,
+                blocks,
+                arcs,
+            });
+        }
+        cfgs
+    }
+
     /// Writes out the graph as Graphvis `*.dot` format.
     ///
     /// This is mainly intended for debugging.
@@ -423,5 +423,5 @@ macro_rules! derive_serialize_with_interner {
 }
 
 derive_serialize_with_interner! {
-    direct: u32, u64, usize, PathBuf
+    direct: u32, u64, usize, String, PathBuf
 }
@@ -50,11 +50,19 @@
 //! # Ok(()) }
 //! ```
 //!
+//! ## Source-based coverage
+//!
+//! Programs compiled with `-C instrument-coverage` produce LLVM source-based coverage instead, which is read by the
+//! [`llvm`] module. The coverage mapping embedded in the executables and the raw profiles (`*.profraw`) written when
+//! running them are merged into a [`Coverage`], which also produces a [`Report`].
+//!
 //! [cfg]: https://en.wikipedia.org/wiki/Control_flow_graph
 //! [`Reader`]: ./reader/struct.Reader.html
 //! [`Gcov`]: ./raw/struct.Gcov.html
 //! [`Graph`]: ./graph/struct.Graph.html
 //! [`Report`]: ./report/struct.Report.html
+//! [`llvm`]: ./llvm/index.html
+//! [`Coverage`]: ./llvm/struct.Coverage.html
 
 #![recursion_limit = "128"] // needed for error_chain.
 
@@ -75,7 +83,10 @@ extern crate serde_json;
 extern crate byteorder;
 extern crate petgraph;
 extern crate fixedbitset;
+extern crate md5;
+extern crate miniz_oxide;
 extern crate num_traits; // required for shawshank
+extern crate rustc_demangle;
 extern crate shawshank;
 
 #[macro_use]
@@ -87,12 +98,14 @@ pub mod error;
 pub mod raw;
 pub mod reader;
 pub mod graph;
+pub mod cfg;
 pub mod report;
+pub mod llvm;
 
 #[cfg(feature = "serde")]
 pub use deserializer::with_interner as deserializer_with_interner;
 pub use error::{ErrorKind, Result};
-pub use graph::Graph;
+pub use graph::{BranchDirection, Graph, ReportOptions, UncoveredPath};
 pub use intern::{Interner, Symbol};
 #[cfg(feature = "serde")]
 pub use intern::SerializeWithInterner;
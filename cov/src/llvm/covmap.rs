@@ -0,0 +1,328 @@
+//! Parser of the coverage mapping (`__llvm_covmap` and `__llvm_covfun` sections) embedded in the built objects.
+//!
+//! Coverage mapping versions 4 to 7 (LLVM 11 and above) are supported. See the [LLVM Code Coverage Mapping Format]
+//! for details.
+//!
+//! [LLVM Code Coverage Mapping Format]: https://llvm.org/docs/CoverageMappingFormat.html
+
+use super::ByteReader;
+use super::inflate::zlib_decompress;
+use super::md5::md5_hash;
+use error::{ErrorKind, Result};
+use intern::{Interner, Symbol};
+
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A reference to a counter value in the coverage mapping.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Counter {
+    /// A constant zero.
+    Zero,
+    /// The value of the counter with this index.
+    Counter(usize),
+    /// The difference of the operands of the expression with this index.
+    Subtract(usize),
+    /// The sum of the operands of the expression with this index.
+    Add(usize),
+}
+
+impl Counter {
+    /// Decodes a counter from its encoded integer.
+    fn decode(encoded: u64) -> Counter {
+        let index = (encoded >> 2) as usize;
+        match encoded & 3 {
+            0 => Counter::Zero,
+            1 => Counter::Counter(index),
+            2 => Counter::Subtract(index),
+            _ => Counter::Add(index),
+        }
+    }
+}
+
+/// The kind of a mapping region.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RegionKind {
+    /// A region of code which is executed `counter` times.
+    Code,
+    /// A region of code which expands a macro, whose regions are stored in the file with this ID.
+    Expansion(usize),
+    /// A region of code skipped by the preprocessor, e.g. `#[cfg]`'ed out code.
+    Skipped,
+    /// A region between statements, e.g. the `else` in `} else {`, which should not count as a line being executed.
+    Gap,
+    /// A condition of a branch. The `counter` of the region is the number of times the condition is true, and the
+    /// associated counter is the number of times the condition is false.
+    Branch(Counter),
+    /// A decision of MC/DC. Only the location is recorded.
+    Decision,
+}
+
+/// A mapping region, associating a counter with a range of source code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Region {
+    /// The index into the filenames of the function.
+    pub file_id: usize,
+    /// The counter associated with the region.
+    pub counter: Counter,
+    /// The kind of region.
+    pub kind: RegionKind,
+    /// The starting line (1-based).
+    pub line_start: u32,
+    /// The starting column (1-based).
+    pub column_start: u32,
+    /// The ending line (1-based, inclusive).
+    pub line_end: u32,
+    /// The ending column (1-based, exclusive).
+    pub column_end: u32,
+}
+
+/// The coverage mapping of a function.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FunctionMapping {
+    /// MD5 hash of the function name.
+    pub name_ref: u64,
+    /// Structural hash of the function.
+    pub func_hash: u64,
+    /// The source files referred by the regions.
+    pub filenames: Vec<Symbol>,
+    /// The operands of the counter expressions.
+    pub expressions: Vec<(Counter, Counter)>,
+    /// The mapping regions.
+    pub regions: Vec<Region>,
+}
+
+impl FunctionMapping {
+    /// Evaluates the value of a counter, given the counters of this function from the profile.
+    pub fn evaluate(&self, counter: Counter, counters: &[u64]) -> u64 {
+        self.evaluate_signed(counter, counters).max(0) as u64
+    }
+
+    fn evaluate_signed(&self, counter: Counter, counters: &[u64]) -> i64 {
+        match counter {
+            Counter::Zero => 0,
+            Counter::Counter(index) => counters.get(index).map_or(0, |&c| c as i64),
+            Counter::Subtract(index) | Counter::Add(index) => {
+                let (lhs, rhs) = match self.expressions.get(index) {
+                    Some(&(lhs, rhs)) => (self.evaluate_signed(lhs, counters), self.evaluate_signed(rhs, counters)),
+                    None => return 0,
+                };
+                if let Counter::Add(_) = counter {
+                    lhs.saturating_add(rhs)
+                } else {
+                    lhs.saturating_sub(rhs)
+                }
+            },
+        }
+    }
+}
+
+/// Parses the coverage mapping sections, and returns the mapping of every function.
+///
+/// # Errors
+///
+/// * Returns [`UnsupportedCovmapVersion`] if the coverage mapping version is not supported.
+/// * Returns [`MalformedLlvmData`] if the sections are truncated or corrupted.
+///
+/// [`UnsupportedCovmapVersion`]: ../../error/enum.ErrorKind.html#variant.UnsupportedCovmapVersion
+/// [`MalformedLlvmData`]: ../../error/enum.ErrorKind.html#variant.MalformedLlvmData
+pub fn parse(covmap: &[&[u8]], covfun: &[&[u8]], is_big_endian: bool, interner: &mut Interner) -> Result<Vec<FunctionMapping>> {
+    // Read the filenames of every translation unit, keyed by the hash of the encoded filenames.
+    let mut filenames_by_hash = HashMap::new();
+    for &section in covmap {
+        parse_covmap_section(section, is_big_endian, &mut filenames_by_hash, interner)?;
+    }
+
+    let mut functions = Vec::new();
+    for &section in covfun {
+        parse_covfun_section(section, is_big_endian, &filenames_by_hash, &mut functions)?;
+    }
+    Ok(functions)
+}
+
+/// Maps the hash of the encoded filenames to the decoded filenames and the coverage mapping version.
+type FilenamesByHash = HashMap<u64, (Vec<Symbol>, u32)>;
+
+/// Reads the filenames of every translation unit from a `__llvm_covmap` section.
+fn parse_covmap_section(covmap: &[u8], is_big_endian: bool, filenames_by_hash: &mut FilenamesByHash, interner: &mut Interner) -> Result<()> {
+    let mut reader = ByteReader::new(covmap, is_big_endian);
+    while reader.remaining() >= 16 {
+        let _records_count = reader.u32()?;
+        let filenames_size = reader.u32()? as usize;
+        let coverage_size = reader.u32()? as usize;
+        let version = reader.u32()?;
+        if filenames_size == 0 && coverage_size == 0 && version == 0 {
+            // Zero padding at the end of the section.
+            break;
+        } else if version < 3 || version > 6 {
+            bail!(ErrorKind::UnsupportedCovmapVersion(version + 1));
+        }
+
+        let encoded_filenames = reader.bytes(filenames_size)?;
+        let filenames = decode_filenames(encoded_filenames, version, is_big_endian, interner)?;
+        filenames_by_hash.insert(md5_hash(encoded_filenames), (filenames, version));
+        reader.skip(coverage_size)?;
+        reader.align(8);
+    }
+    Ok(())
+}
+
+/// Reads the mapping of every function from a `__llvm_covfun` section.
+fn parse_covfun_section(covfun: &[u8], is_big_endian: bool, filenames_by_hash: &FilenamesByHash, functions: &mut Vec<FunctionMapping>) -> Result<()> {
+    let mut reader = ByteReader::new(covfun, is_big_endian);
+    while reader.remaining() >= 28 {
+        let name_ref = reader.u64()?;
+        let data_size = reader.u32()? as usize;
+        let func_hash = reader.u64()?;
+        let filenames_ref = reader.u64()?;
+        let data = reader.bytes(data_size)?;
+        reader.align(8);
+        if data_size == 0 {
+            continue;
+        }
+
+        let &(ref all_filenames, version) = match filenames_by_hash.get(&filenames_ref) {
+            Some(entry) => entry,
+            None => bail!(ErrorKind::MalformedLlvmData("function refers to unknown filenames")),
+        };
+        let mut function = decode_function(data, version, all_filenames, is_big_endian)?;
+        function.name_ref = name_ref;
+        function.func_hash = func_hash;
+        functions.push(function);
+    }
+    Ok(())
+}
+
+/// Decodes the filenames of a translation unit.
+///
+/// Since version 6, the first filename is the compilation directory, and other relative filenames are relative to it.
+fn decode_filenames(data: &[u8], version: u32, is_big_endian: bool, interner: &mut Interner) -> Result<Vec<Symbol>> {
+    let mut reader = ByteReader::new(data, is_big_endian);
+    let count = reader.uleb128()? as usize;
+    let uncompressed_len = reader.uleb128()? as usize;
+    let compressed_len = reader.uleb128()? as usize;
+    let decompressed;
+    let mut reader = if compressed_len > 0 {
+        decompressed = zlib_decompress(reader.bytes(compressed_len)?, uncompressed_len)?;
+        ByteReader::new(&decompressed, is_big_endian)
+    } else {
+        ByteReader::new(reader.bytes(uncompressed_len)?, is_big_endian)
+    };
+
+    let mut filenames = Vec::with_capacity(count);
+    for _ in 0..count {
+        let len = reader.uleb128()? as usize;
+        filenames.push(String::from_utf8_lossy(reader.bytes(len)?).into_owned());
+    }
+    if version >= 5 {
+        if let Some((compilation_dir, rest)) = filenames.split_first_mut() {
+            for filename in rest {
+                if !compilation_dir.is_empty() && Path::new(filename).is_relative() {
+                    *filename = Path::new(compilation_dir).join(&filename).to_string_lossy().into_owned();
+                }
+            }
+        }
+    }
+    Ok(filenames.into_iter().map(|f| interner.intern(f)).collect())
+}
+
+/// Decodes the mapping regions of a function.
+fn decode_function(data: &[u8], version: u32, all_filenames: &[Symbol], is_big_endian: bool) -> Result<FunctionMapping> {
+    const EXPANSION_REGION_BIT: u64 = 4;
+    const GAP_REGION_BIT: u64 = 1 << 31;
+
+    let mut reader = ByteReader::new(data, is_big_endian);
+
+    let files_count = reader.uleb128()? as usize;
+    let mut filenames = Vec::with_capacity(files_count);
+    for _ in 0..files_count {
+        match all_filenames.get(reader.uleb128()? as usize) {
+            Some(&filename) => filenames.push(filename),
+            None => bail!(ErrorKind::MalformedLlvmData("filename index out of bounds")),
+        }
+    }
+
+    let expressions_count = reader.uleb128()? as usize;
+    let mut expressions = Vec::with_capacity(expressions_count);
+    for _ in 0..expressions_count {
+        let lhs = Counter::decode(reader.uleb128()?);
+        let rhs = Counter::decode(reader.uleb128()?);
+        expressions.push((lhs, rhs));
+    }
+
+    let mut regions = Vec::new();
+    for file_id in 0..files_count {
+        let regions_count = reader.uleb128()?;
+        let mut line_start = 0_u32;
+        for _ in 0..regions_count {
+            let encoded = reader.uleb128()?;
+            let mut counter = Counter::Zero;
+            let mut kind = RegionKind::Code;
+            if encoded & 3 != 0 {
+                counter = Counter::decode(encoded);
+            } else if encoded & EXPANSION_REGION_BIT != 0 {
+                kind = RegionKind::Expansion((encoded >> 3) as usize);
+            } else {
+                match encoded >> 3 {
+                    0 => {},
+                    2 => kind = RegionKind::Skipped,
+                    4 => {
+                        counter = Counter::decode(reader.uleb128()?);
+                        kind = RegionKind::Branch(Counter::decode(reader.uleb128()?));
+                    },
+                    5 => {
+                        // Bitmap index and number of conditions.
+                        reader.uleb128()?;
+                        reader.uleb128()?;
+                        kind = RegionKind::Decision;
+                    },
+                    6 if version >= 6 => {
+                        counter = Counter::decode(reader.uleb128()?);
+                        kind = RegionKind::Branch(Counter::decode(reader.uleb128()?));
+                        // Condition ID, and the IDs of the next conditions when true or false.
+                        reader.uleb128()?;
+                        reader.uleb128()?;
+                        reader.uleb128()?;
+                    },
+                    _ => bail!(ErrorKind::MalformedLlvmData("unknown region kind")),
+                }
+            }
+
+            let line_start_delta = reader.uleb128()? as u32;
+            let mut column_start = reader.uleb128()? as u32;
+            let lines_count = reader.uleb128()? as u32;
+            let mut column_end = reader.uleb128()?;
+            if column_end & GAP_REGION_BIT != 0 {
+                column_end &= !GAP_REGION_BIT;
+                if kind == RegionKind::Code {
+                    kind = RegionKind::Gap;
+                }
+            }
+            let mut column_end = column_end as u32;
+            line_start += line_start_delta;
+            if column_start == 0 && column_end == 0 {
+                // The whole lines are covered.
+                column_start = 1;
+                column_end = u32::max_value();
+            }
+
+            regions.push(Region {
+                file_id,
+                counter,
+                kind,
+                line_start,
+                column_start,
+                line_end: line_start + lines_count,
+                column_end,
+            });
+        }
+    }
+
+    Ok(FunctionMapping {
+        name_ref: 0,
+        func_hash: 0,
+        filenames,
+        expressions,
+        regions,
+    })
+}
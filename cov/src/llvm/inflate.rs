@@ -0,0 +1,35 @@
+//! zlib decompression, used by LLVM to compress function names and filename lists in the coverage formats.
+
+use error::{ErrorKind, Result};
+
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+
+/// Decompresses a zlib stream, which should produce exactly `expected_len` bytes.
+pub fn zlib_decompress(input: &[u8], expected_len: usize) -> Result<Vec<u8>> {
+    let output = decompress_to_vec_zlib_with_limit(input, expected_len).map_err(|_| ErrorKind::MalformedLlvmData("invalid zlib stream"))?;
+    if output.len() != expected_len {
+        bail!(ErrorKind::MalformedLlvmData("decompressed size mismatch"));
+    }
+    Ok(output)
+}
+
+#[test]
+fn test_zlib_decompress() {
+    let stored = b"\x78\x01\x01\x06\x00\xf9\xff\x73\x74\x6f\x72\x65\x64\x09\x3c\x02\x92";
+    assert_eq!(zlib_decompress(stored, 6).unwrap(), b"stored");
+
+    let fixed = b"\x78\xda\xcb\x48\xcd\xc9\xc9\xd7\x51\xc8\x40\xa2\x14\xca\xf3\x8b\x72\x52\x00\x74\x87\x09\x1d";
+    assert_eq!(zlib_decompress(fixed, 25).unwrap(), &b"hello, hello, hello world"[..]);
+
+    let dynamic = b"\x78\xda\x85\xd3\x4b\x0e\x82\x50\x10\x44\xd1\xb9\xab\xe8\x25\xd8\x55\x7e\x70\x39\xc4\x3c\x91\x04\x81\
+        \x10\xc3\xfa\x89\x0b\xb0\xef\xf8\xce\x4e\xaa\xa6\x71\x6e\x71\x8e\xe5\x15\xdf\x77\x8b\xe7\xb2\xb7\xad\
+        \x1f\x5a\x7c\xfa\x75\x1d\xe7\xe1\x34\xfd\x72\xd6\x59\x75\x76\x9d\x2f\x75\xbe\xd6\xf9\x56\xe7\x7b\x9d\
+        \xbb\x3a\x3f\x80\x85\xd8\xc0\x2d\x01\x2e\x41\x2e\x81\x2e\xc1\x2e\x01\x2f\x41\x2f\x81\x2f\xc1\x4f\xe0\
+        \x27\xda\x1d\xf8\x09\xfc\x04\x7e\x02\x3f\x81\x9f\xc0\x4f\xe0\x27\xf0\x33\xf8\x19\xfc\x4c\xc7\x05\x3f\
+        \x83\x9f\xc1\xcf\xe0\x67\xf0\x33\xf8\xf9\xbf\xdf\x01\x2f\x06\xb7\x20";
+    let expected = (0..40).map(|i| format!("line {} of the coverage mapping\n", i)).collect::<String>();
+    assert_eq!(zlib_decompress(dynamic, 1270).unwrap(), expected.as_bytes());
+
+    assert!(zlib_decompress(fixed, 24).is_err());
+    assert!(zlib_decompress(&fixed[..10], 25).is_err());
+}
@@ -0,0 +1,20 @@
+//! MD5 digest, used by LLVM to hash function names and filename lists in the coverage formats.
+
+use byteorder::{ByteOrder, LittleEndian};
+
+/// Computes the MD5 digest of the data.
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    ::md5::compute(data).0
+}
+
+/// Computes the 64-bit hash used by LLVM, i.e. the first 8 bytes of the MD5 digest as a little-endian integer.
+pub fn md5_hash(data: &[u8]) -> u64 {
+    LittleEndian::read_u64(&md5(data))
+}
+
+#[test]
+fn test_md5() {
+    assert_eq!(md5(b""), *b"\xd4\x1d\x8c\xd9\x8f\x00\xb2\x04\xe9\x80\x09\x98\xec\xf8\x42\x7e");
+    assert_eq!(md5(b"The quick brown fox jumps over the lazy dog"), *b"\x9e\x10\x7d\x9d\x37\x2b\xb6\x82\x6b\xd8\x1d\x35\x42\xa4\x19\xd6");
+    assert_eq!(md5_hash(b""), 0x04b2_008f_d98c_1dd4);
+}
@@ -0,0 +1,394 @@
+//! Source-based coverage produced by `rustc -C instrument-coverage`.
+//!
+//! Unlike GCNO/GCDA, the LLVM source-based coverage is split into two kinds of files:
+//!
+//! * The **coverage mapping** is embedded inside the compiled object file or executable. It records the source regions
+//!   of every function, and which counter (or expression of counters) is associated with each region.
+//! * The **raw profile** (`*.profraw`) is written when running the instrumented program. It records the value of every
+//!   counter.
+//!
+//! Combining them with a [`Coverage`], a [`Report`] can be produced just like a [`Graph`] does for GCNO/GCDA. Since
+//! the regions have column information, the line counts are more precise than those derived from basic blocks.
+//!
+//! ```rust,no_run
+//! extern crate cov;
+//! use cov::Interner;
+//! use cov::llvm::Coverage;
+//!
+//! # fn main() { run().unwrap(); }
+//! # fn run() -> cov::Result<()> {
+//! let mut interner = Interner::default();
+//! let mut coverage = Coverage::default();
+//!
+//! coverage.merge_object_file("target/debug/my_program", &mut interner)?;
+//! coverage.merge_profraw_file("default.profraw")?;
+//!
+//! let report = coverage.report();
+//! # drop(report); Ok(()) }
+//! ```
+//!
+//! [`Coverage`]: ./struct.Coverage.html
+//! [`Report`]: ../report/struct.Report.html
+//! [`Graph`]: ../graph/struct.Graph.html
+
+mod md5;
+mod inflate;
+mod object;
+pub mod profraw;
+pub mod covmap;
+
+use self::covmap::{FunctionMapping, RegionKind};
+use error::{ErrorKind, Location, Result};
+use intern::{Interner, Symbol};
+use raw::{ArcAttr, BlockAttr};
+use report::{self, Report};
+use utils::EntryExt;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use std::cmp;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Accumulated source-based coverage of a program.
+///
+/// Merge every object file containing coverage mapping first, then every raw profile, and finally call [`report()`]
+/// to generate the coverage report.
+///
+/// [`report()`]: #method.report
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    /// The coverage mapping of every function, together with the function name.
+    functions: Vec<(Symbol, FunctionMapping)>,
+    /// Maps a function name hash to its index in `functions`.
+    function_indices: HashMap<u64, usize>,
+    /// The accumulated counters of every function, keyed by the function name hash and the structural hash.
+    counters: HashMap<(u64, u64), Vec<u64>>,
+}
+
+impl Coverage {
+    /// Merges the coverage mapping from an object file or executable.
+    ///
+    /// The same function may appear in multiple object files (e.g. generic functions instantiated in several crates),
+    /// in which case only the first mapping is kept. Functions which are never instrumented (having zero structural
+    /// hash) are replaced by the instrumented ones.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`UnknownObjectFormat`] if the data is not an object file.
+    /// * Returns [`UnsupportedCovmapVersion`] if the coverage mapping version is not supported.
+    /// * Returns [`MalformedLlvmData`] if the coverage mapping is corrupted.
+    ///
+    /// [`UnknownObjectFormat`]: ../error/enum.ErrorKind.html#variant.UnknownObjectFormat
+    /// [`UnsupportedCovmapVersion`]: ../error/enum.ErrorKind.html#variant.UnsupportedCovmapVersion
+    /// [`MalformedLlvmData`]: ../error/enum.ErrorKind.html#variant.MalformedLlvmData
+    pub fn merge_object(&mut self, data: &[u8], interner: &mut Interner) -> Result<()> {
+        let sections = object::find_sections(data)?;
+        let mut names = HashMap::new();
+        for section in &sections.names {
+            parse_names(section, &mut names)?;
+        }
+        for mapping in covmap::parse(&sections.covmap, &sections.covfun, sections.is_big_endian, interner)? {
+            let name = match names.get(&mapping.name_ref) {
+                Some(name) => interner.intern(&**name),
+                None => interner.intern(format!("{:016x}", mapping.name_ref)),
+            };
+
+            let name_ref = mapping.name_ref;
+            if let Some(&index) = self.function_indices.get(&name_ref) {
+                if self.functions[index].1.func_hash == 0 && mapping.func_hash != 0 {
+                    self.functions[index] = (name, mapping);
+                }
+            } else {
+                self.function_indices.insert(name_ref, self.functions.len());
+                self.functions.push((name, mapping));
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges the coverage mapping from an object file or executable at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`merge_object()`], and also returns [`Io`] on I/O failure.
+    ///
+    /// [`merge_object()`]: #method.merge_object
+    /// [`Io`]: ../error/enum.ErrorKind.html#variant.Io
+    pub fn merge_object_file<P: AsRef<Path>>(&mut self, path: P, interner: &mut Interner) -> Result<()> {
+        debug!("open object file {:?}", path.as_ref());
+        Location::File(path.as_ref().to_owned()).wrap(|| -> Result<()> {
+            let data = read_file(path.as_ref())?;
+            self.merge_object(&data, interner)
+        })
+    }
+
+    /// Merges the counters from a raw profile. Counters of the same function are summed.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`UnknownFileType`] if the data is not a raw profile.
+    /// * Returns [`UnsupportedProfrawVersion`] if the raw profile version is not supported.
+    /// * Returns [`MalformedLlvmData`] if the raw profile is corrupted.
+    ///
+    /// [`UnknownFileType`]: ../error/enum.ErrorKind.html#variant.UnknownFileType
+    /// [`UnsupportedProfrawVersion`]: ../error/enum.ErrorKind.html#variant.UnsupportedProfrawVersion
+    /// [`MalformedLlvmData`]: ../error/enum.ErrorKind.html#variant.MalformedLlvmData
+    pub fn merge_profraw(&mut self, data: &[u8]) -> Result<()> {
+        for function in profraw::parse(data)? {
+            let counters = self.counters.entry((function.name_ref, function.func_hash)).or_default_();
+            if counters.len() < function.counters.len() {
+                counters.resize(function.counters.len(), 0);
+            }
+            for (sum, count) in counters.iter_mut().zip(function.counters) {
+                *sum = sum.saturating_add(count);
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges the counters from a raw profile at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`merge_profraw()`], and also returns [`Io`] on I/O failure.
+    ///
+    /// [`merge_profraw()`]: #method.merge_profraw
+    /// [`Io`]: ../error/enum.ErrorKind.html#variant.Io
+    pub fn merge_profraw_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        debug!("open profraw file {:?}", path.as_ref());
+        Location::File(path.as_ref().to_owned()).wrap(|| -> Result<()> {
+            let data = read_file(path.as_ref())?;
+            self.merge_profraw(&data)
+        })
+    }
+
+    /// Obtains a coverage report from the accumulated coverage.
+    ///
+    /// The count of a line is the maximum among the counts of the regions starting on that line, and the count of the
+    /// innermost region spanning across the line. Every branch region produces two [`Branch`]es, the first for the
+    /// condition being true and the second for being false.
+    ///
+    /// The code regions are reported in [`File::regions`]. Regions of generic functions having the same span are merged
+    /// into one, with the counts summed.
+    ///
+    /// Since source-based coverage does not instrument function returns, the `exit_count` of every function is
+    /// reported to be the same as the `entry_count`.
+    ///
+    /// [`Branch`]: ../report/struct.Branch.html
+    /// [`File::regions`]: ../report/struct.File.html#structfield.regions
+    pub fn report(&self) -> Report {
+        let mut r = Report::default();
+
+        // Code regions having the same span are summed, since generic functions are instantiated multiple times.
+        let mut regions = HashMap::<Symbol, BTreeMap<(u32, u32, u32, u32), u64>>::new();
+
+        for &(name, ref mapping) in &self.functions {
+            let counters = self.counters.get(&(mapping.name_ref, mapping.func_hash)).map_or(&[][..], |c| &c[..]);
+            let mut summary = report::FunctionSummary {
+                complexity: 1,
+                ..report::FunctionSummary::default()
+            };
+            for region in &mapping.regions {
+                let filename = mapping.filenames[region.file_id];
+                let count = mapping.evaluate(region.counter, counters);
+                let span = (region.line_start, region.column_start, region.line_end, region.column_end);
+                match region.kind {
+                    RegionKind::Code => {
+                        *regions.entry(filename).or_default_().entry(span).or_insert(0) += count;
+                        summary.blocks_count += 1;
+                        summary.blocks_executed += (count > 0) as usize;
+                        summary.block_executions += count;
+                    },
+                    RegionKind::Branch(false_counter) => {
+                        let false_count = mapping.evaluate(false_counter, counters);
+                        let line = r.files.entry(filename).or_default_().lines.entry(region.line_start).or_default_();
+                        for &count in &[count, false_count] {
+                            line.branches.push(report::Branch {
+                                count,
+                                attr: ArcAttr::empty(),
+                                filename,
+                                line: region.line_start,
+                                column: region.column_start,
+                            });
+                        }
+                        summary.branches_count += 2;
+                        summary.branches_executed += if count + false_count > 0 { 2 } else { 0 };
+                        summary.branches_taken += (count > 0) as usize + (false_count > 0) as usize;
+                        summary.complexity += 1;
+                    },
+                    RegionKind::Expansion(_) | RegionKind::Skipped | RegionKind::Gap | RegionKind::Decision => {},
+                }
+            }
+
+            let first_region = match mapping.regions.first() {
+                Some(region) => region,
+                None => continue,
+            };
+            summary.entry_count = mapping.evaluate(first_region.counter, counters);
+            summary.exit_count = summary.entry_count;
+            let file = r.files.entry(mapping.filenames[first_region.file_id]).or_default_();
+            file.functions.push(report::Function {
+                name,
+                demangled_name: String::new(),
+                generic_name: String::new(),
+                line: first_region.line_start,
+                column: first_region.column_start,
+                summary,
+                covered_by: Vec::new(),
+                configuration: None,
+            });
+        }
+
+        for (filename, regions) in regions {
+            let file = r.files.entry(filename).or_default_();
+            file.regions = regions
+                .iter()
+                .map(|(&(line_start, column_start, line_end, column_end), &count)| report::Region {
+                    line_start,
+                    column_start,
+                    line_end,
+                    column_end,
+                    count,
+                })
+                .collect();
+            // Maps each line to (max count of regions starting on this line, (start of the innermost wrapping region,
+            // its count)).
+            let mut line_counts = BTreeMap::<u32, (Option<u64>, Option<((u32, u32), u64)>)>::new();
+            for (&(line_start, column_start, line_end, _), &count) in &regions {
+                let start_count = &mut line_counts.entry(line_start).or_default_().0;
+                *start_count = cmp::max(*start_count, Some(count));
+                for line in (line_start + 1)..(line_end + 1) {
+                    let wrapping = &mut line_counts.entry(line).or_default_().1;
+                    if wrapping.map_or(true, |(start, _)| start <= (line_start, column_start)) {
+                        *wrapping = Some(((line_start, column_start), count));
+                    }
+                }
+            }
+
+            for (line_number, (start_count, wrapping)) in line_counts {
+                let wrapping_count = wrapping.map(|(_, count)| count);
+                let line = file.lines.entry(line_number).or_default_();
+                line.count = cmp::max(start_count, wrapping_count).unwrap_or(0);
+                line.attr = BlockAttr::empty();
+            }
+        }
+
+        for file in r.files.values_mut() {
+            file.functions.sort_by_key(|f| (f.line, f.column));
+        }
+
+        r
+    }
+}
+
+/// Reads the whole content of a file.
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Parses a `__llvm_prf_names` section, and inserts the function names keyed by their MD5 hash.
+fn parse_names(data: &[u8], names: &mut HashMap<u64, String>) -> Result<()> {
+    let mut reader = ByteReader::new(data, false);
+    while reader.remaining() > 0 {
+        let uncompressed_len = reader.uleb128()? as usize;
+        let compressed_len = reader.uleb128()? as usize;
+        let chunk = if compressed_len > 0 {
+            inflate::zlib_decompress(reader.bytes(compressed_len)?, uncompressed_len)?
+        } else {
+            reader.bytes(uncompressed_len)?.to_vec()
+        };
+        for name in chunk.split(|&b| b == 1) {
+            names.insert(md5::md5_hash(name), String::from_utf8_lossy(name).into_owned());
+        }
+        // The names section may be padded with zeros.
+        while reader.remaining() > 0 && reader.data[reader.pos] == 0 {
+            reader.pos += 1;
+        }
+    }
+    Ok(())
+}
+
+/// A cursor over a byte slice, reading integers in the endianness of the file.
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    is_big_endian: bool,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8], is_big_endian: bool) -> ByteReader<'a> {
+        ByteReader {
+            data,
+            pos: 0,
+            is_big_endian,
+        }
+    }
+
+    /// Number of bytes not yet read.
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    /// Reads the next `len` bytes.
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        match self.pos.checked_add(len).and_then(|end| self.data.get(self.pos..end)) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            },
+            None => bail!(ErrorKind::MalformedLlvmData("unexpected end of data")),
+        }
+    }
+
+    /// Skips the next `len` bytes.
+    fn skip(&mut self, len: usize) -> Result<()> {
+        self.bytes(len)?;
+        Ok(())
+    }
+
+    /// Skips to the next position which is a multiple of `alignment`, or to the end if it is too short.
+    fn align(&mut self, alignment: usize) {
+        let pos = (self.pos + alignment - 1) / alignment * alignment;
+        self.pos = cmp::min(pos, self.data.len());
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(if self.is_big_endian { BigEndian::read_u32(bytes) } else { LittleEndian::read_u32(bytes) })
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let bytes = self.bytes(8)?;
+        Ok(if self.is_big_endian { BigEndian::read_u64(bytes) } else { LittleEndian::read_u64(bytes) })
+    }
+
+    /// Reads an unsigned LEB128 integer.
+    fn uleb128(&mut self) -> Result<u64> {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.bytes(1)?[0];
+            if shift < 64 {
+                result |= u64::from(byte & 0x7f) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_names() {
+    let mut names = HashMap::new();
+    parse_names(b"\x06\x00main\x01f\x00\x00", &mut names).unwrap();
+    assert_eq!(names.len(), 2);
+    assert_eq!(names[&md5::md5_hash(b"main")], "main");
+    assert_eq!(names[&md5::md5_hash(b"f")], "f");
+}
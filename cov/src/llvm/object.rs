@@ -0,0 +1,196 @@
+//! Locating the coverage sections inside object files and executables.
+//!
+//! ELF, Mach-O and PE/COFF formats are supported. Universal (fat) Mach-O binaries are not supported.
+
+use error::{ErrorKind, Result};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+/// The coverage-related sections of an object file.
+///
+/// Relocatable object files may contain multiple sections of the same name, e.g. each instantiation of a generic
+/// function has its own `__llvm_covfun` section in a COMDAT group. Their contents are stored separately.
+#[derive(Clone, Debug, Default)]
+pub struct Sections<'a> {
+    /// Contents of the `__llvm_covmap` sections, containing the filenames.
+    pub covmap: Vec<&'a [u8]>,
+    /// Contents of the `__llvm_covfun` sections, containing the mapping regions of every function.
+    pub covfun: Vec<&'a [u8]>,
+    /// Contents of the `__llvm_prf_names` sections, containing the function names.
+    pub names: Vec<&'a [u8]>,
+    /// Whether the object file is in big-endian.
+    pub is_big_endian: bool,
+}
+
+impl<'a> Sections<'a> {
+    /// Stores the section content if the name is one of the coverage sections.
+    fn insert(&mut self, name: &[u8], content: &'a [u8]) {
+        match name {
+            b"__llvm_covmap" | b".lcovmap" => self.covmap.push(content),
+            b"__llvm_covfun" | b".lcovfun" => self.covfun.push(content),
+            b"__llvm_prf_names" | b".lprfn" => self.names.push(content),
+            _ => {},
+        }
+    }
+}
+
+/// Finds the coverage sections of an object file.
+///
+/// # Errors
+///
+/// Returns [`UnknownObjectFormat`] if the data is not an ELF, Mach-O or PE/COFF object file.
+///
+/// [`UnknownObjectFormat`]: ../../error/enum.ErrorKind.html#variant.UnknownObjectFormat
+pub fn find_sections(data: &[u8]) -> Result<Sections> {
+    if data.starts_with(b"\x7fELF") {
+        match data.get(5) {
+            Some(&2) => find_elf_sections::<BigEndian>(data, true),
+            _ => find_elf_sections::<LittleEndian>(data, false),
+        }
+    } else if data.starts_with(b"\xcf\xfa\xed\xfe") || data.starts_with(b"\xce\xfa\xed\xfe") {
+        find_macho_sections::<LittleEndian>(data, false)
+    } else if data.starts_with(b"\xfe\xed\xfa\xcf") || data.starts_with(b"\xfe\xed\xfa\xce") {
+        find_macho_sections::<BigEndian>(data, true)
+    } else if data.starts_with(b"MZ") {
+        let pe_offset = read_u32::<LittleEndian>(data, 0x3c)? as usize;
+        if data.get(pe_offset..(pe_offset + 4)) != Some(b"PE\0\0") {
+            bail!(ErrorKind::UnknownObjectFormat);
+        }
+        find_coff_sections(data, pe_offset + 4)
+    } else if data.len() >= 20 {
+        // COFF object files have no signature. We check whether it starts with a known machine type instead.
+        match LittleEndian::read_u16(data) {
+            0x014c | 0x8664 | 0x01c4 | 0xaa64 => find_coff_sections(data, 0),
+            _ => bail!(ErrorKind::UnknownObjectFormat),
+        }
+    } else {
+        bail!(ErrorKind::UnknownObjectFormat)
+    }
+}
+
+fn slice(data: &[u8], offset: usize, len: usize) -> Result<&[u8]> {
+    match offset.checked_add(len).and_then(|end| data.get(offset..end)) {
+        Some(s) => Ok(s),
+        None => bail!(ErrorKind::MalformedLlvmData("section out of bounds")),
+    }
+}
+
+fn read_u16<B: ByteOrder>(data: &[u8], offset: usize) -> Result<u16> {
+    Ok(B::read_u16(slice(data, offset, 2)?))
+}
+
+fn read_u32<B: ByteOrder>(data: &[u8], offset: usize) -> Result<u32> {
+    Ok(B::read_u32(slice(data, offset, 4)?))
+}
+
+fn read_u64<B: ByteOrder>(data: &[u8], offset: usize) -> Result<u64> {
+    Ok(B::read_u64(slice(data, offset, 8)?))
+}
+
+/// Strips the trailing NUL characters of a fixed-size name field.
+fn trim_name(name: &[u8]) -> &[u8] {
+    let len = name.iter().position(|&b| b == 0).unwrap_or_else(|| name.len());
+    &name[..len]
+}
+
+fn find_elf_sections<B: ByteOrder>(data: &[u8], is_big_endian: bool) -> Result<Sections> {
+    let is_64_bit = data.get(4) == Some(&2);
+    let (sh_offset, sh_entsize, sh_num, sh_strndx) = if is_64_bit {
+        (read_u64::<B>(data, 0x28)? as usize, read_u16::<B>(data, 0x3a)?, read_u16::<B>(data, 0x3c)?, read_u16::<B>(data, 0x3e)?)
+    } else {
+        (read_u32::<B>(data, 0x20)? as usize, read_u16::<B>(data, 0x2e)?, read_u16::<B>(data, 0x30)?, read_u16::<B>(data, 0x32)?)
+    };
+
+    // Returns the (name offset, content offset, content size) of a section header.
+    let section_header = |index: u16| -> Result<(usize, usize, usize)> {
+        let header = sh_offset + index as usize * sh_entsize as usize;
+        Ok(if is_64_bit {
+            (read_u32::<B>(data, header)? as usize, read_u64::<B>(data, header + 24)? as usize, read_u64::<B>(data, header + 32)? as usize)
+        } else {
+            (read_u32::<B>(data, header)? as usize, read_u32::<B>(data, header + 16)? as usize, read_u32::<B>(data, header + 20)? as usize)
+        })
+    };
+
+    let mut sections = Sections {
+        is_big_endian,
+        ..Sections::default()
+    };
+    if sh_num == 0 {
+        return Ok(sections);
+    }
+
+    let (_, names_offset, names_size) = section_header(sh_strndx)?;
+    let names = slice(data, names_offset, names_size)?;
+    for index in 0..sh_num {
+        let (name_offset, offset, size) = section_header(index)?;
+        let name = trim_name(names.get(name_offset..).unwrap_or_default());
+        if name.starts_with(b"__llvm_") {
+            sections.insert(name, slice(data, offset, size)?);
+        }
+    }
+    Ok(sections)
+}
+
+fn find_macho_sections<B: ByteOrder>(data: &[u8], is_big_endian: bool) -> Result<Sections> {
+    const LC_SEGMENT: u32 = 0x1;
+    const LC_SEGMENT_64: u32 = 0x19;
+
+    let is_64_bit = data[0] == 0xcf || data[3] == 0xcf;
+    let commands_count = read_u32::<B>(data, 16)?;
+    let mut command_offset = if is_64_bit { 32 } else { 28 };
+
+    let mut sections = Sections {
+        is_big_endian,
+        ..Sections::default()
+    };
+    for _ in 0..commands_count {
+        let command = read_u32::<B>(data, command_offset)?;
+        let command_size = read_u32::<B>(data, command_offset + 4)? as usize;
+        if command == LC_SEGMENT_64 || command == LC_SEGMENT {
+            let (sections_count_offset, first_section_offset, section_size) = if command == LC_SEGMENT_64 { (64, 72, 80) } else { (48, 56, 68) };
+            let sections_count = read_u32::<B>(data, command_offset + sections_count_offset)? as usize;
+            for i in 0..sections_count {
+                let header = command_offset + first_section_offset + i * section_size;
+                let name = trim_name(slice(data, header, 16)?);
+                let (offset, size) = if command == LC_SEGMENT_64 {
+                    (read_u32::<B>(data, header + 48)? as usize, read_u64::<B>(data, header + 40)? as usize)
+                } else {
+                    (read_u32::<B>(data, header + 40)? as usize, read_u32::<B>(data, header + 36)? as usize)
+                };
+                if name.starts_with(b"__llvm_") {
+                    sections.insert(name, slice(data, offset, size)?);
+                }
+            }
+        }
+        if command_size == 0 {
+            bail!(ErrorKind::MalformedLlvmData("empty Mach-O load command"));
+        }
+        command_offset += command_size;
+    }
+    Ok(sections)
+}
+
+fn find_coff_sections(data: &[u8], header_offset: usize) -> Result<Sections> {
+    let sections_count = read_u16::<LittleEndian>(data, header_offset + 2)? as usize;
+    let optional_header_size = read_u16::<LittleEndian>(data, header_offset + 16)? as usize;
+    let first_section_offset = header_offset + 20 + optional_header_size;
+
+    let mut sections = Sections::default();
+    for i in 0..sections_count {
+        let header = first_section_offset + i * 40;
+        // Grouped section names like `.lcovmap$M` are merged by the linker, and truncated to 8 bytes in images.
+        let name = trim_name(slice(data, header, 8)?);
+        let name = &name[..name.iter().position(|&b| b == b'$').unwrap_or_else(|| name.len())];
+        let virtual_size = read_u32::<LittleEndian>(data, header + 8)? as usize;
+        let mut size = read_u32::<LittleEndian>(data, header + 16)? as usize;
+        let offset = read_u32::<LittleEndian>(data, header + 20)? as usize;
+        if virtual_size != 0 && virtual_size < size {
+            // In images the raw data is padded to the file alignment.
+            size = virtual_size;
+        }
+        if name.starts_with(b".l") {
+            sections.insert(name, slice(data, offset, size)?);
+        }
+    }
+    Ok(sections)
+}
@@ -0,0 +1,178 @@
+//! Parser of the raw profile (`*.profraw`), written by programs built with `-C instrument-coverage`.
+//!
+//! Raw profile versions 5 to 10 (LLVM 11 to 21) are supported. Only the counters are read, the function names are
+//! taken from the object files instead.
+
+use super::ByteReader;
+use error::{ErrorKind, Result};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+const MAGIC_64: u64 = 0xff6c_7072_6f66_7281;
+const MAGIC_32: u64 = 0xff6c_7072_6f66_5281;
+const VARIANT_MASKS_ALL: u64 = 0xffff_ffff_0000_0000;
+const VARIANT_MASK_BYTE_COVERAGE: u64 = 1 << 60;
+
+/// The counters of a function in the raw profile.
+#[derive(Clone, Debug)]
+pub struct FunctionCounters {
+    /// MD5 hash of the function name.
+    pub name_ref: u64,
+    /// Structural hash of the function.
+    pub func_hash: u64,
+    /// Values of the counters.
+    pub counters: Vec<u64>,
+}
+
+/// Parses a raw profile, and returns the counters of every function.
+///
+/// # Errors
+///
+/// * Returns [`UnknownFileType`] if the data is not a raw profile.
+/// * Returns [`UnsupportedProfrawVersion`] if the raw profile version is not supported.
+/// * Returns [`MalformedLlvmData`] if the raw profile is truncated or corrupted.
+///
+/// [`UnknownFileType`]: ../../error/enum.ErrorKind.html#variant.UnknownFileType
+/// [`UnsupportedProfrawVersion`]: ../../error/enum.ErrorKind.html#variant.UnsupportedProfrawVersion
+/// [`MalformedLlvmData`]: ../../error/enum.ErrorKind.html#variant.MalformedLlvmData
+pub fn parse(data: &[u8]) -> Result<Vec<FunctionCounters>> {
+    if data.len() < 8 {
+        bail!(ErrorKind::UnknownFileType(0));
+    }
+    let magic = LittleEndian::read_u64(data);
+    let (is_big_endian, is_64_bit) = match magic {
+        MAGIC_64 => (false, true),
+        MAGIC_32 => (false, false),
+        _ if magic.swap_bytes() == MAGIC_64 => (true, true),
+        _ if magic.swap_bytes() == MAGIC_32 => (true, false),
+        _ => bail!(ErrorKind::UnknownFileType(magic as u32)),
+    };
+
+    let mut reader = ByteReader::new(data, is_big_endian);
+    reader.skip(8)?;
+    let raw_version = reader.u64()?;
+    let version = raw_version & !VARIANT_MASKS_ALL;
+    if version < 5 || version > 10 {
+        bail!(ErrorKind::UnsupportedProfrawVersion(version));
+    }
+    let counter_size = if raw_version & VARIANT_MASK_BYTE_COVERAGE != 0 { 1 } else { 8 };
+
+    let binary_ids_size = if version >= 6 { reader.u64()? } else { 0 };
+    let data_count = reader.u64()?;
+    let padding_before_counters = reader.u64()?;
+    let counters_count = reader.u64()?;
+    let _padding_after_counters = reader.u64()?;
+    if version >= 9 {
+        let _bitmap_bytes = reader.u64()?;
+        let _padding_after_bitmap_bytes = reader.u64()?;
+    }
+    let _names_size = reader.u64()?;
+    let mut counters_delta = reader.u64()?;
+    if !is_64_bit {
+        // On 32-bit platforms, the delta is a 32-bit pointer difference zero-extended to 64-bit.
+        counters_delta = i64::from(counters_delta as u32 as i32) as u64;
+    }
+    if version >= 9 {
+        let _bitmap_delta = reader.u64()?;
+    }
+    let _names_delta = reader.u64()?;
+    if version >= 10 {
+        let _vtables_count = reader.u64()?;
+        let _vnames_size = reader.u64()?;
+    }
+    let _value_kind_last = reader.u64()?;
+    reader.skip(checked_size(&reader, binary_ids_size, 1)?)?;
+
+    // Each data record contains the name hash, function hash and the following pointers: counters, bitmap (v9+),
+    // function and values. The end of the record contains the counters count, value sites count ×2 (u16), and the
+    // bitmap size (v9+).
+    let pointer_size = if is_64_bit { 8 } else { 4 };
+    let pointers_count = if version >= 9 { 4 } else { 3 };
+    let tail_size = if version >= 9 { 12 } else { 8 };
+    let record_size = (16 + pointers_count * pointer_size + tail_size + 7) & !7;
+
+    // Check the counts against the input before allocating, a corrupted header may claim billions of records.
+    checked_size(&reader, data_count, record_size as u64)?;
+    let mut records = Vec::with_capacity(data_count as usize);
+    for i in 0..data_count {
+        let mut record = ByteReader::new(reader.bytes(record_size)?, is_big_endian);
+        let name_ref = record.u64()?;
+        let func_hash = record.u64()?;
+        let counter_ptr = if is_64_bit { record.u64()? } else { i64::from(record.u32()? as i32) as u64 };
+        record.skip((pointers_count - 1) * pointer_size)?;
+        let num_counters = u64::from(record.u32()?);
+
+        // Since version 7, the counter pointer is relative to the data record itself.
+        let base = if version >= 7 {
+            counters_delta.wrapping_sub(i.wrapping_mul(record_size as u64))
+        } else {
+            counters_delta
+        };
+        let offset = counter_ptr.wrapping_sub(base) / counter_size;
+        if offset.checked_add(num_counters).map_or(true, |end| end > counters_count) {
+            bail!(ErrorKind::MalformedLlvmData("counters out of bounds"));
+        }
+        records.push((name_ref, func_hash, offset as usize, num_counters as usize));
+    }
+
+    reader.skip(checked_size(&reader, padding_before_counters, 1)?)?;
+    let counters_bytes = reader.bytes(checked_size(&reader, counters_count, counter_size)?)?;
+    let mut counters_reader = ByteReader::new(counters_bytes, is_big_endian);
+    let mut all_counters = Vec::with_capacity(counters_count as usize);
+    for _ in 0..counters_count {
+        all_counters.push(if counter_size == 1 {
+            // Single-byte counters are 0 when covered.
+            (counters_reader.bytes(1)?[0] == 0) as u64
+        } else {
+            counters_reader.u64()?
+        });
+    }
+
+    Ok(records
+        .into_iter()
+        .map(|(name_ref, func_hash, offset, len)| FunctionCounters {
+            name_ref,
+            func_hash,
+            counters: all_counters[offset..(offset + len)].to_vec(),
+        })
+        .collect())
+}
+
+/// Computes the size of `count` items of `size` bytes each, and checks that they fit in the remaining input.
+///
+/// # Errors
+///
+/// Returns [`MalformedLlvmData`] if the input is too short.
+///
+/// [`MalformedLlvmData`]: ../../error/enum.ErrorKind.html#variant.MalformedLlvmData
+fn checked_size(reader: &ByteReader, count: u64, size: u64) -> Result<usize> {
+    match count.checked_mul(size) {
+        Some(total) if total <= reader.remaining() as u64 => Ok(total as usize),
+        _ => bail!(ErrorKind::MalformedLlvmData("unexpected end of data")),
+    }
+}
+
+#[test]
+fn test_parse_truncated() {
+    // A version 8 header claiming 2⁶⁰ data records and counters, without any data following.
+    let mut data = Vec::new();
+    for &field in &[MAGIC_64, 8, 0, 1 << 60, 0, 1 << 60, 0, 0, 0, 0, 0] {
+        data.extend_from_slice(&[0; 8]);
+        let len = data.len();
+        LittleEndian::write_u64(&mut data[(len - 8)..], field);
+    }
+    match parse(&data).unwrap_err().kind() {
+        &ErrorKind::MalformedLlvmData(_) => {},
+        kind => panic!("unexpected error: {:?}", kind),
+    }
+
+    // The same header with no data records.
+    LittleEndian::write_u64(&mut data[24..], 0);
+    match parse(&data).unwrap_err().kind() {
+        &ErrorKind::MalformedLlvmData(_) => {},
+        kind => panic!("unexpected error: {:?}", kind),
+    }
+
+    LittleEndian::write_u64(&mut data[40..], 0);
+    assert!(parse(&data).unwrap().is_empty());
+}
@@ -219,6 +219,13 @@ impl<'si, R: Read> Reader<'si, R> {
         Ok(result)
     }
 
+    /// Obtains the stamp of the file read from the header.
+    ///
+    /// A GCDA file has the same stamp as the GCNO file produced by the same compilation.
+    pub fn stamp(&self) -> u32 {
+        self.stamp
+    }
+
     /// Parses the content of the reader, to produce a [`Gcov`] structure.
     ///
     /// # Errors
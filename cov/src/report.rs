@@ -7,15 +7,17 @@
 //! [`Report`]: ./struct.Report.html
 
 #[cfg(feature = "serde")]
-use intern::{Interner, SerializeWithInterner};
-use intern::Symbol;
+use intern::SerializeWithInterner;
+use intern::{Interner, Symbol};
 use raw::{ArcAttr, BlockAttr};
 use utils::tuple_4_add;
 
+use rustc_demangle::demangle;
 #[cfg(feature = "serde")]
 use serde::{Serialize, Serializer};
 
-use std::collections::{BTreeMap, HashMap};
+use std::cmp::max;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 derive_serialize_with_interner! {
     /// A coverage report, generated from a [`Graph`].
@@ -29,6 +31,151 @@ derive_serialize_with_interner! {
     }
 }
 
+impl Report {
+    /// Records `tag` as a test covering every line and function in `executed`.
+    ///
+    /// `executed` is typically obtained from the same GCNO files as `self`, but with the GCDA of a single test only. Lines
+    /// and functions absent from `self` are ignored. A line or function is tagged at most once, even if the test covers
+    /// it in several configurations.
+    pub fn add_covered_by(&mut self, executed: &Executed, tag: Symbol) {
+        for &(filename, line_number) in &executed.lines {
+            let line = self.files.get_mut(&filename).and_then(|file| file.lines.get_mut(&line_number));
+            match line {
+                Some(line) if !line.covered_by.contains(&tag) => line.covered_by.push(tag),
+                _ => {},
+            }
+        }
+        for (&filename, file) in &mut self.files {
+            for function in &mut file.functions {
+                let identity = (filename, function.name, function.line, function.configuration);
+                if !function.covered_by.contains(&tag) && executed.functions.contains(&identity) {
+                    function.covered_by.push(tag);
+                }
+            }
+        }
+    }
+
+    /// Lists the lines and functions which have been executed in this report.
+    pub fn executed(&self) -> Executed {
+        let mut executed = Executed::default();
+        for (&filename, file) in &self.files {
+            executed.lines.extend(file.lines.iter().filter(|&(_, line)| line.count > 0).map(|(&line_number, _)| (filename, line_number)));
+            let functions = file.functions.iter().filter(|f| f.summary.entry_count > 0);
+            executed.functions.extend(functions.map(|f| (filename, f.name, f.line, f.configuration)));
+        }
+        executed
+    }
+
+    /// Fills in the [`demangled_name`] and [`generic_name`] of every function.
+    ///
+    /// [`demangled_name`]: ./struct.Function.html#structfield.demangled_name
+    /// [`generic_name`]: ./struct.Function.html#structfield.generic_name
+    pub fn demangle_functions(&mut self, interner: &Interner) {
+        for file in self.files.values_mut() {
+            for function in &mut file.functions {
+                function.demangled_name = format!("{:#}", demangle(&interner[function.name]));
+                function.generic_name = strip_generic_args(&function.demangled_name);
+            }
+        }
+    }
+}
+
+/// The lines and functions executed by a program, used to attribute them to tests by [`Report::add_covered_by()`].
+///
+/// [`Report::add_covered_by()`]: ./struct.Report.html#method.add_covered_by
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Executed {
+    /// The filename and line number of every executed line.
+    pub lines: HashSet<(Symbol, u32)>,
+    /// The filename, name, line number and configuration of every executed function.
+    pub functions: HashSet<(Symbol, Symbol, u32, Option<Symbol>)>,
+}
+
+#[test]
+fn test_add_covered_by() {
+    let mut interner = Interner::new();
+    let filename = interner.intern("x.rs");
+    let name = interner.intern("x::f");
+    let test_a = interner.intern("tests::a");
+    let test_b = interner.intern("tests::b");
+
+    let mut file = File::default();
+    file.lines.insert(1, Line::default());
+    file.lines.insert(2, Line {
+        count: 4,
+        ..Line::default()
+    });
+    file.functions.push(Function {
+        name,
+        line: 1,
+        ..Function::default()
+    });
+    let mut report = Report::default();
+    report.files.insert(filename, file);
+
+    let mut executed = Executed::default();
+    executed.lines.insert((filename, 1));
+    executed.lines.insert((filename, 3));
+    executed.functions.insert((filename, name, 1, None));
+    report.add_covered_by(&executed, test_a);
+    report.add_covered_by(&executed, test_a);
+    executed.lines.clear();
+    report.add_covered_by(&executed, test_b);
+
+    let file = &report.files[&filename];
+    assert_eq!(file.lines[&1].covered_by, vec![test_a]);
+    assert_eq!(file.lines[&2].covered_by, vec![]);
+    assert!(!file.lines.contains_key(&3));
+    assert_eq!(file.functions[0].covered_by, vec![test_a, test_b]);
+
+    let executed = report.executed();
+    assert_eq!(executed.lines.into_iter().collect::<Vec<_>>(), vec![(filename, 2)]);
+    assert!(executed.functions.is_empty());
+}
+
+/// Removes the generic arguments from a demangled name, e.g. `x::largest::<i32>` becomes `x::largest`, and
+/// `<alloc::vec::Vec<u8> as core::ops::drop::Drop>::drop` becomes `<alloc::vec::Vec as core::ops::drop::Drop>::drop`.
+fn strip_generic_args(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        // A `<` starting a path segment is a qualified path like `<T as Trait>`, which should be kept.
+        let is_generic_args = c == '<' && (result.ends_with("::") || result.ends_with(|p: char| p.is_alphanumeric() || p == '_' || p == '}'));
+        if !is_generic_args {
+            result.push(c);
+            continue;
+        }
+        if result.ends_with("::") {
+            let len = result.len() - 2;
+            result.truncate(len);
+        }
+        let mut depth = 1;
+        let mut prev = c;
+        while depth > 0 {
+            let c = match chars.next() {
+                Some(c) => c,
+                None => break,
+            };
+            match c {
+                '<' => depth += 1,
+                '>' if prev != '-' => depth -= 1,
+                _ => {},
+            }
+            prev = c;
+        }
+    }
+    result
+}
+
+#[test]
+fn test_strip_generic_args() {
+    assert_eq!(strip_generic_args("x::largest::<i32>"), "x::largest");
+    assert_eq!(strip_generic_args("core::option::Option<T>::unwrap"), "core::option::Option::unwrap");
+    assert_eq!(strip_generic_args("<alloc::vec::Vec<u8> as core::ops::drop::Drop>::drop"), "<alloc::vec::Vec as core::ops::drop::Drop>::drop");
+    assert_eq!(strip_generic_args("x::apply::<fn() -> u8, (i32, &[u8])>::{closure#0}"), "x::apply::{closure#0}");
+    assert_eq!(strip_generic_args("main"), "main");
+}
+
 derive_serialize_with_interner! {
     /// Coverage information about a source file.
     #[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
@@ -39,6 +186,11 @@ derive_serialize_with_interner! {
 
         /// Functions in the file.
         pub functions: Vec<Function>,
+
+        /// Regions of code in the file, sorted by their start positions. Empty unless the coverage data has column
+        /// information, e.g. the source-based coverage.
+        #[cfg_attr(feature="serde", serde(default))]
+        pub regions: Vec<Region>,
     }
 }
 
@@ -55,6 +207,9 @@ impl File {
                 (s.branches_count, s.branches_executed, s.branches_taken, (s.entry_count > 0) as usize)
             })
             .fold((0, 0, 0, 0), tuple_4_add);
+        let (panic_paths_count, panic_paths_taken) = self.functions
+            .iter()
+            .fold((0, 0), |(count, taken), f| (count + f.summary.panic_paths_count, taken + f.summary.panic_paths_taken));
         FileSummary {
             lines_count,
             lines_covered,
@@ -63,8 +218,40 @@ impl File {
             branches_taken,
             functions_count,
             functions_called,
+            panic_paths_count,
+            panic_paths_taken,
         }
     }
+
+    /// Groups the monomorphized instances of every generic function by their definition, i.e. by the [`generic_name`]
+    /// and the source position. Functions which are not demangled are not grouped with others.
+    ///
+    /// The groups are sorted by the source position.
+    ///
+    /// [`generic_name`]: ./struct.Function.html#structfield.generic_name
+    pub fn function_groups(&self) -> Vec<FunctionGroup> {
+        let mut groups = Vec::<FunctionGroup>::new();
+        let mut indices = HashMap::new();
+        for function in &self.functions {
+            let distinct_name = if function.generic_name.is_empty() { Some(function.name) } else { None };
+            let key = (&*function.generic_name, distinct_name, function.line, function.column);
+            let index = *indices.entry(key).or_insert_with(|| {
+                groups.push(FunctionGroup {
+                    generic_name: &function.generic_name,
+                    line: function.line,
+                    column: function.column,
+                    instances: Vec::new(),
+                    summary: FunctionSummary::default(),
+                });
+                groups.len() - 1
+            });
+            let group = &mut groups[index];
+            group.instances.push(function);
+            group.summary.merge_instance(&function.summary);
+        }
+        groups.sort_by_key(|group| (group.line, group.column));
+        groups
+    }
 }
 
 derive_serialize_with_interner! {
@@ -82,6 +269,49 @@ derive_serialize_with_interner! {
 
         /// List of branches this line will lead to.
         pub branches: Vec<Branch>,
+
+        /// Tests which have executed this line. Empty unless the coverage is collected per test.
+        #[cfg_attr(feature="serde", serde(default))]
+        pub covered_by: Vec<Symbol>,
+
+        /// Statistics of the loops whose header starts at this line, if any. Only available for GCNO/GCDA.
+        #[cfg_attr(feature="serde", serde(default))]
+        pub loop_stats: Option<Loop>,
+
+        /// Configurations (e.g. feature sets or target triples) which have executed this line, in the order of the
+        /// symbols. Empty unless the GCNO/GCDA files are merged with [`Graph::merge_with_configuration()`]. The
+        /// [`count`] is then the sum of the counts in every configuration.
+        ///
+        /// [`Graph::merge_with_configuration()`]: ../graph/struct.Graph.html#method.merge_with_configuration
+        /// [`count`]: #structfield.count
+        #[cfg_attr(feature="serde", serde(default))]
+        pub configurations: Vec<Symbol>,
+    }
+}
+
+/// Execution statistics of the natural loops whose header block starts at a line. If several loops share the same
+/// line (e.g. the instances of a generic function), their statistics are added up.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Loop {
+    /// Number of times the loop is entered from outside.
+    pub entry_count: u64,
+
+    /// Number of times the loop jumps back to its header, i.e. the total count of the back-edges.
+    ///
+    /// For loops tested at the top (e.g. `while` and `for` loops in unoptimized builds), this is the number of times
+    /// the loop body has run.
+    pub iterations: u64,
+}
+
+impl Loop {
+    /// Computes the mean number of iterations per entry. Zero if the loop is never entered.
+    pub fn mean_iterations(&self) -> f64 {
+        if self.entry_count == 0 {
+            0.0
+        } else {
+            self.iterations as f64 / self.entry_count as f64
+        }
     }
 }
 
@@ -107,14 +337,55 @@ derive_serialize_with_interner! {
     }
 }
 
+/// Coverage information about a region of code, which may span multiple lines or only part of a line.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Region {
+    /// The line number where this region starts.
+    pub line_start: u32,
+
+    /// The column number where this region starts.
+    pub column_start: u32,
+
+    /// The line number where this region ends (inclusive).
+    pub line_end: u32,
+
+    /// The column number where this region ends (exclusive).
+    pub column_end: u32,
+
+    /// Number of times this region is executed.
+    pub count: u64,
+}
+
+impl Region {
+    /// Checks whether the position is inside this region.
+    pub fn contains(&self, line: u32, column: u32) -> bool {
+        (self.line_start, self.column_start) <= (line, column) && (line, column) < (self.line_end, self.column_end)
+    }
+}
+
 derive_serialize_with_interner! {
     /// Coverage information about a function.
-    #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+    #[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
     #[cfg_attr(feature="serde", derive(Serialize, Deserialize))]
     pub struct Function {
         /// Name of the function.
         pub name: Symbol,
 
+        /// Demangled name of the function without the hash, e.g. `x::largest::<i32>`. Empty unless
+        /// [`Report::demangle_functions()`] is called.
+        ///
+        /// [`Report::demangle_functions()`]: ./struct.Report.html#method.demangle_functions
+        #[cfg_attr(feature="serde", serde(default))]
+        pub demangled_name: String,
+
+        /// Demangled name of the generic definition, i.e. the [`demangled_name`] without generic arguments, e.g.
+        /// `x::largest`. Every monomorphized instance of a generic function shares the same `generic_name`.
+        ///
+        /// [`demangled_name`]: #structfield.demangled_name
+        #[cfg_attr(feature="serde", serde(default))]
+        pub generic_name: String,
+
         /// The line number where this function is defined. Zero if missing.
         pub line: u32,
 
@@ -123,6 +394,17 @@ derive_serialize_with_interner! {
 
         /// Summary about this function.
         pub summary: FunctionSummary,
+
+        /// Tests which have called this function. Empty unless the coverage is collected per test.
+        #[cfg_attr(feature="serde", serde(default))]
+        pub covered_by: Vec<Symbol>,
+
+        /// The configuration this function instance is built under, if the GCNO is merged with
+        /// [`Graph::merge_with_configuration()`].
+        ///
+        /// [`Graph::merge_with_configuration()`]: ../graph/struct.Graph.html#method.merge_with_configuration
+        #[cfg_attr(feature="serde", serde(default))]
+        pub configuration: Option<Symbol>,
     }
 }
 
@@ -150,6 +432,100 @@ pub struct FunctionSummary {
 
     /// Number of branches that has been taken.
     pub branches_taken: usize,
+
+    /// Number of branches on panic and unwind paths. Zero unless the graph is reported with
+    /// [`exclude_panic_paths`], in which case they are not counted in [`branches_count`].
+    ///
+    /// [`exclude_panic_paths`]: ../graph/struct.ReportOptions.html#structfield.exclude_panic_paths
+    /// [`branches_count`]: #structfield.branches_count
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub panic_paths_count: usize,
+
+    /// Number of branches on panic and unwind paths that has been taken.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub panic_paths_taken: usize,
+
+    /// The cyclomatic complexity of the function, i.e. the number of linearly independent paths through it.
+    ///
+    /// For GCNO/GCDA, this is computed from the control-flow graph as *E* − *N* + 2, where *E* is the number of real
+    /// (non-fake) arcs and *N* the number of blocks. For the source-based coverage, this is the number of branch regions
+    /// plus 1, which requires the program to be built with branch coverage.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub complexity: usize,
+
+    /// Total number of times the basic blocks of the function have been executed, i.e. the sum of their counts. For the
+    /// source-based coverage, this is the sum of the counts of the code regions.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub block_executions: u64,
+}
+
+impl FunctionSummary {
+    /// Merges the summary of another monomorphized instance of the same generic function. The call counts are added up,
+    /// while the block and branch statistics take the maximum among the instances, since the instances may be compiled
+    /// into different basic blocks.
+    fn merge_instance(&mut self, other: &FunctionSummary) {
+        self.blocks_count = max(self.blocks_count, other.blocks_count);
+        self.blocks_executed = max(self.blocks_executed, other.blocks_executed);
+        self.entry_count += other.entry_count;
+        self.exit_count += other.exit_count;
+        self.branches_count = max(self.branches_count, other.branches_count);
+        self.branches_executed = max(self.branches_executed, other.branches_executed);
+        self.branches_taken = max(self.branches_taken, other.branches_taken);
+        self.panic_paths_count = max(self.panic_paths_count, other.panic_paths_count);
+        self.panic_paths_taken = max(self.panic_paths_taken, other.panic_paths_taken);
+        self.complexity = max(self.complexity, other.complexity);
+        self.block_executions += other.block_executions;
+    }
+
+    /// Computes the CRAP (Change Risk Anti-Patterns) score of the function, `complexity² × (1 − coverage)³ +
+    /// complexity`, where the coverage is the ratio of executed basic blocks.
+    ///
+    /// A high score means the function is both complex and poorly tested. A fully covered function scores its
+    /// complexity, while an uncovered one scores `complexity² + complexity`.
+    pub fn crap(&self) -> f64 {
+        let complexity = self.complexity as f64;
+        let coverage = if self.blocks_count == 0 {
+            (self.entry_count > 0) as u8 as f64
+        } else {
+            self.blocks_executed as f64 / self.blocks_count as f64
+        };
+        complexity * complexity * (1.0 - coverage).powi(3) + complexity
+    }
+}
+
+#[test]
+fn test_crap() {
+    let summary = |complexity, blocks_executed| FunctionSummary {
+        blocks_count: 10,
+        blocks_executed,
+        complexity,
+        ..FunctionSummary::default()
+    };
+    assert_eq!(summary(1, 10).crap(), 1.0);
+    assert_eq!(summary(5, 10).crap(), 5.0);
+    assert_eq!(summary(5, 0).crap(), 30.0);
+    assert_eq!(summary(4, 5).crap(), 6.0);
+}
+
+/// The monomorphized instances of a generic function, grouped by [`File::function_groups()`].
+///
+/// [`File::function_groups()`]: ./struct.File.html#method.function_groups
+#[derive(Clone, Debug)]
+pub struct FunctionGroup<'a> {
+    /// Demangled name of the generic definition.
+    pub generic_name: &'a str,
+
+    /// The line number where the function is defined. Zero if missing.
+    pub line: u32,
+
+    /// The column number where the function is defined. Zero if missing.
+    pub column: u32,
+
+    /// The instances of the generic function.
+    pub instances: Vec<&'a Function>,
+
+    /// Summary of all instances merged together.
+    pub summary: FunctionSummary,
 }
 
 /// Statistical summary of a file.
@@ -176,8 +552,19 @@ pub struct FileSummary {
 
     /// Number of functions that has been called.
     pub functions_called: usize,
+
+    /// Number of branches on panic and unwind paths in functions defined in this file. Zero unless these are excluded
+    /// from [`branches_count`].
+    ///
+    /// [`branches_count`]: #structfield.branches_count
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub panic_paths_count: usize,
+
+    /// Number of branches on panic and unwind paths that has been taken.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub panic_paths_taken: usize,
 }
 
 derive_serialize_with_interner! {
-    direct: FunctionSummary, FileSummary
+    direct: Region, Loop, FunctionSummary, FileSummary
 }
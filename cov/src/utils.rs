@@ -27,16 +27,19 @@ impl<'a, K: 'a, V: Default + 'a> EntryExt<'a> for hash_map::Entry<'a, K, V> {
     }
 }
 
-/// Computes the sum of two 3-tuples.
-pub fn tuple_3_add<A: Add<X>, B: Add<Y>, C: Add<Z>, X, Y, Z>(left: (A, B, C), right: (X, Y, Z)) -> (A::Output, B::Output, C::Output) {
-    (left.0 + right.0, left.1 + right.1, left.2 + right.2)
-}
-
 /// Computes the sum of two 4-tuples.
 pub fn tuple_4_add<A: Add<X>, B: Add<Y>, C: Add<Z>, D: Add<W>, X, Y, Z, W>(left: (A, B, C, D), right: (X, Y, Z, W)) -> (A::Output, B::Output, C::Output, D::Output) {
     (left.0 + right.0, left.1 + right.1, left.2 + right.2, left.3 + right.3)
 }
 
+/// Computes the sum of two 5-tuples.
+pub fn tuple_5_add<A: Add<X>, B: Add<Y>, C: Add<Z>, D: Add<W>, E: Add<V>, X, Y, Z, W, V>(
+    left: (A, B, C, D, E),
+    right: (X, Y, Z, W, V),
+) -> (A::Output, B::Output, C::Output, D::Output, E::Output) {
+    (left.0 + right.0, left.1 + right.1, left.2 + right.2, left.3 + right.3, left.4 + right.4)
+}
+
 /// Adds the `into_string_lossy` method to `OsString` and `Vec<u8>`.
 pub trait IntoStringLossy {
     /// Consumes the ownership and converts the string-like object into a real string. Unconvertible characters are
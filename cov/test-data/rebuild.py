@@ -25,6 +25,11 @@ BUILDERS = {
         cmd=['rustc', '-g', '-Zprofile'],
         gcov='gcov'
     ),
+    '.llvm': Builder(
+        ext='.rs',
+        cmd=['rustc', '-Cinstrument-coverage', '-Clink-dead-code', '-Zcoverage-options=branch', '--emit=obj,link'],
+        gcov=None
+    ),
 }
 
 def build():
@@ -45,6 +50,9 @@ def build():
         src_path = os.path.join('src', base + builder.ext)
         dst_path = os.path.join(out_dir, 'x' + builder.ext)
         os.link(src_path, dst_path)
+        if not builder.gcov:
+            build_llvm(directory, out_dir, builder)
+            continue
         subprocess.run(builder.cmd + [
             '-o', 'x',
             'x' + builder.ext,
@@ -99,6 +107,28 @@ def build():
             os.rename(src_path, dst_path)
 
 
+def build_llvm(directory, out_dir, builder):
+    subprocess.run(builder.cmd + [
+        '--remap-path-prefix=' + os.path.abspath(out_dir) + '=',
+        '-o', 'x',
+        'x' + builder.ext,
+    ], cwd=out_dir, check=True, env=dict(os.environ, RUSTC_BOOTSTRAP='1'))
+    subprocess.run(['./x'], cwd=out_dir, check=True, stdout=subprocess.DEVNULL, env=dict(os.environ, LLVM_PROFILE_FILE='x.profraw'))
+    subprocess.run([
+        'objcopy',
+        '--strip-all',
+        '--only-section=__llvm_covmap',
+        '--only-section=__llvm_covfun',
+        '--only-section=__llvm_prf_names',
+        'x.o', 'x.covmap',
+    ], cwd=out_dir, check=True)
+
+    for filename in ('x.covmap', 'x.profraw'):
+        src_path = os.path.join(out_dir, filename)
+        dst_path = os.path.join(directory, filename)
+        os.rename(src_path, dst_path)
+
+
 def clean():
     for filename in os.listdir('.'):
         if os.path.isdir(filename):
@@ -110,6 +140,14 @@ def clean():
                 os.remove(os.path.join(filename, 'x.gcda'))
             except FileNotFoundError:
                 pass
+            try:
+                os.remove(os.path.join(filename, 'x.covmap'))
+            except FileNotFoundError:
+                pass
+            try:
+                os.remove(os.path.join(filename, 'x.profraw'))
+            except FileNotFoundError:
+                pass
             try:
                 shutil.rmtree(os.path.join(filename, 'build'))
             except FileNotFoundError:
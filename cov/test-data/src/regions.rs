@@ -0,0 +1,31 @@
+fn classify(x: i32) -> &'static str {
+    if x > 0 && x < 10 {
+        "small"
+    } else if x >= 10 {
+        "big"
+    } else {
+        "negative"
+    }
+}
+
+fn largest<T: PartialOrd + Copy>(items: &[T]) -> T {
+    let mut largest = items[0];
+    for &item in items {
+        if item > largest {
+            largest = item;
+        }
+    }
+    largest
+}
+
+fn unused() -> u32 {
+    42
+}
+
+fn main() {
+    for i in -1..3 {
+        println!("{}", classify(i));
+    }
+    println!("{}", largest(&[1, 5, 2]));
+    println!("{}", largest(&[0.5, 0.25]));
+}
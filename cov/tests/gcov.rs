@@ -1,7 +1,8 @@
 //! Test against output of GCOVR and LCOV.
 //!
 //! This test program will generate a report for `test-data/*/x.{gcno,gcda}`, and compare against `test-data/*/x.json`.
-//! It is considered failure if the report differs.
+//! It is considered failure if the report differs. For the `*.llvm` tests, the report is generated from
+//! `test-data/*.llvm/x.{covmap,profraw}` instead.
 //!
 //! The GCNO and GCDA files, along with GCOVR and LCOV output can be regenerated by running:
 //!
@@ -29,6 +30,7 @@
 //! | `clang++`  | clang 3.4+            |
 //! | `gcov`     | LLVM 3.4+ or GCC 4.6- |
 //! | `rustc`    | Rust nightly          |
+//! | `objcopy`  | GNU binutils          |
 //! | `lcov`     | LCOV 1.13+            |
 //! | `genhtml`  | LCOV 1.13+            |
 //! | `gcovr`    | GCOVR 3.3+            |
@@ -36,7 +38,8 @@
 //! ### Creating a new test
 //!
 //! 1. Write the new source code in `test-data/src`. Must be a single file, of the form `filename.cpp` or `filename.rs`.
-//! 2. Create the folder `test-data/filename.clang/`, `test-data/filename.gcc7/` or `test-data/filename.rustc/`.
+//! 2. Create the folder `test-data/filename.clang/`, `test-data/filename.gcc7/`, `test-data/filename.rustc/` or
+//!    `test-data/filename.llvm/`.
 //! 3. Run `rebuild.py` (no need to clean)
 //! 4. Fill in `test-data/filename.*/x.json`.
 //! 5. Run `cargo test` and update the JSON file or the code.
@@ -63,7 +66,7 @@ fn main() {
 }
 
 fn run() -> io::Result<()> {
-    let allowed_extensions = [OsStr::new("gcc7"), OsStr::new("clang"), OsStr::new("rustc")];
+    let allowed_extensions = [OsStr::new("gcc7"), OsStr::new("clang"), OsStr::new("rustc"), OsStr::new("llvm")];
     let mut failed_tests = 0;
 
     let stdout = StandardStream::stdout(ColorChoice::Auto);
@@ -101,6 +104,10 @@ fn run() -> io::Result<()> {
 
 /// Generates a report, and compare with the expected report. Returns both reports a JSON values.
 fn test(path: &Path) -> Result<(Value, Value)> {
+    if path.extension() == Some(OsStr::new("llvm")) {
+        return test_llvm(path);
+    }
+
     let mut interner = Interner::new();
     let mut graph = Graph::new();
 
@@ -112,7 +119,8 @@ fn test(path: &Path) -> Result<(Value, Value)> {
     graph.merge(Gcov::open(&gcda_path, &mut interner)?)?;
 
     graph.analyze();
-    let report = graph.report();
+    let mut report = graph.report();
+    report.demangle_functions(&interner);
     let actual_report = to_value(report.with_interner(&interner))?;
 
     let mut report_path = gcda_path;
@@ -122,6 +130,22 @@ fn test(path: &Path) -> Result<(Value, Value)> {
     Ok((actual_report, expected_report))
 }
 
+/// Generates a report from the source-based coverage, and compare with the expected report.
+fn test_llvm(path: &Path) -> Result<(Value, Value)> {
+    let mut interner = Interner::new();
+    let mut coverage = llvm::Coverage::default();
+
+    coverage.merge_object_file(path.join("x.covmap"), &mut interner)?;
+    coverage.merge_profraw_file(path.join("x.profraw"))?;
+
+    let mut report = coverage.report();
+    report.demangle_functions(&interner);
+    let actual_report = to_value(report.with_interner(&interner))?;
+    let expected_report = from_reader(File::open(path.join("x.json"))?)?;
+
+    Ok((actual_report, expected_report))
+}
+
 fn print_test_result<W: Write + WriteColor>(mut lock: W, result: Result<(Value, Value)>) -> io::Result<bool> {
     Ok(match result {
         Ok((actual_report, expected_report)) => {